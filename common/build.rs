@@ -1,19 +1,19 @@
-use std::error::Error;
+use std::{env, error::Error, path::PathBuf};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let iter = std::fs::read_dir("proto")?.filter_map(|x| x.ok());
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    let protos = std::fs::read_dir("proto")?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|ext| ext == "proto"))
+        .collect::<Vec<_>>();
 
-    for entry in iter {
-        if let Some(file) = entry.file_name().to_str() {
-            if file.ends_with(".proto") {
-                tonic_build::configure()
-                    .build_client(true)
-                    .build_server(true)
-                    .build_transport(false)
-                    .compile(&[file], &[entry.path().parent().unwrap()])?;
-            }
-        }
-    }
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .build_transport(false)
+        .file_descriptor_set_path(out_dir.join("monitor_descriptor.bin"))
+        .compile(&protos, &["proto"])?;
 
     Ok(())
 }
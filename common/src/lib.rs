@@ -1,3 +1,7 @@
 pub mod monitoring {
     tonic::include_proto!("monitor");
-}
\ No newline at end of file
+
+    /// Encoded descriptors of every message and service in `proto/`, used for server reflection.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("monitor_descriptor");
+}
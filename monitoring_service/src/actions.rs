@@ -0,0 +1,277 @@
+use std::{
+    collections::BTreeMap, error::Error, io::ErrorKind, process::Stdio, sync::Arc, time::Duration,
+};
+
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    sync::{mpsc, Semaphore},
+};
+
+use crate::update::UPDATE_ENV;
+
+/// Chunks of output buffered for a slow client before the action waits for it.
+const BUFFERED_OUTPUT: usize = 64;
+/// How long output left in the pipes is still read after the action exits.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An `[actions.<name>]` entry of the config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionConfig {
+    /// Program and its arguments, run without a shell.
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    /// Always the last one.
+    Exit(Exit),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exit {
+    /// Missing when the action was killed by a signal, including on timeout, or could not
+    /// be waited for.
+    pub code: Option<i32>,
+    pub timed_out: bool,
+    /// Set when waiting for the action failed, how it ended is unknown then.
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.error, self.timed_out, self.code) {
+            (Some(error), _, _) => write!(f, "could not be waited for: {error}"),
+            (None, true, _) => write!(f, "timed out"),
+            (None, false, Some(code)) => write!(f, "exited with {code}"),
+            (None, false, None) => write!(f, "killed by a signal"),
+        }
+    }
+}
+
+/// Actions declared in the config. Nothing else can be run.
+#[derive(Debug)]
+pub struct Actions {
+    actions: BTreeMap<String, ActionConfig>,
+    running: Arc<Semaphore>,
+    max_running: usize,
+}
+
+impl Actions {
+    pub fn new(
+        actions: &BTreeMap<String, ActionConfig>,
+        max_running: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        if max_running == 0 {
+            return Err("max_running_actions has to be at least 1".into());
+        }
+        for (name, action) in actions {
+            if action.command.is_empty() {
+                return Err(format!("action {name} has no command").into());
+            }
+            if action.timeout_secs == 0 {
+                return Err(format!("action {name} needs a positive timeout").into());
+            }
+        }
+
+        Ok(Actions {
+            actions: actions.clone(),
+            running: Arc::new(Semaphore::new(max_running)),
+            max_running,
+        })
+    }
+
+    /// Names and descriptions.
+    pub fn list(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.actions
+            .iter()
+            .map(|(name, x)| (name.as_str(), x.description.as_str()))
+    }
+
+    /// Starts an action and streams its output. `finished` is called with the exit once the
+    /// action is done, even if the receiver was dropped.
+    pub fn start(
+        &self,
+        name: &str,
+        finished: impl FnOnce(Exit) + Send + 'static,
+    ) -> std::io::Result<mpsc::Receiver<Output>> {
+        let action = self.actions.get(name).ok_or_else(|| {
+            std::io::Error::new(ErrorKind::NotFound, format!("no action named {name}"))
+        })?;
+        let permit = self.running.clone().try_acquire_owned().map_err(|_| {
+            std::io::Error::new(
+                ErrorKind::WouldBlock,
+                format!("{} actions are already running", self.max_running),
+            )
+        })?;
+
+        let mut child = Command::new(&action.command[0])
+            .args(&action.command[1..])
+            .env_remove(UPDATE_ENV)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (sender, receiver) = mpsc::channel(BUFFERED_OUTPUT);
+        let mut stdout = forward(child.stdout.take(), sender.clone(), Output::Stdout);
+        let mut stderr = forward(child.stderr.take(), sender.clone(), Output::Stderr);
+        let timeout = Duration::from_secs(action.timeout_secs);
+
+        tokio::spawn(async move {
+            let exit = match tokio::time::timeout(timeout, child.wait()).await {
+                Ok(Ok(status)) => Exit {
+                    code: status.code(),
+                    timed_out: false,
+                    error: None,
+                },
+                Ok(Err(e)) => Exit {
+                    code: None,
+                    timed_out: false,
+                    error: Some(e.to_string()),
+                },
+                Err(_) => {
+                    let _ = child.kill().await;
+                    Exit {
+                        code: None,
+                        timed_out: true,
+                        error: None,
+                    }
+                }
+            };
+            // Children of the action may keep the pipes open, they are not waited for
+            let drain = async {
+                let _ = (&mut stdout).await;
+                let _ = (&mut stderr).await;
+            };
+            let _ = tokio::time::timeout(DRAIN_TIMEOUT, drain).await;
+            stdout.abort();
+            stderr.abort();
+            drop(permit);
+
+            finished(exit.clone());
+            let _ = sender.send(Output::Exit(exit)).await;
+        });
+        Ok(receiver)
+    }
+}
+
+fn forward(
+    pipe: Option<impl AsyncRead + Unpin + Send + 'static>,
+    sender: mpsc::Sender<Output>,
+    wrap: fn(Vec<u8>) -> Output,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let Some(mut pipe) = pipe else {
+            return;
+        };
+        let mut buffer = vec![0; 4096];
+
+        while let Ok(read @ 1..) = pipe.read(&mut buffer).await {
+            if sender.send(wrap(buffer[..read].to_vec())).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        io::ErrorKind,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{ActionConfig, Actions, Exit, Output};
+
+    fn action(script: &str, timeout_secs: u64) -> ActionConfig {
+        ActionConfig {
+            command: ["sh", "-c", script].map(ToOwned::to_owned).to_vec(),
+            description: String::new(),
+            timeout_secs,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_and_exit_are_streamed() {
+        let actions = Actions::new(
+            &BTreeMap::from([(
+                "fail".to_owned(),
+                action("echo out; echo err >&2; exit 3", 5),
+            )]),
+            1,
+        )
+        .unwrap();
+        let finished = Arc::new(Mutex::new(None));
+        let reported = finished.clone();
+
+        let mut receiver = actions
+            .start("fail", move |x| *reported.lock().unwrap() = Some(x))
+            .unwrap();
+        let mut outputs = vec![];
+        while let Some(output) = receiver.recv().await {
+            outputs.push(output);
+        }
+
+        let exit = Exit {
+            code: Some(3),
+            timed_out: false,
+            error: None,
+        };
+        assert!(outputs.contains(&Output::Stdout(b"out\n".to_vec())));
+        assert!(outputs.contains(&Output::Stderr(b"err\n".to_vec())));
+        assert_eq!(outputs.last(), Some(&Output::Exit(exit.clone())));
+        assert_eq!(*finished.lock().unwrap(), Some(exit));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_concurrency_limit() {
+        let actions = Actions::new(
+            &BTreeMap::from([("hang".to_owned(), action("sleep 10", 1))]),
+            1,
+        )
+        .unwrap();
+
+        let mut receiver = actions.start("hang", |_| {}).unwrap();
+        let busy = actions.start("hang", |_| {}).unwrap_err();
+        assert_eq!(busy.kind(), ErrorKind::WouldBlock);
+        let unknown = actions.start("rm -rf /", |_| {}).unwrap_err();
+        assert_eq!(unknown.kind(), ErrorKind::NotFound);
+
+        let exit = receiver.recv().await.unwrap();
+        assert_eq!(
+            exit,
+            Output::Exit(Exit {
+                code: None,
+                timed_out: true,
+                error: None,
+            })
+        );
+        assert!(actions.start("hang", |_| {}).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_actions_are_rejected() {
+        let empty = ActionConfig {
+            command: vec![],
+            ..action("", 1)
+        };
+
+        assert!(Actions::new(&BTreeMap::from([("a".to_owned(), empty)]), 1).is_err());
+        assert!(Actions::new(&BTreeMap::from([("a".to_owned(), action("true", 0))]), 1).is_err());
+        assert!(Actions::new(&BTreeMap::new(), 0).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+
+use common::monitoring::Pack;
+use serde::{Deserialize, Serialize};
+
+use crate::events::{Events, Severity};
+
+/// Readings above which an event is raised, `[alerts]`. Unset thresholds are not checked.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Mean usage of all cores, in percent.
+    pub cpu_usage_percent: Option<f64>,
+    /// Hottest thermal zone or CPU sensor, in degrees Celsius.
+    pub temperature_celsius: Option<f64>,
+    /// One minute load average.
+    pub load_average: Option<f64>,
+}
+
+impl AlertsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for reading in Reading::ALL {
+            let Some(threshold) = reading.threshold(self) else {
+                continue;
+            };
+            if !threshold.is_finite() || threshold < 0.0 {
+                return Err(format!("the {reading} threshold must be zero or more"));
+            }
+            if reading == Reading::CpuUsage && threshold > 100.0 {
+                return Err(format!("the {reading} threshold is above 100%"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Reading {
+    CpuUsage,
+    Temperature,
+    LoadAverage,
+}
+
+impl std::fmt::Display for Reading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reading::CpuUsage => write!(f, "CPU usage"),
+            Reading::Temperature => write!(f, "temperature"),
+            Reading::LoadAverage => write!(f, "load average"),
+        }
+    }
+}
+
+impl Reading {
+    const ALL: [Reading; 3] = [
+        Reading::CpuUsage,
+        Reading::Temperature,
+        Reading::LoadAverage,
+    ];
+
+    fn threshold(self, config: &AlertsConfig) -> Option<f64> {
+        match self {
+            Reading::CpuUsage => config.cpu_usage_percent,
+            Reading::Temperature => config.temperature_celsius,
+            Reading::LoadAverage => config.load_average,
+        }
+    }
+
+    /// Missing when the pack has no section to read it from.
+    fn read(self, pack: &Pack) -> Option<f64> {
+        match self {
+            Reading::CpuUsage => {
+                let usage = &pack.cpu.as_ref()?.usage;
+                (!usage.is_empty()).then(|| usage.iter().sum::<f32>() / usage.len() as f32)
+            }
+            Reading::Temperature => {
+                let zones = pack.thermal.iter().flat_map(|x| &x.zones);
+                zones
+                    .filter_map(|x| x.temperature)
+                    .chain(pack.cpu.as_ref().and_then(|x| x.temperature))
+                    .reduce(f32::max)
+            }
+            Reading::LoadAverage => pack.load.as_ref().map(|x| x.load1),
+        }
+        .map(f64::from)
+    }
+
+    fn format(self, value: f64) -> String {
+        match self {
+            Reading::CpuUsage => format!("{value:.1}%"),
+            Reading::Temperature => format!("{value:.1} °C"),
+            Reading::LoadAverage => format!("{value:.2}"),
+        }
+    }
+}
+
+/// Raises a warning when a reading goes above its threshold, and an info event once it is back.
+#[derive(Debug)]
+pub struct Alerts {
+    config: AlertsConfig,
+    events: Events,
+    above: HashSet<Reading>,
+}
+
+impl Alerts {
+    pub fn new(config: AlertsConfig, events: Events) -> Self {
+        Alerts {
+            config,
+            events,
+            above: HashSet::new(),
+        }
+    }
+
+    /// Replaces the thresholds. Readings that stay above their new threshold are not raised again.
+    pub fn configure(&mut self, config: AlertsConfig) {
+        self.above.retain(|x| x.threshold(&config).is_some());
+        self.config = config;
+    }
+
+    pub fn check(&mut self, pack: &Pack) {
+        for reading in Reading::ALL {
+            let (Some(threshold), Some(value)) =
+                (reading.threshold(&self.config), reading.read(pack))
+            else {
+                continue;
+            };
+
+            let (severity, message) = if value > threshold && self.above.insert(reading) {
+                let message = format!(
+                    "The {reading} is {}, above {}",
+                    reading.format(value),
+                    reading.format(threshold)
+                );
+                (Severity::Warning, message)
+            } else if value <= threshold && self.above.remove(&reading) {
+                let message = format!("The {reading} is back to {}", reading.format(value));
+                (Severity::Info, message)
+            } else {
+                continue;
+            };
+            self.events.raise("alerts", severity, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::monitoring::{CpuResponse, LoadResponse, Pack, ThermalResponse, ThermalZone};
+
+    use crate::events::{Events, Severity};
+
+    use super::{Alerts, AlertsConfig};
+
+    fn pack(usage: &[f32], temperature: f32) -> Pack {
+        Pack {
+            cpu: Some(CpuResponse {
+                usage: usage.to_vec(),
+                ..Default::default()
+            }),
+            thermal: Some(ThermalResponse {
+                zones: vec![ThermalZone {
+                    temperature: Some(temperature),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            load: Some(LoadResponse {
+                load1: 0.5,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_thresholds_are_raised_once() {
+        let events = Events::default();
+        let config = AlertsConfig {
+            cpu_usage_percent: Some(90.0),
+            temperature_celsius: Some(80.0),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        let mut alerts = Alerts::new(config.clone(), events.clone());
+
+        alerts.check(&pack(&[20.0, 40.0], 50.0));
+        assert!(events.after(0).is_empty());
+        alerts.check(&pack(&[100.0, 90.0], 85.5));
+        alerts.check(&pack(&[100.0, 90.0], 85.5));
+        // Sections missing from partial packs leave the alerts as they are
+        alerts.check(&Pack::default());
+        alerts.check(&pack(&[50.0, 40.0], 85.0));
+
+        let raised = events.after(0);
+        let messages = raised
+            .iter()
+            .map(|x| x.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "The CPU usage is 95.0%, above 90.0%",
+                "The temperature is 85.5 °C, above 80.0 °C",
+                "The CPU usage is back to 45.0%",
+            ]
+        );
+        assert_eq!(raised[0].severity, Severity::Warning);
+        assert_eq!(raised[2].severity, Severity::Info);
+
+        alerts.configure(AlertsConfig {
+            load_average: Some(0.1),
+            ..config
+        });
+        alerts.check(&pack(&[50.0, 40.0], 85.0));
+        let raised = events.after(3);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].message, "The load average is 0.50, above 0.10");
+
+        let invalid = AlertsConfig {
+            cpu_usage_percent: Some(120.0),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tonic::{Request, Status};
+
+use crate::listen::ConnectionInfo;
+
+/// `[audit]` section of the config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// JSON lines file entries are appended to. Without it they are only printed to stderr and
+    /// cannot be queried.
+    pub path: Option<PathBuf>,
+    /// The log is moved to `<path>.1` once it would grow past this size.
+    pub max_size_bytes: u64,
+    /// Rotated files kept, `<path>.1` being the newest.
+    pub keep: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            path: None,
+            max_size_bytes: 10 << 20,
+            keep: 5,
+        }
+    }
+}
+
+/// A privileged operation, whether it succeeded or not.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Milliseconds since the epoch.
+    pub time_ms: u64,
+    /// Name of the admin token, missing when the caller had none.
+    pub caller: Option<String>,
+    pub peer: Option<String>,
+    /// Name of the RPC.
+    pub operation: String,
+    pub arguments: String,
+    /// `ok` or the error returned to the caller.
+    pub outcome: String,
+}
+
+impl Entry {
+    pub fn new<T, R>(
+        request: &Request<T>,
+        caller: Option<&str>,
+        operation: &str,
+        arguments: String,
+        result: &Result<R, Status>,
+    ) -> Self {
+        Entry {
+            time_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            caller: caller.map(ToOwned::to_owned),
+            peer: request
+                .extensions()
+                .get::<ConnectionInfo>()
+                .map(ToString::to_string),
+            operation: operation.to_owned(),
+            arguments,
+            outcome: match result {
+                Ok(_) => "ok".to_owned(),
+                Err(e) => format!("{:?}: {}", e.code(), e.message()),
+            },
+        }
+    }
+}
+
+/// Which entries a query returns, missing fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Milliseconds since the epoch, both inclusive.
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    pub caller: Option<String>,
+    pub operation: Option<String>,
+    /// Only operations that did not succeed.
+    pub failed_only: bool,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.since_ms.is_none_or(|x| entry.time_ms >= x)
+            && self.until_ms.is_none_or(|x| entry.time_ms <= x)
+            && (self.caller.is_none() || self.caller == entry.caller)
+            && self
+                .operation
+                .as_ref()
+                .is_none_or(|x| *x == entry.operation)
+            && !(self.failed_only && entry.outcome == "ok")
+    }
+}
+
+#[derive(Debug)]
+struct Log {
+    file: File,
+    size: u64,
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+}
+
+fn rotated(path: &Path, generation: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{generation}"));
+    PathBuf::from(path)
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Log {
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.size > 0 && self.size + length > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += length;
+        Ok(())
+    }
+
+    /// Shifts every file one generation back, dropping the oldest.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for generation in (1..self.keep).rev() {
+                let from = rotated(&self.path, generation);
+                match std::fs::rename(&from, rotated(&self.path, generation + 1)) {
+                    Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))?;
+            self.file = open_append(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Oldest first.
+    fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        (1..=self.keep)
+            .rev()
+            .map(|x| rotated(&self.path, x))
+            .chain([self.path.clone()])
+    }
+}
+
+/// Append-only log of privileged operations, shared by the RPCs that perform them.
+#[derive(Debug, Clone, Default)]
+pub struct Audit(Arc<Mutex<Option<Log>>>);
+
+impl Audit {
+    pub fn open(config: &AuditConfig) -> std::io::Result<Self> {
+        let log = match &config.path {
+            Some(path) => {
+                let file = open_append(path)?;
+                Some(Log {
+                    size: file.metadata()?.len(),
+                    file,
+                    path: path.clone(),
+                    max_size: config.max_size_bytes,
+                    keep: config.keep,
+                })
+            }
+            None => None,
+        };
+
+        Ok(Audit(Arc::new(Mutex::new(log))))
+    }
+
+    /// Failing to write does not fail the operation, which already happened.
+    pub fn record(&self, entry: &Entry) {
+        let line = serde_json::to_string(entry).expect("entries are always serializable");
+        eprintln!("audit: {line}");
+
+        if let Some(log) = self.0.lock().unwrap().as_mut() {
+            if let Err(e) = log.write(&line) {
+                eprintln!("Cannot write the audit log: {e}");
+            }
+        }
+    }
+
+    /// The newest `limit` matching entries, oldest first. Fails with `Unsupported` when the log
+    /// is not written to a file.
+    pub fn query(&self, filter: &Filter, limit: usize) -> std::io::Result<Vec<Entry>> {
+        // Rotating while the files are read would skip or repeat entries
+        let log = self.0.lock().unwrap();
+        let Some(log) = log.as_ref() else {
+            return Err(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "the audit log is not written to a file",
+            ));
+        };
+
+        let mut entries = VecDeque::new();
+        for path in log.files() {
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for line in BufReader::new(file).lines() {
+                // A line cut short by a crash is not worth failing the whole query for
+                let Ok(entry) = serde_json::from_str::<Entry>(&line?) else {
+                    continue;
+                };
+                if filter.matches(&entry) {
+                    entries.push_back(entry);
+                    if entries.len() > limit {
+                        entries.pop_front();
+                    }
+                }
+            }
+        }
+        Ok(entries.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{Request, Status};
+
+    use crate::test_util::Fixture;
+
+    use super::{Audit, AuditConfig, Entry, Filter};
+
+    #[test]
+    fn test_entries_are_appended() {
+        let fixture = Fixture::new("audit-append");
+        std::fs::create_dir_all(fixture.path()).unwrap();
+        let path = fixture.path().join("audit.jsonl");
+        let request = Request::new(());
+        let failed: Result<(), _> = Err(Status::permission_denied("PID 1 cannot be signalled"));
+
+        for _ in 0..2 {
+            let audit = Audit::open(&AuditConfig {
+                path: Some(path.clone()),
+                ..Default::default()
+            })
+            .unwrap();
+            let entry = Entry::new(
+                &request,
+                Some("oncall"),
+                "SignalProcess",
+                "1 TERM".to_owned(),
+                &failed,
+            );
+            audit.record(&entry);
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let entries = content
+            .lines()
+            .map(|x| serde_json::from_str::<Entry>(x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].caller.as_deref(), Some("oncall"));
+        assert_eq!(
+            entries[0].outcome,
+            "PermissionDenied: PID 1 cannot be signalled"
+        );
+    }
+
+    #[test]
+    fn test_rotation_and_queries() {
+        let fixture = Fixture::new("audit-rotation");
+        std::fs::create_dir_all(fixture.path()).unwrap();
+        let path = fixture.path().join("audit.jsonl");
+        let audit = Audit::open(&AuditConfig {
+            path: Some(path.clone()),
+            max_size_bytes: 400,
+            keep: 2,
+        })
+        .unwrap();
+        let request = Request::new(());
+
+        for time_ms in 0..20 {
+            let result = match time_ms % 4 {
+                0 => Err(Status::not_found("no process with PID 42")),
+                _ => Ok(()),
+            };
+            let caller = if time_ms % 2 == 0 { "oncall" } else { "deploy" };
+            let mut entry = Entry::new(
+                &request,
+                Some(caller),
+                "SignalProcess",
+                "pid=42 signal=Term".to_owned(),
+                &result,
+            );
+            entry.time_ms = time_ms;
+            audit.record(&entry);
+        }
+
+        let size = |path| std::fs::metadata(path).map(|x| x.len()).unwrap_or_default();
+        assert!(size(path.clone()) <= 400);
+        assert!(size(fixture.path().join("audit.jsonl.2")) > 0);
+        assert!(!fixture.path().join("audit.jsonl.3").exists());
+
+        let times = |filter: &Filter, limit| {
+            audit
+                .query(filter, limit)
+                .unwrap()
+                .iter()
+                .map(|x| x.time_ms)
+                .collect::<Vec<_>>()
+        };
+        let all = times(&Filter::default(), 100);
+        assert!(all.len() < 20);
+        assert_eq!(all.last(), Some(&19));
+        assert!(all.windows(2).all(|x| x[0] < x[1]));
+        assert_eq!(times(&Filter::default(), 2), [18, 19]);
+
+        let filter = Filter {
+            since_ms: Some(12),
+            until_ms: Some(18),
+            caller: Some("oncall".to_owned()),
+            failed_only: true,
+            ..Default::default()
+        };
+        assert_eq!(times(&filter, 100), [12, 16]);
+        let other = Filter {
+            operation: Some("ExecAction".to_owned()),
+            ..Default::default()
+        };
+        assert!(times(&other, 100).is_empty());
+
+        let unwritten = Audit::open(&AuditConfig::default()).unwrap();
+        assert!(unwritten.query(&Filter::default(), 1).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::{collections::HashMap, io::Read};
+
+use serde::Deserialize;
+use tonic::{Request, Status};
+
+/// `[auth]` section of the config. Reading the board stays open to everyone, admin-only data and
+/// operations need `authorization: Bearer <token>` with one of the tokens.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Admin tokens by name, the name identifies the caller in logs.
+    pub tokens: HashMap<String, String>,
+}
+
+/// Compares in time independent of where the first difference is.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 128 random bits as hex, for tokens handed out by the service.
+pub(crate) fn random_token() -> std::io::Result<String> {
+    let mut bytes = [0; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+
+    Ok(hex::encode(bytes))
+}
+
+impl AuthConfig {
+    /// Name of the admin token the request carries, if any.
+    pub fn admin<T>(&self, request: &Request<T>) -> Option<&str> {
+        let header = request.metadata().get("authorization")?.to_str().ok()?;
+        let token = header.strip_prefix("Bearer ")?.trim();
+
+        self.tokens
+            .iter()
+            .find(|(_, x)| !x.is_empty() && same(x.as_bytes(), token.as_bytes()))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Like [`AuthConfig::admin`], but tells a missing token from an unknown one.
+    pub fn require_admin<T>(&self, request: &Request<T>) -> Result<&str, Denied> {
+        if request.metadata().get("authorization").is_none() {
+            return Err(Denied::Missing);
+        }
+        self.admin(request).ok_or(Denied::Unknown)
+    }
+}
+
+/// Why a request needing an admin token is refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    Missing,
+    Unknown,
+}
+
+impl From<Denied> for Status {
+    fn from(denied: Denied) -> Self {
+        match denied {
+            Denied::Missing => Status::unauthenticated("an admin token is required"),
+            Denied::Unknown => Status::permission_denied("unknown token"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{Code, Request, Status};
+
+    use super::{AuthConfig, Denied};
+
+    #[test]
+    fn test_admin_tokens() {
+        let auth = AuthConfig {
+            tokens: [("oncall".to_owned(), "s3cret".to_owned())].into(),
+        };
+        let request = |header: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(header) = header {
+                request
+                    .metadata_mut()
+                    .insert("authorization", header.parse().unwrap());
+            }
+            request
+        };
+
+        assert_eq!(auth.admin(&request(Some("Bearer s3cret"))), Some("oncall"));
+        assert_eq!(auth.admin(&request(Some("Bearer s3cre"))), None);
+        assert_eq!(auth.admin(&request(Some("s3cret"))), None);
+        assert_eq!(auth.require_admin(&request(None)), Err(Denied::Missing));
+        let denied = auth
+            .require_admin(&request(Some("Bearer nope")))
+            .unwrap_err();
+        assert_eq!(Status::from(denied).code(), Code::PermissionDenied);
+
+        let empty = AuthConfig {
+            tokens: [("nobody".to_owned(), String::new())].into(),
+        };
+        assert!(empty.admin(&request(Some("Bearer "))).is_none());
+    }
+}
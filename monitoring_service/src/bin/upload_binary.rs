@@ -0,0 +1,57 @@
+//! Uploads a build to a running service, which restarts into it. The admin token is read from
+//! `MONITOR_TOKEN`.
+#![forbid(unsafe_code)]
+
+use std::{error::Error, path::PathBuf};
+
+use clap::Parser;
+use common::monitoring::{monitor_client::MonitorClient, BinaryChunk};
+use sha2::{Digest, Sha256};
+use tonic::{transport::Endpoint, Request};
+
+/// Large enough to keep the overhead low, well below the 4 MiB message limit.
+const CHUNK_SIZE: usize = 256 << 10;
+
+#[derive(Parser)]
+struct UploadCli {
+    /// Address of the running service, such as `http://orangepi:50525`.
+    #[arg(long = "to")]
+    to: String,
+    /// The new build of `monitoring_service`.
+    binary: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = UploadCli::parse();
+    let token = std::env::var("MONITOR_TOKEN").map_err(|_| "MONITOR_TOKEN is not set")?;
+    let binary = std::fs::read(&cli.binary)?;
+    let sha256 = hex::encode(Sha256::digest(&binary));
+
+    let chunks = binary
+        .chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, x)| BinaryChunk {
+            sha256: if i == 0 {
+                sha256.clone()
+            } else {
+                String::new()
+            },
+            data: x.to_vec(),
+        })
+        .collect::<Vec<_>>();
+    let mut request = Request::new(tokio_stream::iter(chunks));
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {token}").parse()?);
+
+    let channel = Endpoint::from_shared(cli.to.clone())?.connect().await?;
+    let mut client = MonitorClient::new(channel);
+    let response = client.upload_binary(request).await?.into_inner();
+    println!(
+        "{} restarts into {}, the previous build comes back unless it is healthy within {} seconds",
+        cli.to, response.version, response.health_timeout_secs
+    );
+
+    Ok(())
+}
@@ -0,0 +1,522 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use common::monitoring::{self as proto, cgroup, CgroupResponse, Pack};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+};
+
+/// `[cgroups]` section of the config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CgroupsConfig {
+    /// Where the cgroup v2 hierarchy is mounted.
+    pub root: PathBuf,
+    /// Docker state, container names are read from `containers/<id>/config.v2.json`.
+    pub docker_root: PathBuf,
+    /// Podman storage, container names are read from `overlay-containers/containers.json`.
+    pub podman_root: PathBuf,
+}
+
+impl Default for CgroupsConfig {
+    fn default() -> Self {
+        CgroupsConfig {
+            root: PathBuf::from("/sys/fs/cgroup"),
+            docker_root: PathBuf::from("/var/lib/docker"),
+            podman_root: PathBuf::from("/var/lib/containers/storage"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Docker,
+    Podman,
+    Systemd,
+    Other,
+}
+
+/// Cumulative counters of a cgroup.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub cpu_us: u64,
+    pub io_read_bytes: u64,
+    pub io_write_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cgroup {
+    /// Relative to the root of the hierarchy, starting with `/`.
+    pub path: String,
+    pub name: String,
+    pub kind: Kind,
+    /// Percent of one CPU.
+    pub cpu_usage: f32,
+    pub memory_current: u64,
+    /// Missing when unlimited.
+    pub memory_max: Option<u64>,
+    pub io_read_bytes_per_second: f32,
+    pub io_write_bytes_per_second: f32,
+    pub pids_current: u64,
+    pub pids_max: Option<u64>,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cgroups(pub Vec<Cgroup>);
+
+/// Reads a file of a controller that may be disabled in this cgroup.
+fn read_optional(path: &Path) -> std::io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn invalid(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidData,
+        format!("cannot parse {}", path.display()),
+    )
+}
+
+/// Reads a single number, `max` meaning no limit.
+fn read_limit(path: &Path) -> std::io::Result<Option<u64>> {
+    match read_optional(path)?.as_deref().map(str::trim) {
+        None | Some("max") => Ok(None),
+        Some(x) => x.parse().map(Some).map_err(|_| invalid(path)),
+    }
+}
+
+/// Value of a `key value` line, as in `cpu.stat`.
+fn flat_field(content: &str, key: &str) -> u64 {
+    content
+        .lines()
+        .find_map(|x| x.strip_prefix(key)?.strip_prefix(' ')?.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+/// Sums `key=value` fields of every line, as in `io.stat`.
+fn sum_fields(content: &str, key: &str) -> u64 {
+    content
+        .split_whitespace()
+        .filter_map(|x| x.strip_prefix(key)?.strip_prefix('='))
+        .filter_map(|x| x.parse::<u64>().ok())
+        .sum()
+}
+
+/// Every cgroup below `root`, parents before children.
+fn walk(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut cgroups = vec![];
+    let mut pending = vec![root.to_owned()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // Removed since its parent was listed, as cgroups of finished services are
+            Err(e) if e.kind() == ErrorKind::NotFound && dir != root => continue,
+            Err(e) => return Err(e),
+        };
+        let mut children = entries
+            .filter_map(Result::ok)
+            .filter(|x| x.file_type().is_ok_and(|x| x.is_dir()))
+            .map(|x| x.path())
+            .collect::<Vec<_>>();
+        children.sort();
+        pending.extend(children.iter().rev().cloned());
+        if dir != root {
+            cgroups.push(dir);
+        }
+    }
+    Ok(cgroups)
+}
+
+/// Container engine and ID of a cgroup, for both the systemd and the cgroupfs drivers.
+fn container(path: &str) -> Option<(Kind, &str)> {
+    let mut components = path.rsplit('/');
+    let last = components.next()?;
+    let parent = components.next().unwrap_or_default();
+    let scope = |prefix: &str| last.strip_prefix(prefix)?.strip_suffix(".scope");
+
+    if let Some(id) = scope("docker-") {
+        Some((Kind::Docker, id))
+    } else if parent == "docker" {
+        Some((Kind::Docker, last))
+    } else if let Some(id) = scope("libpod-").filter(|x| !x.starts_with("conmon-")) {
+        Some((Kind::Podman, id))
+    } else if parent.starts_with("libpod_parent") {
+        Some((Kind::Podman, last))
+    } else {
+        None
+    }
+}
+
+#[derive(Deserialize)]
+struct DockerContainer {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct PodmanContainer {
+    id: String,
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+fn docker_name(docker_root: &Path, id: &str) -> Option<String> {
+    let path = docker_root
+        .join("containers")
+        .join(id)
+        .join("config.v2.json");
+    let container = serde_json::from_slice::<DockerContainer>(&std::fs::read(path).ok()?).ok()?;
+
+    Some(container.name.trim_start_matches('/').to_owned())
+}
+
+fn podman_names(podman_root: &Path) -> HashMap<String, String> {
+    let path = podman_root.join("overlay-containers/containers.json");
+    let containers = std::fs::read(path)
+        .ok()
+        .and_then(|x| serde_json::from_slice::<Vec<PodmanContainer>>(&x).ok())
+        .unwrap_or_default();
+
+    containers
+        .into_iter()
+        .filter_map(|x| Some((x.id, x.names.into_iter().next()?)))
+        .collect()
+}
+
+/// Reports resource usage of every cgroup of the v2 hierarchy.
+#[derive(Debug)]
+pub struct CgroupsCollector {
+    config: CgroupsConfig,
+    /// Readable names of running containers by ID, looked up until found.
+    names: HashMap<String, String>,
+    previous: Option<(Instant, HashMap<String, Usage>)>,
+}
+
+impl CgroupsCollector {
+    pub fn new(config: CgroupsConfig) -> Self {
+        CgroupsCollector {
+            config,
+            names: HashMap::new(),
+            previous: None,
+        }
+    }
+
+    fn identify(&mut self, path: &str) -> (Kind, String) {
+        let last = path.rsplit('/').next().unwrap_or_default();
+        let Some((kind, id)) = container(path) else {
+            let systemd = [".service", ".slice", ".scope", ".mount", ".socket", ".swap"];
+            return if systemd.iter().any(|x| last.ends_with(x)) {
+                (Kind::Systemd, last.to_owned())
+            } else {
+                (Kind::Other, path.to_owned())
+            };
+        };
+
+        if let Some(name) = self.names.get(id) {
+            return (kind, name.clone());
+        }
+        let name = match kind {
+            Kind::Docker => docker_name(&self.config.docker_root, id),
+            _ => podman_names(&self.config.podman_root).remove(id),
+        };
+        // The engine may not have written its state yet, so misses are looked up again
+        let Some(name) = name else {
+            return (kind, id.get(..12).unwrap_or(id).to_owned());
+        };
+        self.names.insert(id.to_owned(), name.clone());
+        (kind, name)
+    }
+
+    fn read(&mut self, dir: &Path) -> std::io::Result<Cgroup> {
+        let relative = dir.strip_prefix(&self.config.root).unwrap_or(dir);
+        let path = format!("/{}", relative.display());
+        let (kind, name) = self.identify(&path);
+        let cpu = read_optional(&dir.join("cpu.stat"))?.unwrap_or_default();
+        let io = read_optional(&dir.join("io.stat"))?.unwrap_or_default();
+
+        Ok(Cgroup {
+            path,
+            name,
+            kind,
+            cpu_usage: 0.0,
+            memory_current: read_limit(&dir.join("memory.current"))?.unwrap_or_default(),
+            memory_max: read_limit(&dir.join("memory.max"))?,
+            io_read_bytes_per_second: 0.0,
+            io_write_bytes_per_second: 0.0,
+            pids_current: read_limit(&dir.join("pids.current"))?.unwrap_or_default(),
+            pids_max: read_limit(&dir.join("pids.max"))?,
+            usage: Usage {
+                cpu_us: flat_field(&cpu, "usage_usec"),
+                io_read_bytes: sum_fields(&io, "rbytes"),
+                io_write_bytes: sum_fields(&io, "wbytes"),
+            },
+        })
+    }
+
+    /// Fills the rates from the change of counters since the previous update.
+    fn update(&mut self, now: Instant, mut cgroups: Vec<Cgroup>) -> Cgroups {
+        if let Some((at, previous)) = &self.previous {
+            let seconds = now.saturating_duration_since(*at).as_secs_f32();
+
+            for cgroup in cgroups.iter_mut().filter(|_| seconds > 0.0) {
+                let Some(before) = previous.get(&cgroup.path) else {
+                    continue;
+                };
+                // A cgroup recreated with the same path starts from zero
+                let delta = |now: u64, before: u64| now.checked_sub(before).unwrap_or(now) as f32;
+                let usage = &cgroup.usage;
+
+                cgroup.cpu_usage = delta(usage.cpu_us, before.cpu_us) / seconds / 1e4;
+                cgroup.io_read_bytes_per_second =
+                    delta(usage.io_read_bytes, before.io_read_bytes) / seconds;
+                cgroup.io_write_bytes_per_second =
+                    delta(usage.io_write_bytes, before.io_write_bytes) / seconds;
+            }
+        }
+
+        let usage = cgroups.iter().map(|x| (x.path.clone(), x.usage)).collect();
+        self.previous = Some((now, usage));
+        Cgroups(cgroups)
+    }
+}
+
+impl Collector for CgroupsCollector {
+    type Output = Cgroups;
+
+    fn name(&self) -> &'static str {
+        "cgroups"
+    }
+
+    /// Walking the hierarchy reads a few files of every service.
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        let root = self.config.root.clone();
+        if !root.join("cgroup.controllers").exists() {
+            return Err(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("{} is not a cgroup v2 hierarchy", root.display()),
+            ));
+        }
+
+        let cgroups = walk(&root)?
+            .iter()
+            .map(|x| self.read(x))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let running = cgroups
+            .iter()
+            .filter_map(|x| container(&x.path))
+            .map(|x| x.1)
+            .collect::<HashSet<_>>();
+        self.names.retain(|id, _| running.contains(id.as_str()));
+
+        Ok(self.update(Instant::now(), cgroups))
+    }
+}
+
+impl Section for Cgroups {
+    const FIELD: &'static str = "cgroups";
+
+    fn store(self, pack: &mut Pack) {
+        pack.cgroups = Some(CgroupResponse {
+            cgroups: self
+                .0
+                .into_iter()
+                .map(|x| {
+                    let kind = match x.kind {
+                        Kind::Docker => cgroup::Kind::Docker,
+                        Kind::Podman => cgroup::Kind::Podman,
+                        Kind::Systemd => cgroup::Kind::Systemd,
+                        Kind::Other => cgroup::Kind::Other,
+                    };
+
+                    proto::Cgroup {
+                        path: x.path,
+                        name: x.name,
+                        kind: kind.into(),
+                        cpu_usage: x.cpu_usage,
+                        cpu_usage_us: x.usage.cpu_us,
+                        memory_current: x.memory_current,
+                        memory_max: x.memory_max,
+                        io_read_bytes_per_second: x.io_read_bytes_per_second,
+                        io_write_bytes_per_second: x.io_write_bytes_per_second,
+                        io_read_bytes: x.usage.io_read_bytes,
+                        io_write_bytes: x.usage.io_write_bytes,
+                        pids_current: x.pids_current,
+                        pids_max: x.pids_max,
+                    }
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.cgroups = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use sysinfo::{System, SystemExt};
+
+    use crate::{collector::Collector, test_util::Fixture};
+
+    use super::{container, walk, CgroupsCollector, CgroupsConfig, Kind};
+
+    const DOCKER_ID: &str = "4f1c2a9d0b7e4f1c2a9d0b7e4f1c2a9d0b7e4f1c2a9d0b7e4f1c2a9d0b7e0001";
+
+    fn hierarchy(fixture: &Fixture, usage_usec: u64, rbytes: u64) {
+        let docker = format!("sys/fs/cgroup/system.slice/docker-{DOCKER_ID}.scope");
+        fixture
+            .write("sys/fs/cgroup/cgroup.controllers", "cpu io memory pids")
+            .write("sys/fs/cgroup/system.slice/memory.current", "734003200")
+            .write(
+                "sys/fs/cgroup/system.slice/ssh.service/memory.current",
+                "4096",
+            )
+            .write(
+                &format!("{docker}/cpu.stat"),
+                &format!("usage_usec {usage_usec}\nuser_usec 1\nsystem_usec 1"),
+            )
+            .write(&format!("{docker}/memory.current"), "536870912")
+            .write(&format!("{docker}/memory.max"), "1073741824")
+            .write(
+                &format!("{docker}/io.stat"),
+                &format!("179:0 rbytes={rbytes} wbytes=0 rios=1 wios=0\n8:0 rbytes=100 wbytes=50"),
+            )
+            .write(&format!("{docker}/pids.current"), "12")
+            .write(&format!("{docker}/pids.max"), "max")
+            .write(
+                &format!("var/lib/docker/containers/{DOCKER_ID}/config.v2.json"),
+                r#"{"ID":"4f1c","Name":"/grafana","State":{"Running":true}}"#,
+            );
+    }
+
+    #[test]
+    fn test_usage_and_container_names() {
+        let fixture = Fixture::new("cgroups-docker");
+        hierarchy(&fixture, 1_000_000, 1000);
+        let mut collector = CgroupsCollector::new(CgroupsConfig {
+            root: fixture.path().join("sys/fs/cgroup"),
+            docker_root: fixture.path().join("var/lib/docker"),
+            ..Default::default()
+        });
+        let read = |collector: &mut CgroupsCollector| {
+            walk(&fixture.path().join("sys/fs/cgroup"))
+                .unwrap()
+                .iter()
+                .map(|x| collector.read(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let start = Instant::now();
+
+        let cgroups = read(&mut collector);
+        collector.update(start, cgroups);
+        hierarchy(&fixture, 1_500_000, 2_001_000);
+        let cgroups = read(&mut collector);
+        let cgroups = collector.update(start + Duration::from_secs(2), cgroups);
+
+        let paths = cgroups
+            .0
+            .iter()
+            .map(|x| x.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "/system.slice",
+                &format!("/system.slice/docker-{DOCKER_ID}.scope"),
+                "/system.slice/ssh.service",
+            ]
+        );
+        let docker = &cgroups.0[1];
+        assert_eq!(
+            (docker.kind, docker.name.as_str()),
+            (Kind::Docker, "grafana")
+        );
+        assert_eq!(docker.cpu_usage, 25.0);
+        assert_eq!(docker.memory_max, Some(1024 * 1024 * 1024));
+        assert_eq!(docker.io_read_bytes_per_second, 1_000_000.0);
+        assert_eq!(docker.usage.io_write_bytes, 50);
+        assert_eq!((docker.pids_current, docker.pids_max), (12, None));
+        assert_eq!(cgroups.0[2].kind, Kind::Systemd);
+        assert_eq!(cgroups.0[2].memory_max, None);
+
+        fixture.write("sys/fs/cgroup/cgroup.controllers", "cpu io memory pids");
+        collector.collect(&mut System::new()).unwrap();
+        assert!(collector.names.contains_key(DOCKER_ID));
+        let scope = format!("sys/fs/cgroup/system.slice/docker-{DOCKER_ID}.scope");
+        std::fs::remove_dir_all(fixture.path().join(scope)).unwrap();
+        let cgroups = collector.collect(&mut System::new()).unwrap();
+        assert_eq!(cgroups.0.len(), 2);
+        assert!(collector.names.is_empty());
+    }
+
+    #[test]
+    fn test_container_ids() {
+        assert_eq!(
+            container("/system.slice/libpod-abc.scope"),
+            Some((Kind::Podman, "abc"))
+        );
+        assert_eq!(container("/machine.slice/libpod-conmon-abc.scope"), None);
+        assert_eq!(container("/docker/abc"), Some((Kind::Docker, "abc")));
+        assert_eq!(container("/system.slice/docker.service"), None);
+    }
+
+    #[test]
+    fn test_podman_names_and_unknown_ids() {
+        let fixture = Fixture::new("cgroups-podman");
+        fixture.write(
+            "storage/overlay-containers/containers.json",
+            r#"[{"id":"abc","names":["influxdb"],"image":"x"}]"#,
+        );
+        let mut collector = CgroupsCollector::new(CgroupsConfig {
+            podman_root: fixture.path().join("storage"),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            collector.identify("/machine.slice/libpod-abc.scope"),
+            (Kind::Podman, "influxdb".to_owned())
+        );
+        assert_eq!(
+            collector.identify("/machine.slice/libpod-0123456789abcdef.scope"),
+            (Kind::Podman, "0123456789ab".to_owned())
+        );
+
+        // Named once podman has written its state
+        fixture.write(
+            "storage/overlay-containers/containers.json",
+            r#"[{"id":"0123456789abcdef","names":["telegraf"]}]"#,
+        );
+        assert_eq!(
+            collector.identify("/machine.slice/libpod-0123456789abcdef.scope"),
+            (Kind::Podman, "telegraf".to_owned())
+        );
+    }
+}
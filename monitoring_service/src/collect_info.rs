@@ -1,140 +1,169 @@
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, time::Duration};
 
-use sysinfo::{ComponentExt, CpuExt, CpuRefreshKind, NetworkData, NetworkExt, System, SystemExt};
-use tonic::async_trait;
+use sysinfo::{System, SystemExt};
 
-#[async_trait]
-pub trait AsyncNew: Sized {
-    async fn new(system: &mut System) -> std::io::Result<Self>;
-}
+use common::monitoring::{NetworkInterface, NetworkResponse, Pack};
 
-#[derive(Debug)]
-pub struct CpuLoad {
-    pub user: f32,
-    pub nice: f32,
-    pub system: f32,
-    pub idle: f32,
-}
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Cpu {
-    pub time: Instant,
     pub freq: Vec<u64>,
-    pub temperature: Option<f32>,
     pub usage: Vec<f32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Network {
     pub names: Vec<String>,
     pub bytes_in: HashMap<String, u64>,
     pub bytes_out: HashMap<String, u64>,
 }
 
-#[derive(Debug)]
-pub struct MonitoringData<T> {
-    data: T,
-}
+#[derive(Debug, Clone, Copy)]
+pub struct Temperature(pub f32);
+
+#[derive(Debug, Default)]
+pub struct CpuCollector;
+
+#[derive(Debug, Default)]
+pub struct NetworkCollector;
+
+#[derive(Debug, Default)]
+pub struct SensorsCollector;
+
+impl Collector for CpuCollector {
+    type Output = Cpu;
+
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
 
-#[async_trait]
-impl AsyncNew for Cpu {
-    async fn new(system: &mut System) -> std::io::Result<Self> {
+    fn interval(&self) -> Duration {
+        System::MINIMUM_CPU_UPDATE_INTERVAL
+    }
+
+    fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
         Cpu::new(system)
     }
 }
 
-#[async_trait]
-impl AsyncNew for Network {
-    async fn new(system: &mut System) -> std::io::Result<Self> {
+impl Collector for NetworkCollector {
+    type Output = Network;
+
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
         Network::new(system)
     }
 }
 
-#[async_trait]
-impl<T: AsyncNew + Send, V: AsyncNew + Send> AsyncNew for (T, V) {
-    async fn new(system: &mut System) -> std::io::Result<Self> {
-        Ok((T::new(system).await?, V::new(system).await?))
+impl Collector for SensorsCollector {
+    type Output = Temperature;
+
+    fn name(&self) -> &'static str {
+        "sensors"
+    }
+
+    fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        Temperature::new(system)
     }
 }
 
 impl Cpu {
-    fn read_freq(system: &System) -> std::io::Result<Vec<u64>> {
-        Ok(system.cpus().iter().map(|c| c.frequency()).collect())
-    }
+    fn new(system: &mut dyn SystemSource) -> std::io::Result<Self> {
+        let (usage, freq) = system.cpus().into_iter().unzip();
 
-    fn read_loads(system: &System) -> std::io::Result<Vec<f32>> {
-        let cpus = system.cpus();
-        Ok(cpus.iter().map(|c| c.cpu_usage()).collect())
+        Ok(Self { freq, usage })
     }
+}
+
+impl Temperature {
+    fn read_temp(system: &mut dyn SystemSource) -> std::io::Result<f32> {
+        let temps = system.temperatures();
 
-    fn read_temp(system: &System) -> std::io::Result<f32> {
-        let temps = system
-            .components()
-            .iter()
-            .map(|c| c.temperature())
-            .collect::<Vec<_>>();
+        if temps.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no temperature sensors found",
+            ));
+        }
 
         let temps_len = temps.len();
         Ok(temps.into_iter().sum::<f32>() / temps_len as f32)
     }
 
-    fn new(system: &mut System) -> std::io::Result<Self> {
-        system.refresh_all();
-        system.refresh_cpu_specifics(CpuRefreshKind::new().with_cpu_usage());
-
-        Ok(Self {
-            time: Instant::now(),
-            freq: Cpu::read_freq(system).unwrap_or_default(),
-            temperature: Cpu::read_temp(system).ok(),
-            usage: Cpu::read_loads(system).unwrap_or_default(),
-        })
+    fn new(system: &mut dyn SystemSource) -> std::io::Result<Self> {
+        Ok(Self(Temperature::read_temp(system)?))
     }
 }
 
 impl Network {
-    fn read_networks_stats<F: Fn(&NetworkData) -> T, T>(
-        system: &System,
-        f: F,
-    ) -> std::io::Result<HashMap<String, T>> {
-        let networks = system.networks();
-
-        Ok(networks
-            .into_iter()
-            .map(|x| (x.0.clone(), f(x.1)))
-            .collect())
+    fn new(system: &mut dyn SystemSource) -> std::io::Result<Self> {
+        let mut network = Self {
+            names: vec![],
+            bytes_in: HashMap::new(),
+            bytes_out: HashMap::new(),
+        };
+
+        for (name, bytes_in, bytes_out) in system.networks() {
+            network.bytes_in.insert(name.clone(), bytes_in);
+            network.bytes_out.insert(name.clone(), bytes_out);
+            network.names.push(name);
+        }
+        Ok(network)
     }
+}
 
-    fn read_in(system: &System) -> std::io::Result<HashMap<String, u64>> {
-        Network::read_networks_stats(system, |x| x.received())
+impl Section for Cpu {
+    const FIELD: &'static str = "cpu";
+
+    fn store(self, pack: &mut Pack) {
+        let cpu = pack.cpu.get_or_insert_with(Default::default);
+        cpu.usage = self.usage;
+        cpu.frequency = self.freq;
     }
 
-    fn read_out(system: &System) -> std::io::Result<HashMap<String, u64>> {
-        Network::read_networks_stats(system, |x| x.transmitted())
+    fn clear(pack: &mut Pack) {
+        pack.cpu = None;
     }
+}
 
-    fn new(system: &mut System) -> std::io::Result<Self> {
-        system.refresh_all();
+impl Section for Temperature {
+    const FIELD: &'static str = "cpu";
+
+    fn store(self, pack: &mut Pack) {
+        pack.cpu.get_or_insert_with(Default::default).temperature = Some(self.0);
+    }
 
-        Ok(Self {
-            names: system.networks().into_iter().map(|x| x.0.clone()).collect(),
-            bytes_in: Network::read_in(system)?,
-            bytes_out: Network::read_out(system)?,
-        })
+    fn clear(pack: &mut Pack) {
+        pack.cpu = None;
     }
 }
 
-impl<T> MonitoringData<T> {
-    pub async fn new(system: &mut System) -> std::io::Result<Self>
-    where
-        T: AsyncNew,
-    {
-        Ok(MonitoringData {
-            data: T::new(system).await?,
-        })
+impl Section for Network {
+    const FIELD: &'static str = "network";
+
+    fn store(self, pack: &mut Pack) {
+        pack.network = Some(NetworkResponse {
+            interfaces: self
+                .names
+                .into_iter()
+                .map(|x| NetworkInterface {
+                    bytes_in: self.bytes_in.get(x.as_str()).copied().unwrap_or_default(),
+                    bytes_out: self.bytes_out.get(x.as_str()).copied().unwrap_or_default(),
+                    name: x,
+                })
+                .collect(),
+        });
     }
 
-    pub fn into_data(self) -> T {
-        self.data
+    fn clear(pack: &mut Pack) {
+        pack.network = None;
     }
 }
 
@@ -142,30 +171,54 @@ impl<T> MonitoringData<T> {
 mod tests {
     use sysinfo::{CpuRefreshKind, RefreshKind, System, SystemExt};
 
-    use crate::collect_info::Cpu;
+    use crate::{
+        collector::Collector,
+        source::{Simulated, SimulationConfig},
+    };
 
-    use super::{MonitoringData, Network};
+    use super::{CpuCollector, NetworkCollector, SensorsCollector};
 
-    #[tokio::test]
-    async fn test_cpu_works() {
-        let mut system = System::new_with_specifics(
-            RefreshKind::new()
-                .with_cpu(CpuRefreshKind::everything())
-                .with_components(),
-        );
-        let data = MonitoringData::<Cpu>::new(&mut system).await;
+    #[test]
+    fn test_cpu_works() {
+        let mut system =
+            System::new_with_specifics(RefreshKind::new().with_cpu(CpuRefreshKind::everything()));
+        let data = CpuCollector.collect(&mut system);
 
         assert!(data.is_ok());
-        let data = data.unwrap().data;
-        assert!(data.temperature.is_some());
+        let data = data.unwrap();
+        assert_eq!(data.usage.len(), system.cpus().len());
         assert!(data.freq.into_iter().all(|x| x > 0));
     }
 
-    #[tokio::test]
-    async fn test_network_works() {
+    #[test]
+    fn test_sensors_fail_without_components() {
+        let mut system = System::new_with_specifics(RefreshKind::new().with_components_list());
+        let data = SensorsCollector.collect(&mut system);
+
+        assert_eq!(data.is_ok(), !system.components().is_empty());
+        assert!(data.map_or(true, |x| x.0.is_finite()));
+    }
+
+    #[test]
+    fn test_network_works() {
         let mut system = System::new_with_specifics(RefreshKind::new().with_networks());
-        let data = MonitoringData::<Network>::new(&mut system).await;
+        let data = NetworkCollector.collect(&mut system);
 
         assert!(data.is_ok());
     }
+
+    #[test]
+    fn test_simulated_collectors() {
+        let mut source = Simulated::new(SimulationConfig::default());
+
+        let cpu = CpuCollector.collect(&mut source).unwrap();
+        assert_eq!(cpu.usage.len(), 4);
+        assert!(cpu.usage.iter().all(|x| (0.0..=100.0).contains(x)));
+
+        let temperature = SensorsCollector.collect(&mut source).unwrap();
+        assert!((40.0..=70.0).contains(&temperature.0));
+
+        let network = NetworkCollector.collect(&mut source).unwrap();
+        assert_eq!(network.names, vec!["eth0", "lo"]);
+    }
 }
@@ -0,0 +1,591 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{config::CollectorConfig, record::Recorder, source::SystemSource};
+use common::monitoring::{CollectorStats, Pack};
+
+/// Part of a [`Pack`] filled by collector outputs.
+pub trait Section: Clone + Send {
+    /// Name of the `Pack` field this section is stored in.
+    const FIELD: &'static str;
+
+    fn store(self, pack: &mut Pack);
+
+    /// Removes the section from the pack.
+    fn clear(pack: &mut Pack);
+}
+
+/// `Pack` fields filled by registered collectors, with the way to clear each of them.
+pub type Fields = HashMap<&'static str, fn(&mut Pack)>;
+
+pub trait Collector: Send + 'static {
+    type Output: Section;
+
+    /// Unique name used in configuration and health checks.
+    fn name(&self) -> &'static str;
+
+    /// Minimal time between two collections. Requests arriving sooner reuse the last output.
+    fn interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// Reads sysfs, procfs or D-Bus itself instead of asking the [`SystemSource`]. Such
+    /// collectors are skipped when the source is not the host.
+    fn reads_host(&self) -> bool {
+        false
+    }
+
+    fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output>;
+}
+
+/// Object safe view of a [`Collector`] together with its settings.
+trait Entry: Send {
+    fn name(&self) -> &'static str;
+
+    fn field(&self) -> &'static str;
+
+    fn enabled(&self) -> bool;
+
+    fn reads_host(&self) -> bool;
+
+    fn stats(&self) -> CollectorStats;
+
+    fn configure(&mut self, config: &CollectorConfig, default_interval: Duration);
+
+    fn collect_into(
+        &mut self,
+        system: &mut dyn SystemSource,
+        pack: &mut Pack,
+    ) -> std::io::Result<()>;
+}
+
+struct Registered<C: Collector> {
+    collector: C,
+    enabled: bool,
+    interval: Duration,
+    last: Option<(Instant, C::Output)>,
+    timings: Timings,
+}
+
+#[derive(Debug, Default)]
+struct Timings {
+    collections: u64,
+    failures: u64,
+    last: Duration,
+    total: Duration,
+    max: Duration,
+}
+
+impl Timings {
+    fn record(&mut self, duration: Duration, success: bool) {
+        self.collections += 1;
+        self.failures += u64::from(!success);
+        self.last = duration;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+}
+
+impl<C: Collector> Entry for Registered<C> {
+    fn name(&self) -> &'static str {
+        self.collector.name()
+    }
+
+    fn field(&self) -> &'static str {
+        C::Output::FIELD
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn reads_host(&self) -> bool {
+        self.collector.reads_host()
+    }
+
+    fn stats(&self) -> CollectorStats {
+        let timings = &self.timings;
+
+        CollectorStats {
+            name: self.name().to_owned(),
+            collections: timings.collections,
+            failures: timings.failures,
+            last_duration_us: timings.last.as_micros() as u64,
+            mean_duration_us: timings
+                .total
+                .checked_div(timings.collections as u32)
+                .unwrap_or_default()
+                .as_micros() as u64,
+            max_duration_us: timings.max.as_micros() as u64,
+        }
+    }
+
+    fn configure(&mut self, config: &CollectorConfig, default_interval: Duration) {
+        let enabled = config
+            .enabled
+            .unwrap_or_else(|| self.collector.enabled_by_default());
+        if enabled != self.enabled {
+            // An output kept from before the collector was disabled is stale
+            self.last = None;
+        }
+        self.enabled = enabled;
+        self.interval = config
+            .interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| default_interval.max(self.collector.interval()));
+    }
+
+    fn collect_into(
+        &mut self,
+        system: &mut dyn SystemSource,
+        pack: &mut Pack,
+    ) -> std::io::Result<()> {
+        let output = match &self.last {
+            Some((time, output)) if time.elapsed() < self.interval => output.clone(),
+            _ => {
+                let start = Instant::now();
+                let output = self.collector.collect(system);
+                self.timings.record(start.elapsed(), output.is_ok());
+
+                let output = output?;
+                self.last = Some((Instant::now(), output.clone()));
+                output
+            }
+        };
+
+        output.store(pack);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownCollectors(Vec<String>);
+
+impl Display for UnknownCollectors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown collectors in configuration: {}",
+            self.0.join(", ")
+        )
+    }
+}
+
+impl Error for UnknownCollectors {}
+
+/// Set of collectors, configured from the `[collectors.<name>]` tables. Disabled collectors
+/// are kept so they can be enabled again with [`Registry::reconfigure`].
+pub struct Registry {
+    config: HashMap<String, CollectorConfig>,
+    default_interval: Duration,
+    known: HashSet<&'static str>,
+    fields: Fields,
+    entries: Vec<Box<dyn Entry>>,
+}
+
+impl Registry {
+    pub fn new(config: HashMap<String, CollectorConfig>, default_interval: Duration) -> Self {
+        Registry {
+            config,
+            default_interval,
+            known: HashSet::new(),
+            fields: HashMap::new(),
+            entries: vec![],
+        }
+    }
+
+    pub fn register<C: Collector>(&mut self, collector: C) -> &mut Self {
+        let name = collector.name();
+        let config = self.config.get(name).cloned().unwrap_or_default();
+        self.known.insert(name);
+        self.fields.insert(C::Output::FIELD, C::Output::clear);
+
+        let mut entry = Registered {
+            collector,
+            enabled: false,
+            interval: Duration::ZERO,
+            last: None,
+            timings: Timings::default(),
+        };
+        entry.configure(&config, self.default_interval);
+        self.entries.push(Box::new(entry));
+        self
+    }
+
+    fn unknown(&self, config: &HashMap<String, CollectorConfig>) -> Result<(), UnknownCollectors> {
+        let mut unknown = config
+            .keys()
+            .filter(|x| !self.known.contains(x.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            unknown.sort();
+            Err(UnknownCollectors(unknown))
+        }
+    }
+
+    /// Fails if the configuration mentions collectors that were never registered.
+    pub fn validate(&self) -> Result<(), UnknownCollectors> {
+        self.unknown(&self.config)
+    }
+
+    /// Replaces the configuration of every collector. Collectors missing from `config` go back
+    /// to their defaults. Nothing changes if `config` mentions unknown collectors.
+    pub fn reconfigure(
+        &mut self,
+        config: HashMap<String, CollectorConfig>,
+        default_interval: Duration,
+    ) -> Result<(), UnknownCollectors> {
+        self.unknown(&config)?;
+
+        for entry in self.entries.iter_mut() {
+            let settings = config.get(entry.name()).cloned().unwrap_or_default();
+            entry.configure(&settings, default_interval);
+        }
+        self.config = config;
+        self.default_interval = default_interval;
+        Ok(())
+    }
+
+    /// Every registered collector in registration order, with whether it is enabled and the
+    /// interval set in the configuration.
+    pub fn collectors(&self) -> Vec<(&'static str, CollectorConfig)> {
+        self.entries
+            .iter()
+            .map(|x| {
+                let configured = self.config.get(x.name());
+                let config = CollectorConfig {
+                    enabled: Some(x.enabled()),
+                    interval_ms: configured.and_then(|x| x.interval_ms),
+                };
+                (x.name(), config)
+            })
+            .collect()
+    }
+
+    /// Names of the enabled collectors.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries
+            .iter()
+            .filter(|x| x.enabled())
+            .map(|x| x.name())
+            .collect()
+    }
+}
+
+pub struct Sample {
+    pub pack: Pack,
+    pub results: Vec<(&'static str, std::io::Result<()>)>,
+}
+
+/// Runs the registered collectors against the system.
+pub struct Sampler {
+    system: Box<dyn SystemSource>,
+    registry: Registry,
+    recorder: Option<Recorder>,
+}
+
+impl Sampler {
+    pub fn new(system: Box<dyn SystemSource>, registry: Registry) -> Self {
+        Sampler {
+            system,
+            registry,
+            recorder: None,
+        }
+    }
+
+    /// Writes every following sample to `recorder`.
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Fields filled by registered collectors, enabled or not.
+    pub fn fields(&self) -> &Fields {
+        &self.registry.fields
+    }
+
+    /// Collects every enabled collector whose section matches `fields`.
+    /// The whole pack is stamped with the time the collection started.
+    pub fn sample<F: Fn(&str) -> bool>(&mut self, fields: F) -> Sample {
+        let timestamp = Some(SystemTime::now().into());
+        if let Some(mut sample) = self.replay(&fields) {
+            sample.pack.timestamp = timestamp;
+            return sample;
+        }
+
+        let host = self.system.is_host();
+        let mut pack = Pack {
+            timestamp,
+            ..Default::default()
+        };
+        let results = self
+            .registry
+            .entries
+            .iter_mut()
+            .filter(|x| x.enabled() && fields(x.field()))
+            // Readings of the host would be mixed with simulated ones
+            .filter(|x| host || !x.reads_host())
+            .map(|x| (x.name(), x.collect_into(self.system.as_mut(), &mut pack)))
+            .collect();
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.write(&pack) {
+                eprintln!("Recording stopped: {e}");
+                self.recorder = None;
+            }
+        }
+
+        Sample { pack, results }
+    }
+
+    /// Serves the recorded sections that the enabled collectors would fill, without running
+    /// any of them. `None` unless the source replays a recording.
+    fn replay<F: Fn(&str) -> bool>(&mut self, fields: &F) -> Option<Sample> {
+        let entries = self
+            .registry
+            .entries
+            .iter()
+            .filter(|x| x.enabled() && fields(x.field()));
+        let wanted = entries.clone().map(|x| x.field()).collect::<HashSet<_>>();
+        let pack = self.system.recorded(&|x| wanted.contains(x))?;
+
+        Some(Sample {
+            pack,
+            results: entries.map(|x| (x.name(), Ok(()))).collect(),
+        })
+    }
+
+    /// Collection counters and timings of every enabled collector.
+    pub fn stats(&self) -> Vec<CollectorStats> {
+        self.registry
+            .entries
+            .iter()
+            .filter(|x| x.enabled())
+            .map(|x| x.stats())
+            .collect()
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut Registry {
+        &mut self.registry
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use common::monitoring::{NetworkResponse, Pack};
+    use sysinfo::{System, SystemExt};
+
+    use crate::{
+        config::CollectorConfig,
+        record::Replay,
+        source::{Simulated, SimulationConfig, SystemSource},
+    };
+
+    use super::{Collector, Registry, Sampler, Section};
+
+    #[derive(Clone)]
+    pub struct Counted(u64);
+
+    impl Section for Counted {
+        const FIELD: &'static str = "network";
+
+        fn store(self, pack: &mut Pack) {
+            pack.network = Some(NetworkResponse {
+                interfaces: vec![Default::default(); self.0 as usize],
+            });
+        }
+
+        fn clear(pack: &mut Pack) {
+            pack.network = None;
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Counter(pub u64);
+
+    impl Collector for Counter {
+        type Output = Counted;
+
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+
+        fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+            self.0 += 1;
+            Ok(Counted(self.0))
+        }
+    }
+
+    /// Reads the host itself, like the sysfs collectors.
+    #[derive(Default)]
+    struct HostCounter(Counter);
+
+    impl Collector for HostCounter {
+        type Output = Counted;
+
+        fn name(&self) -> &'static str {
+            "host"
+        }
+
+        fn reads_host(&self) -> bool {
+            true
+        }
+
+        fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+            self.0.collect(system)
+        }
+    }
+
+    pub fn count(sampler: &mut Sampler) -> usize {
+        let sample = sampler.sample(|_| true);
+        sample.pack.network.unwrap().interfaces.len()
+    }
+
+    fn config(enabled: Option<bool>, interval_ms: Option<u64>) -> HashMap<String, CollectorConfig> {
+        HashMap::from([(
+            "counter".to_owned(),
+            CollectorConfig {
+                enabled,
+                interval_ms,
+            },
+        )])
+    }
+
+    #[test]
+    fn test_disabled_collector_is_skipped() {
+        let mut registry = Registry::new(config(Some(false), None), Duration::ZERO);
+        registry.register(Counter::default());
+
+        assert!(registry.names().is_empty());
+        assert!(registry.validate().is_ok());
+    }
+
+    #[test]
+    fn test_collectors_are_reconfigured() {
+        let mut registry = Registry::new(config(Some(false), None), Duration::ZERO);
+        registry.register(Counter::default());
+        let mut sampler = Sampler::new(Box::new(System::new()), registry);
+        assert!(sampler.sample(|_| true).results.is_empty());
+
+        let registry = sampler.registry_mut();
+        let mut unknown = config(None, None);
+        unknown.insert("gpu".to_owned(), Default::default());
+        assert!(registry.reconfigure(unknown, Duration::ZERO).is_err());
+        assert!(registry.names().is_empty());
+
+        registry
+            .reconfigure(config(None, Some(60_000)), Duration::ZERO)
+            .unwrap();
+        assert_eq!(registry.names(), ["counter"]);
+        let (name, settings) = &registry.collectors()[0];
+        assert_eq!(*name, "counter");
+        assert_eq!(settings.enabled, Some(true));
+        assert_eq!(settings.interval_ms, Some(60_000));
+        assert_eq!(count(&mut sampler), 1);
+        assert_eq!(count(&mut sampler), 1);
+
+        sampler
+            .registry_mut()
+            .reconfigure(HashMap::new(), Duration::ZERO)
+            .unwrap();
+        assert_eq!(count(&mut sampler), 2);
+        assert_eq!(sampler.registry().collectors()[0].1.interval_ms, None);
+    }
+
+    #[test]
+    fn test_unknown_collector_is_rejected() {
+        let registry = Registry::new(config(None, None), Duration::ZERO);
+
+        assert!(registry.validate().is_err());
+    }
+
+    #[test]
+    fn test_interval_reuses_output() {
+        let mut registry = Registry::new(config(None, Some(60_000)), Duration::ZERO);
+        registry.register(Counter::default());
+        let mut sampler = Sampler::new(Box::new(System::new()), registry);
+
+        assert_eq!(count(&mut sampler), 1);
+        assert_eq!(count(&mut sampler), 1);
+        assert_eq!(sampler.stats()[0].collections, 1);
+    }
+
+    #[test]
+    fn test_stats_are_recorded() {
+        let mut registry = Registry::new(HashMap::new(), Duration::ZERO);
+        registry.register(Counter::default());
+        let mut sampler = Sampler::new(Box::new(System::new()), registry);
+        count(&mut sampler);
+        count(&mut sampler);
+
+        let stats = sampler.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].name, "counter");
+        assert_eq!(stats[0].collections, 2);
+        assert_eq!(stats[0].failures, 0);
+        assert!(stats[0].max_duration_us >= stats[0].mean_duration_us);
+    }
+
+    #[test]
+    fn test_field_filter() {
+        let mut registry = Registry::new(HashMap::new(), Duration::ZERO);
+        registry.register(Counter::default());
+        let mut sampler = Sampler::new(Box::new(System::new()), registry);
+
+        assert_eq!(count(&mut sampler), 1);
+        assert_eq!(count(&mut sampler), 2);
+        assert!(sampler.sample(|x| x == "cpu").results.is_empty());
+        assert!(sampler.fields().contains_key("network"));
+        assert!(!sampler.fields().contains_key("cpu"));
+    }
+
+    #[test]
+    fn test_host_is_not_read_without_host_source() {
+        let registry = || {
+            let mut registry = Registry::new(HashMap::new(), Duration::ZERO);
+            registry
+                .register(Counter::default())
+                .register(HostCounter::default());
+            registry
+        };
+
+        let simulated = Simulated::new(SimulationConfig::default());
+        let mut sampler = Sampler::new(Box::new(simulated), registry());
+        let sample = sampler.sample(|_| true);
+        assert_eq!(sample.results.len(), 1);
+        assert_eq!(sample.results[0].0, "counter");
+
+        let recorded = Pack {
+            network: Some(NetworkResponse::default()),
+            cpu: Some(Default::default()),
+            ..Default::default()
+        };
+        let replay = Replay::new(vec![recorded], 1.0).unwrap();
+        let mut sampler = Sampler::new(Box::new(replay), registry());
+        let sample = sampler.sample(|_| true);
+        assert!(sample.results.iter().all(|x| x.1.is_ok()));
+        assert!(sample.pack.network.is_some());
+        // Not filled by a registered collector
+        assert!(sample.pack.cpu.is_none());
+        assert!(sample.pack.timestamp.is_some());
+        assert!(sampler.stats().iter().all(|x| x.collections == 0));
+        assert!(sampler.sample(|x| x == "cpu").pack.network.is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::ActionConfig,
+    alerts::AlertsConfig,
+    audit::AuditConfig,
+    auth::AuthConfig,
+    cgroups::CgroupsConfig,
+    settings::{RetentionConfig, Settings, DEFAULT_CACHE_MAX_AGE_MS, DEFAULT_SAMPLING_INTERVAL_MS},
+    signals::SignalsConfig,
+    source::SimulationConfig,
+    systemd::SystemdConfig,
+    update::UpdateConfig,
+    watch::WatchConfig,
+    wear::WearConfig,
+};
+
+/// Contents of the TOML file passed with `--config`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Minimal time between two collections, unless a collector asks for more. `--update`
+    /// takes precedence.
+    pub sampling_interval_ms: Option<u64>,
+    /// How long clients share one collection, 100 ms by default.
+    pub cache_max_age_ms: Option<u64>,
+    pub collectors: HashMap<String, CollectorConfig>,
+    pub alerts: AlertsConfig,
+    pub retention: RetentionConfig,
+    pub simulation: SimulationConfig,
+    /// Where sysfs is mounted, `/sys` by default. Tests point it at fixture directories.
+    pub sysfs_root: Option<PathBuf>,
+    /// Where procfs is mounted, `/proc` by default.
+    pub procfs_root: Option<PathBuf>,
+    pub wear: WearConfig,
+    pub cgroups: CgroupsConfig,
+    pub systemd: SystemdConfig,
+    /// Processes to track, `[[watch]]` tables.
+    pub watch: Vec<WatchConfig>,
+    pub auth: AuthConfig,
+    pub signals: SignalsConfig,
+    pub audit: AuditConfig,
+    /// Commands admins may run by name, `[actions.<name>]`.
+    pub actions: BTreeMap<String, ActionConfig>,
+    /// How many actions may run at once, 1 by default.
+    pub max_running_actions: Option<usize>,
+    pub update: UpdateConfig,
+}
+
+/// Settings of a single collector, `[collectors.<name>]`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectorConfig {
+    pub enabled: Option<bool>,
+    pub interval_ms: Option<u64>,
+}
+
+impl Config {
+    pub fn sysfs_root(&self) -> &Path {
+        self.sysfs_root.as_deref().unwrap_or(Path::new("/sys"))
+    }
+
+    pub fn procfs_root(&self) -> &Path {
+        self.procfs_root.as_deref().unwrap_or(Path::new("/proc"))
+    }
+
+    /// Settings that `UpdateConfig` can change later.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            sampling_interval_ms: self
+                .sampling_interval_ms
+                .unwrap_or(DEFAULT_SAMPLING_INTERVAL_MS),
+            cache_max_age_ms: self.cache_max_age_ms.unwrap_or(DEFAULT_CACHE_MAX_AGE_MS),
+            collectors: self.collectors.clone().into_iter().collect(),
+            alerts: self.alerts.clone(),
+            retention: self.retention.clone(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn test_parse_collectors() {
+        let config: Config = toml::from_str(
+            r#"
+            [collectors.sensors]
+            enabled = false
+
+            [collectors.cpu]
+            interval_ms = 500
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.collectors["sensors"].enabled, Some(false));
+        assert_eq!(config.collectors["cpu"].interval_ms, Some(500));
+        assert_eq!(config.collectors["cpu"].enabled, None);
+    }
+
+    #[test]
+    fn test_parse_simulation() {
+        let config: Config = toml::from_str(
+            r#"
+            [simulation]
+            cpus = 8
+            temperature = { base = 60, noise = 2, sine = { amplitude = 10, period_ms = 5000 } }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.simulation.cpus, 8);
+        assert_eq!(config.simulation.temperature.base, 60.0);
+        assert_eq!(config.simulation.temperature.sine.unwrap().period_ms, 5000);
+        assert!(config.simulation.temperature.step.is_none());
+        assert_eq!(config.simulation.interfaces, vec!["eth0", "lo"]);
+    }
+
+    #[test]
+    fn test_parse_watch() {
+        let config: Config = toml::from_str(
+            r#"
+            [[watch]]
+            name = "web"
+            cmdline = "gunicorn .*app:main"
+
+            [[watch]]
+            name = "nginx"
+            pidfile = "/run/nginx.pid"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.watch.len(), 2);
+        assert_eq!(
+            config.watch[0].cmdline.as_deref(),
+            Some("gunicorn .*app:main")
+        );
+        assert!(config.watch[1].process.is_none());
+    }
+
+    #[test]
+    fn test_parse_auth() {
+        let config: Config = toml::from_str("[auth.tokens]\noncall = \"s3cret\"").unwrap();
+
+        assert_eq!(config.auth.tokens["oncall"], "s3cret");
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let config: Config = toml::from_str(
+            r#"
+            [actions.restart-app]
+            command = ["systemctl", "restart", "app"]
+            description = "Restart the app"
+            "#,
+        )
+        .unwrap();
+
+        let action = &config.actions["restart-app"];
+        assert_eq!(action.command, ["systemctl", "restart", "app"]);
+        assert_eq!(action.timeout_secs, 60);
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        let config: Config = toml::from_str(
+            r#"
+            sampling_interval_ms = 500
+
+            [alerts]
+            temperature_celsius = 85
+
+            [retention]
+            events = 1000
+            "#,
+        )
+        .unwrap();
+
+        let settings = config.settings();
+        assert_eq!(settings.sampling_interval_ms, 500);
+        assert_eq!(settings.cache_max_age_ms, 100);
+        assert_eq!(settings.alerts.temperature_celsius, Some(85.0));
+        assert_eq!(settings.alerts.cpu_usage_percent, None);
+        assert_eq!(settings.retention.events, 1000);
+        assert_eq!(settings.retention.watched_samples, 600);
+        assert!(toml::from_str::<Config>(
+            "[alerts]
+cpu = 90"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        assert!(toml::from_str::<Config>("[collectors.cpu]\nenable = true").is_err());
+    }
+}
@@ -1,75 +1,809 @@
-use std::sync::Arc;
-use sysinfo::System;
-use tokio::sync::Mutex;
-use tokio::try_join;
-use tonic::{async_trait, Request, Response, Status};
+use std::{
+    collections::BTreeMap, error::Error, io::ErrorKind, path::PathBuf, pin::Pin, sync::Arc,
+    time::Duration,
+};
+
+use prost_types::FieldMask;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tonic::{async_trait, Request, Response, Status, Streaming};
 
-use crate::collect_info::{Cpu, MonitoringData, Network};
+use crate::{
+    actions::{self, Actions},
+    alerts::AlertsConfig,
+    audit::{self, Audit, Entry},
+    auth::AuthConfig,
+    cgroups::Cgroups,
+    collect_info::{Cpu, Network},
+    collector::Section,
+    config::{CollectorConfig, Config},
+    events::{self, Events, Severity},
+    filesystems::Filesystems,
+    power::{self, Operation, Power, Step},
+    processes::{self},
+    settings::{RetentionConfig, Settings},
+    signals::{self, Signal, SignalsConfig},
+    snapshot::Snapshots,
+    systemd::Units,
+    update::Updater,
+    watch::{History, Watched},
+};
 
 use common::monitoring::{
-    monitor_server::Monitor, CpuResponse, NetworkInterface, NetworkResponse, Pack,
+    self as proto, action_output, monitor_server::Monitor, Action, ActionExit, ActionOutput,
+    ActionRequest, ActionsResponse, AlertThresholds, AuditEntry, AuditRequest, AuditResponse,
+    BinaryChunk, CgroupResponse, CollectorSettings, CpuResponse, EventsRequest, EventsResponse,
+    HistoryRequest, HistoryResponse, NetworkResponse, Notice, Pack, PowerRequest, PowerResponse,
+    ProcessDetails, ProcessIo, ProcessLimit, ProcessRelative, ProcessRequest,
+    RestartServiceRequest, Retention, RuntimeConfig, ServiceStats, SignalRequest, UnitResponse,
+    UpdateConfigRequest, UploadResponse, WatchedResponse,
 };
 
+/// Audit entries returned when the request does not say.
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+const MAX_AUDIT_LIMIT: usize = 1000;
+
 pub struct MonitorService {
-    system: Arc<Mutex<System>>,
+    snapshots: Arc<Snapshots>,
+    events: Events,
+    history: History,
+    procfs_root: PathBuf,
+    auth: AuthConfig,
+    signals: SignalsConfig,
+    audit: Audit,
+    actions: Arc<Actions>,
+    power: Power,
+    updater: Updater,
+    /// Locked for the whole of an update, so concurrent ones are applied in turn.
+    settings: tokio::sync::Mutex<Settings>,
+    /// Where `UpdateConfig` persists the settings.
+    config_path: Option<PathBuf>,
+}
+
+impl From<processes::Details> for ProcessDetails {
+    fn from(details: processes::Details) -> Self {
+        let relative = |x: processes::Relative| ProcessRelative {
+            pid: x.pid,
+            name: x.name,
+        };
+
+        ProcessDetails {
+            pid: details.pid,
+            name: details.name,
+            state: details.state,
+            uid: details.uid,
+            cmdline: details.cmdline,
+            parents: details.parents.into_iter().map(relative).collect(),
+            children: details.children.into_iter().map(relative).collect(),
+            environment: details.environment.unwrap_or_default(),
+            cwd: details
+                .cwd
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            open_fds: details.open_fds,
+            limits: details
+                .limits
+                .into_iter()
+                .map(|x| ProcessLimit {
+                    name: x.name,
+                    soft: x.soft,
+                    hard: x.hard,
+                    unit: x.unit,
+                })
+                .collect(),
+            threads: details.threads,
+            io: details.io.map(|x| ProcessIo {
+                read_chars: x.read_chars,
+                write_chars: x.write_chars,
+                read_syscalls: x.read_syscalls,
+                write_syscalls: x.write_syscalls,
+                read_bytes: x.read_bytes,
+                write_bytes: x.write_bytes,
+                cancelled_write_bytes: x.cancelled_write_bytes,
+            }),
+            cgroup: details.cgroup.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<events::Event> for proto::Event {
+    fn from(event: events::Event) -> Self {
+        let severity = match event.severity {
+            Severity::Info => proto::Severity::Info,
+            Severity::Warning => proto::Severity::Warning,
+            Severity::Critical => proto::Severity::Critical,
+        };
+
+        proto::Event {
+            id: event.id,
+            time: Some(event.time.into()),
+            severity: severity.into(),
+            source: event.source.to_owned(),
+            message: event.message,
+        }
+    }
+}
+
+impl From<actions::Output> for ActionOutput {
+    fn from(output: actions::Output) -> Self {
+        let output = match output {
+            actions::Output::Stdout(x) => action_output::Output::Stdout(x),
+            actions::Output::Stderr(x) => action_output::Output::Stderr(x),
+            actions::Output::Exit(x) => action_output::Output::Exit(ActionExit {
+                code: x.code,
+                timed_out: x.timed_out,
+                error: x.error,
+            }),
+        };
+
+        ActionOutput {
+            output: Some(output),
+        }
+    }
+}
+
+impl From<Step> for PowerResponse {
+    fn from(step: Step) -> Self {
+        match step {
+            Step::Confirm { token, expires_in } => PowerResponse {
+                confirm_token: token,
+                expires_in_secs: expires_in.as_secs() as u32,
+                scheduled: false,
+            },
+            Step::Scheduled => PowerResponse {
+                scheduled: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Settings after an update, which keeps the current interval, collectors, alerts, retention
+/// and cache age when the update leaves them out.
+fn updated(config: RuntimeConfig, current: &Settings) -> Settings {
+    let collectors = config
+        .collectors
+        .into_iter()
+        .map(|x| {
+            let settings = CollectorConfig {
+                enabled: Some(x.enabled),
+                interval_ms: x.interval_ms,
+            };
+            (x.name, settings)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    Settings {
+        sampling_interval_ms: config
+            .sampling_interval_ms
+            .unwrap_or(current.sampling_interval_ms),
+        cache_max_age_ms: config.cache_max_age_ms.unwrap_or(current.cache_max_age_ms),
+        collectors: if collectors.is_empty() {
+            current.collectors.clone()
+        } else {
+            collectors
+        },
+        alerts: match config.alerts {
+            Some(alerts) => AlertsConfig {
+                cpu_usage_percent: alerts.cpu_usage_percent,
+                temperature_celsius: alerts.temperature_celsius,
+                load_average: alerts.load_average,
+            },
+            None => current.alerts.clone(),
+        },
+        retention: match config.retention {
+            Some(retention) => RetentionConfig {
+                events: retention.events as usize,
+                watched_samples: retention.watched_samples as usize,
+            },
+            None => current.retention.clone(),
+        },
+    }
+}
+
+impl From<Entry> for AuditEntry {
+    fn from(entry: Entry) -> Self {
+        AuditEntry {
+            time_ms: entry.time_ms,
+            caller: entry.caller,
+            peer: entry.peer,
+            operation: entry.operation,
+            arguments: entry.arguments,
+            outcome: entry.outcome,
+        }
+    }
 }
 
 #[async_trait]
 impl Monitor for MonitorService {
+    type ExecActionStream = Pin<Box<dyn Stream<Item = Result<ActionOutput, Status>> + Send>>;
+    type NoticesStream = Pin<Box<dyn Stream<Item = Result<Notice, Status>> + Send>>;
+
     async fn monitor_cpu(&self, _request: Request<()>) -> Result<Response<CpuResponse>, Status> {
-        let mut system = self.system.clone().lock_owned().await;
-        let data = MonitoringData::<Cpu>::new(&mut system).await;
+        let pack = self.snapshots.get(&[Cpu::FIELD.to_owned()]).await?;
 
-        match data.map(|x| x.into_data()) {
-            Ok(data) => Ok(Response::new(CpuResponse {
-                usage: data.usage,
-                temperature: data.temperature,
-            })),
-            Err(e) => Err(Status::from_error(Box::new(e))),
-        }
+        Ok(Response::new(pack.cpu.unwrap_or_default()))
     }
 
     async fn monitor_network(
         &self,
         _request: Request<()>,
     ) -> Result<Response<NetworkResponse>, Status> {
-        let mut system = self.system.clone().lock_owned().await;
-        let data = MonitoringData::<Network>::new(&mut system).await;
-
-        match data.map(|x| x.into_data()) {
-            Ok(data) => Ok(Response::new(NetworkResponse {
-                interfaces: data
-                    .names
-                    .into_iter()
-                    .map(|x| NetworkInterface {
-                        name: x.clone(),
-                        bytes_in: data.bytes_in.get(x.as_str()).copied().unwrap_or_default(),
-                        bytes_out: data.bytes_out.get(x.as_str()).copied().unwrap_or_default(),
+        let pack = self.snapshots.get(&[Network::FIELD.to_owned()]).await?;
+
+        Ok(Response::new(pack.network.unwrap_or_default()))
+    }
+
+    async fn monitor_all(&self, request: Request<FieldMask>) -> Result<Response<Pack>, Status> {
+        let paths = request.into_inner().paths;
+
+        self.snapshots.get(&paths).await.map(Response::new)
+    }
+
+    async fn stats(&self, _request: Request<()>) -> Result<Response<ServiceStats>, Status> {
+        Ok(Response::new(self.snapshots.stats().await))
+    }
+
+    async fn list_cgroups(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<CgroupResponse>, Status> {
+        let pack = self.snapshots.get(&[Cgroups::FIELD.to_owned()]).await?;
+
+        Ok(Response::new(pack.cgroups.unwrap_or_default()))
+    }
+
+    async fn list_units(&self, _request: Request<()>) -> Result<Response<UnitResponse>, Status> {
+        let pack = self.snapshots.get(&[Units::FIELD.to_owned()]).await?;
+
+        Ok(Response::new(pack.units.unwrap_or_default()))
+    }
+
+    async fn list_watched(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<WatchedResponse>, Status> {
+        let pack = self.snapshots.get(&[Watched::FIELD.to_owned()]).await?;
+
+        Ok(Response::new(pack.watched.unwrap_or_default()))
+    }
+
+    async fn get_watched_history(
+        &self,
+        request: Request<HistoryRequest>,
+    ) -> Result<Response<HistoryResponse>, Status> {
+        let name = request.into_inner().name;
+        let samples = self
+            .history
+            .get(&name)
+            .ok_or_else(|| Status::not_found(format!("no watched process named {name}")))?;
+
+        Ok(Response::new(HistoryResponse {
+            samples: samples.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_process(
+        &self,
+        request: Request<ProcessRequest>,
+    ) -> Result<Response<ProcessDetails>, Status> {
+        let admin = self.auth.admin(&request);
+        let pid = request.get_ref().pid;
+
+        let result = match processes::read_details(&self.procfs_root, pid, admin.is_some()) {
+            Ok(details) => Ok(Response::new(details.into())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(Status::not_found(format!("no process with PID {pid}")))
+            }
+            Err(e) => Err(Status::internal(e.to_string())),
+        };
+        // The environment may hold secrets, reading it is recorded like an operation
+        if admin.is_some() {
+            self.audit.record(&Entry::new(
+                &request,
+                admin,
+                "GetProcess",
+                format!("pid={pid} environment"),
+                &result,
+            ));
+        }
+        result
+    }
+
+    async fn signal_process(
+        &self,
+        request: Request<SignalRequest>,
+    ) -> Result<Response<()>, Status> {
+        let caller = self.auth.require_admin(&request);
+        let SignalRequest { pid, signal } = *request.get_ref();
+        let signal = match proto::Signal::from_i32(signal) {
+            Some(proto::Signal::Term) => Some(Signal::Term),
+            Some(proto::Signal::Kill) => Some(Signal::Kill),
+            Some(proto::Signal::Hup) => Some(Signal::Hup),
+            Some(proto::Signal::Stop) => Some(Signal::Stop),
+            Some(proto::Signal::Cont) => Some(Signal::Cont),
+            None => None,
+        };
+
+        let result = match (caller, signal) {
+            (Err(denied), _) => Err(denied.into()),
+            (Ok(_), None) => Err(Status::invalid_argument("unknown signal")),
+            (Ok(_), Some(signal)) => self.signal(pid, signal).map_err(|e| match e.kind() {
+                ErrorKind::NotFound => Status::not_found(format!("no process with PID {pid}")),
+                ErrorKind::PermissionDenied => Status::permission_denied(e.to_string()),
+                _ => Status::internal(e.to_string()),
+            }),
+        };
+        self.audit.record(&Entry::new(
+            &request,
+            caller.ok(),
+            "SignalProcess",
+            match signal {
+                Some(signal) => format!("pid={pid} signal={signal:?}"),
+                None => format!("pid={pid} signal={}", request.get_ref().signal),
+            },
+            &result,
+        ));
+        result.map(Response::new)
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<ActionsResponse>, Status> {
+        Ok(Response::new(ActionsResponse {
+            actions: self
+                .actions
+                .list()
+                .map(|(name, description)| Action {
+                    name: name.to_owned(),
+                    description: description.to_owned(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn exec_action(
+        &self,
+        request: Request<ActionRequest>,
+    ) -> Result<Response<Self::ExecActionStream>, Status> {
+        let caller = self.auth.require_admin(&request);
+        let name = &request.get_ref().name;
+        let arguments = format!("name={name}");
+
+        let result = match caller {
+            Err(denied) => Err(denied.into()),
+            Ok(_) => {
+                // Recorded once the action finishes, with its exit
+                let audit = self.audit.clone();
+                let mut entry = Entry::new(
+                    &request,
+                    caller.ok(),
+                    "ExecAction",
+                    arguments.clone(),
+                    &Ok::<_, Status>(()),
+                );
+                let finished = move |exit: actions::Exit| {
+                    entry.outcome = exit.to_string();
+                    audit.record(&entry);
+                };
+
+                self.actions
+                    .start(name, finished)
+                    .map_err(|e| match e.kind() {
+                        ErrorKind::NotFound => Status::not_found(e.to_string()),
+                        ErrorKind::WouldBlock => Status::resource_exhausted(e.to_string()),
+                        _ => Status::internal(e.to_string()),
                     })
-                    .collect(),
+            }
+        };
+        if result.is_err() {
+            self.audit.record(&Entry::new(
+                &request,
+                caller.ok(),
+                "ExecAction",
+                arguments,
+                &result,
+            ));
+        }
+
+        let stream = ReceiverStream::new(result?).map(ActionOutput::from).map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn query_audit(
+        &self,
+        request: Request<AuditRequest>,
+    ) -> Result<Response<AuditResponse>, Status> {
+        self.auth.require_admin(&request)?;
+        let request = request.into_inner();
+        let filter = audit::Filter {
+            since_ms: request.since_ms,
+            until_ms: request.until_ms,
+            caller: request.caller,
+            operation: request.operation,
+            failed_only: request.failed_only,
+        };
+        let limit = match request.limit {
+            0 => DEFAULT_AUDIT_LIMIT,
+            x => (x as usize).min(MAX_AUDIT_LIMIT),
+        };
+
+        match self.audit.query(&filter, limit) {
+            Ok(entries) => Ok(Response::new(AuditResponse {
+                entries: entries.into_iter().map(Into::into).collect(),
             })),
-            Err(e) => Err(Status::from_error(Box::new(e))),
+            Err(e) if e.kind() == ErrorKind::Unsupported => {
+                Err(Status::failed_precondition(e.to_string()))
+            }
+            Err(e) => Err(Status::internal(e.to_string())),
         }
     }
 
-    async fn monitor_all(&self, request: Request<()>) -> Result<Response<Pack>, Status> {
-        try_join!(
-            self.monitor_cpu(Request::new(())),
-            self.monitor_network(request)
-        )
-        .map(|x| {
-            Response::new(Pack {
-                network: Some(x.1.into_inner()),
-                cpu: Some(x.0.into_inner()),
-            })
-        })
+    async fn reboot(
+        &self,
+        request: Request<PowerRequest>,
+    ) -> Result<Response<PowerResponse>, Status> {
+        let PowerRequest {
+            delay_secs,
+            confirm_token,
+        } = request.get_ref();
+
+        self.power(&request, Operation::Reboot, *delay_secs, confirm_token)
+            .await
+    }
+
+    async fn shutdown(
+        &self,
+        request: Request<PowerRequest>,
+    ) -> Result<Response<PowerResponse>, Status> {
+        let PowerRequest {
+            delay_secs,
+            confirm_token,
+        } = request.get_ref();
+
+        self.power(&request, Operation::Shutdown, *delay_secs, confirm_token)
+            .await
+    }
+
+    async fn restart_service(
+        &self,
+        request: Request<RestartServiceRequest>,
+    ) -> Result<Response<PowerResponse>, Status> {
+        let RestartServiceRequest {
+            unit,
+            delay_secs,
+            confirm_token,
+        } = request.get_ref();
+        let operation = Operation::RestartService(unit.clone());
+
+        self.power(&request, operation, *delay_secs, confirm_token)
+            .await
+    }
+
+    async fn notices(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<Self::NoticesStream>, Status> {
+        let mut notices = self.power.subscribe();
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+
+        tokio::spawn(async move {
+            loop {
+                let notice = match notices.recv().await {
+                    Ok(notice) => notice,
+                    // Older notices were dropped for a slow client, newer ones still matter
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+                let notice = Notice {
+                    message: notice.message,
+                    delay_secs: notice.delay.as_secs() as u32,
+                };
+                if sender.send(Ok(notice)).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+
+    async fn upload_binary(
+        &self,
+        mut request: Request<Streaming<BinaryChunk>>,
+    ) -> Result<Response<UploadResponse>, Status> {
+        let caller = self.auth.require_admin(&request);
+        let mut arguments = String::new();
+
+        let result = match caller {
+            Err(denied) => Err(denied.into()),
+            Ok(_) => self.receive_binary(request.get_mut(), &mut arguments).await,
+        };
+        self.audit.record(&Entry::new(
+            &request,
+            caller.ok(),
+            "UploadBinary",
+            arguments,
+            &result,
+        ));
+        result.map(Response::new)
+    }
+
+    async fn get_config(&self, _request: Request<()>) -> Result<Response<RuntimeConfig>, Status> {
+        let settings = self.settings.lock().await;
+
+        Ok(Response::new(self.runtime_config(&settings).await))
+    }
+
+    async fn update_config(
+        &self,
+        request: Request<UpdateConfigRequest>,
+    ) -> Result<Response<RuntimeConfig>, Status> {
+        let caller = self.auth.require_admin(&request);
+        let UpdateConfigRequest { config, persist } = request.get_ref().clone();
+        let current = self.settings.lock().await.clone();
+        let settings = config.map(|x| updated(x, &current));
+        let mut arguments = settings
+            .as_ref()
+            .and_then(|x| serde_json::to_string(x).ok())
+            .unwrap_or_default();
+        if persist {
+            arguments.push_str(" persist");
+        }
+
+        let result = match (caller, settings) {
+            (Err(denied), _) => Err(denied.into()),
+            (Ok(_), None) => Err(Status::invalid_argument("the config is missing")),
+            (Ok(_), Some(settings)) => self.reconfigure(settings, persist).await,
+        };
+        self.audit.record(&Entry::new(
+            &request,
+            caller.ok(),
+            "UpdateConfig",
+            arguments,
+            &result,
+        ));
+        result.map(Response::new)
+    }
+
+    async fn events(
+        &self,
+        request: Request<EventsRequest>,
+    ) -> Result<Response<EventsResponse>, Status> {
+        // Watchers only notice changes when they are collected. A failing watcher is
+        // reported through health checks and must not hide events raised before.
+        let _ = self
+            .snapshots
+            .get(&[Filesystems::FIELD.to_owned(), Watched::FIELD.to_owned()])
+            .await;
+        let after = request.into_inner().after;
+
+        Ok(Response::new(EventsResponse {
+            events: self
+                .events
+                .after(after)
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }))
     }
 }
 
 impl MonitorService {
-    pub fn new(system: System) -> Self {
-        MonitorService {
-            system: Arc::new(Mutex::new(system)),
+    pub fn new(
+        snapshots: Arc<Snapshots>,
+        events: Events,
+        history: History,
+        config: &Config,
+        config_path: Option<PathBuf>,
+        updater: Updater,
+    ) -> Result<Self, Box<dyn Error>> {
+        let max_running = config.max_running_actions.unwrap_or(1);
+        let system = power::Systemd {
+            address: config.systemd.address.clone(),
+        };
+
+        Ok(MonitorService {
+            snapshots,
+            events: events.clone(),
+            history,
+            procfs_root: config.procfs_root().to_owned(),
+            auth: config.auth.clone(),
+            signals: config.signals.clone(),
+            audit: Audit::open(&config.audit)?,
+            actions: Arc::new(Actions::new(&config.actions, max_running)?),
+            power: Power::new(Arc::new(system), events),
+            updater,
+            settings: tokio::sync::Mutex::new(config.settings()),
+            config_path,
+        })
+    }
+
+    async fn runtime_config(&self, settings: &Settings) -> RuntimeConfig {
+        let collectors = self.snapshots.collectors().await;
+
+        RuntimeConfig {
+            sampling_interval_ms: Some(settings.sampling_interval_ms),
+            cache_max_age_ms: Some(settings.cache_max_age_ms),
+            collectors: collectors
+                .into_iter()
+                .map(|(name, config)| CollectorSettings {
+                    name: name.to_owned(),
+                    enabled: config.enabled.unwrap_or_default(),
+                    interval_ms: config.interval_ms,
+                })
+                .collect(),
+            alerts: Some(AlertThresholds {
+                cpu_usage_percent: settings.alerts.cpu_usage_percent,
+                temperature_celsius: settings.alerts.temperature_celsius,
+                load_average: settings.alerts.load_average,
+            }),
+            retention: Some(Retention {
+                events: settings.retention.events as u32,
+                watched_samples: settings.retention.watched_samples as u32,
+            }),
         }
     }
+
+    /// Checks new settings like those of the config file, applies them and writes them back
+    /// to the config file if asked to.
+    async fn reconfigure(
+        &self,
+        settings: Settings,
+        persist: bool,
+    ) -> Result<RuntimeConfig, Status> {
+        settings
+            .validate()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let path = match (persist, &self.config_path) {
+            (true, None) => {
+                return Err(Status::failed_precondition(
+                    "the service was started without a config file",
+                ))
+            }
+            (true, Some(path)) => Some(path),
+            (false, _) => None,
+        };
+
+        let mut current = self.settings.lock().await;
+        self.snapshots
+            .reconfigure(&settings)
+            .await
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.events.keep(settings.retention.events);
+        self.history.keep(settings.retention.watched_samples);
+        *current = settings;
+
+        if let Some(path) = path {
+            current.persist(path).map_err(|e| {
+                Status::internal(format!("the settings are applied but not saved: {e}"))
+            })?;
+        }
+        Ok(self.runtime_config(&current).await)
+    }
+
+    fn signal(&self, pid: u32, signal: Signal) -> std::io::Result<()> {
+        // Read every time, so users created after the start are known
+        let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+        let uids = signals::parse_passwd(&passwd);
+
+        signals::check(&self.signals, &self.procfs_root, &uids, pid)?
+            .send(&self.procfs_root, signal)
+    }
+
+    /// Receives, verifies and installs a new build, then restarts into it.
+    async fn receive_binary(
+        &self,
+        chunks: &mut Streaming<BinaryChunk>,
+        arguments: &mut String,
+    ) -> Result<UploadResponse, Status> {
+        let status = |e: std::io::Error| match e.kind() {
+            ErrorKind::InvalidData | ErrorKind::InvalidInput => {
+                Status::invalid_argument(e.to_string())
+            }
+            ErrorKind::WouldBlock => Status::resource_exhausted(e.to_string()),
+            ErrorKind::Unsupported => Status::failed_precondition(e.to_string()),
+            _ => Status::internal(e.to_string()),
+        };
+        let mut upload = self.updater.upload().map_err(status)?;
+
+        let mut sha256 = None;
+        while let Some(chunk) = chunks.message().await? {
+            sha256.get_or_insert(chunk.sha256);
+            upload.write(&chunk.data).map_err(status)?;
+        }
+        let sha256 = sha256.unwrap_or_default();
+        *arguments = format!("sha256={sha256}");
+        let staged = upload.finish(&sha256).map_err(status)?;
+        let version = staged.verify().await.map_err(status)?;
+        *arguments = format!("sha256={sha256} size={} version={version:?}", staged.size);
+
+        self.updater.restart(staged).map_err(status)?;
+        Ok(UploadResponse {
+            version,
+            health_timeout_secs: self.updater.health_timeout().as_secs() as u32,
+        })
+    }
+
+    /// Both phases of a power operation, audited either way.
+    async fn power<T>(
+        &self,
+        request: &Request<T>,
+        operation: Operation,
+        delay_secs: u32,
+        confirm_token: &str,
+    ) -> Result<Response<PowerResponse>, Status> {
+        let caller = self.auth.require_admin(request);
+        let delay = Duration::from_secs(delay_secs.into());
+        let name = match operation {
+            Operation::Reboot => "Reboot",
+            Operation::Shutdown => "Shutdown",
+            Operation::RestartService(_) => "RestartService",
+        };
+        let mut arguments = match &operation {
+            Operation::RestartService(unit) => format!("unit={unit} delay_secs={delay_secs}"),
+            _ => format!("delay_secs={delay_secs}"),
+        };
+        if !confirm_token.is_empty() {
+            arguments.push_str(" confirmed");
+        }
+
+        let result = match caller {
+            Err(denied) => Err(denied.into()),
+            Ok(caller) => match self.power.request(caller, operation, delay, confirm_token) {
+                Ok(step) => Ok(Response::new(step.into())),
+                Err(e) => Err(match e.kind() {
+                    ErrorKind::InvalidInput => Status::invalid_argument(e.to_string()),
+                    ErrorKind::PermissionDenied => Status::permission_denied(e.to_string()),
+                    _ => Status::internal(e.to_string()),
+                }),
+            },
+        };
+        self.audit
+            .record(&Entry::new(request, caller.ok(), name, arguments, &result));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::monitoring::{CollectorSettings, RuntimeConfig};
+
+    use crate::config::Config;
+
+    use super::updated;
+
+    #[test]
+    fn test_partial_update_keeps_the_rest() {
+        let mut current = Config::default().settings();
+        current.sampling_interval_ms = 250;
+        current
+            .collectors
+            .entry("cpu".to_owned())
+            .or_default()
+            .interval_ms = Some(500);
+
+        let cache_only = RuntimeConfig {
+            cache_max_age_ms: Some(1000),
+            ..Default::default()
+        };
+        let settings = updated(cache_only, &current);
+        assert_eq!(settings.cache_max_age_ms, 1000);
+        assert_eq!(settings.sampling_interval_ms, 250);
+        assert_eq!(settings.collectors, current.collectors);
+        assert_eq!(settings.alerts, current.alerts);
+
+        let collectors = RuntimeConfig {
+            sampling_interval_ms: Some(100),
+            collectors: vec![CollectorSettings {
+                name: "sensors".to_owned(),
+                enabled: false,
+                interval_ms: None,
+            }],
+            ..Default::default()
+        };
+        let settings = updated(collectors, &current);
+        assert_eq!(settings.sampling_interval_ms, 100);
+        assert_eq!(settings.collectors.keys().collect::<Vec<_>>(), ["sensors"]);
+    }
 }
@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use common::monitoring::{DiskResponse, DiskStats, Pack};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+};
+
+/// `/proc/diskstats` always counts 512-byte sectors.
+pub const SECTOR_SIZE: u64 = 512;
+
+/// Cumulative counters of one line of `diskstats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counters {
+    pub reads: u64,
+    pub sectors_read: u64,
+    pub read_ms: u64,
+    pub writes: u64,
+    pub sectors_written: u64,
+    pub write_ms: u64,
+    pub in_flight: u64,
+    pub io_ms: u64,
+    pub weighted_io_ms: u64,
+}
+
+impl Counters {
+    /// Parses the fields after the device name.
+    fn parse(fields: &[&str]) -> Option<Self> {
+        let field = |i: usize| fields.get(i)?.parse().ok();
+
+        Some(Counters {
+            reads: field(0)?,
+            sectors_read: field(2)?,
+            read_ms: field(3)?,
+            writes: field(4)?,
+            sectors_written: field(6)?,
+            write_ms: field(7)?,
+            in_flight: field(8)?,
+            io_ms: field(9)?,
+            weighted_io_ms: field(10)?,
+        })
+    }
+
+    /// Counters are `unsigned long` and wrap on 32-bit boards, so a decrease is taken
+    /// as a restart from zero.
+    fn since(&self, previous: &Counters) -> Counters {
+        let delta = |now: u64, before: u64| if now >= before { now - before } else { now };
+
+        Counters {
+            reads: delta(self.reads, previous.reads),
+            sectors_read: delta(self.sectors_read, previous.sectors_read),
+            read_ms: delta(self.read_ms, previous.read_ms),
+            writes: delta(self.writes, previous.writes),
+            sectors_written: delta(self.sectors_written, previous.sectors_written),
+            write_ms: delta(self.write_ms, previous.write_ms),
+            in_flight: self.in_flight,
+            io_ms: delta(self.io_ms, previous.io_ms),
+            weighted_io_ms: delta(self.weighted_io_ms, previous.weighted_io_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Disk {
+    pub name: String,
+    pub read_bytes_per_second: f32,
+    pub write_bytes_per_second: f32,
+    pub read_iops: f32,
+    pub write_iops: f32,
+    pub average_latency_ms: f32,
+    /// Average number of requests in flight.
+    pub queue_depth: f32,
+    /// Percent of time the device was busy.
+    pub utilization: f32,
+    pub counters: Counters,
+}
+
+impl Disk {
+    fn new(name: String, counters: Counters, delta: Counters, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f32();
+        let ms = seconds * 1000.0;
+        let ios = delta.reads + delta.writes;
+        let per_second = |x: u64| {
+            if seconds > 0.0 {
+                x as f32 / seconds
+            } else {
+                0.0
+            }
+        };
+        let per_ms = |x: u64| if ms > 0.0 { x as f32 / ms } else { 0.0 };
+
+        Disk {
+            name,
+            read_bytes_per_second: per_second(delta.sectors_read * SECTOR_SIZE),
+            write_bytes_per_second: per_second(delta.sectors_written * SECTOR_SIZE),
+            read_iops: per_second(delta.reads),
+            write_iops: per_second(delta.writes),
+            average_latency_ms: if ios > 0 {
+                (delta.read_ms + delta.write_ms) as f32 / ios as f32
+            } else {
+                0.0
+            },
+            queue_depth: per_ms(delta.weighted_io_ms),
+            utilization: (per_ms(delta.io_ms) * 100.0).min(100.0),
+            counters,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Disks(pub Vec<Disk>);
+
+/// Parses `diskstats` into counters of every device.
+pub(crate) fn read_diskstats(procfs_root: &Path) -> std::io::Result<Vec<(String, Counters)>> {
+    let path = procfs_root.join("diskstats");
+
+    std::fs::read_to_string(&path)?
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let counters = fields.get(3..).and_then(Counters::parse).ok_or_else(|| {
+                std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("cannot parse {}: {line}", path.display()),
+                )
+            })?;
+            Ok((fields[2].to_owned(), counters))
+        })
+        .collect()
+}
+
+/// Whole physical devices: partitions, loop and RAM devices are skipped.
+pub(crate) fn is_disk(sysfs_root: &Path, name: &str) -> bool {
+    !name.starts_with("loop")
+        && !name.starts_with("ram")
+        && !sysfs_root
+            .join("class/block")
+            .join(name)
+            .join("partition")
+            .exists()
+}
+
+/// Reports I/O rates of block devices from the change of `diskstats` between collections.
+#[derive(Debug)]
+pub struct DisksCollector {
+    sysfs_root: PathBuf,
+    procfs_root: PathBuf,
+    previous: Option<(Instant, HashMap<String, Counters>)>,
+}
+
+impl DisksCollector {
+    pub fn new(sysfs_root: impl Into<PathBuf>, procfs_root: impl Into<PathBuf>) -> Self {
+        DisksCollector {
+            sysfs_root: sysfs_root.into(),
+            procfs_root: procfs_root.into(),
+            previous: None,
+        }
+    }
+
+    fn update(&mut self, now: Instant, stats: Vec<(String, Counters)>) -> Disks {
+        let stats = stats
+            .into_iter()
+            .filter(|x| is_disk(&self.sysfs_root, &x.0))
+            .collect::<Vec<_>>();
+        let previous = self.previous.take();
+
+        let disks = stats
+            .iter()
+            .map(|(name, counters)| {
+                let (delta, elapsed) = match &previous {
+                    Some((at, previous)) => previous
+                        .get(name)
+                        .map_or((Counters::default(), Duration::ZERO), |x| {
+                            (counters.since(x), now.saturating_duration_since(*at))
+                        }),
+                    None => (Counters::default(), Duration::ZERO),
+                };
+                Disk::new(name.clone(), *counters, delta, elapsed)
+            })
+            .collect();
+
+        self.previous = Some((now, stats.into_iter().collect()));
+        Disks(disks)
+    }
+}
+
+impl Collector for DisksCollector {
+    type Output = Disks;
+
+    fn name(&self) -> &'static str {
+        "disks"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        let stats = read_diskstats(&self.procfs_root)?;
+        Ok(self.update(Instant::now(), stats))
+    }
+}
+
+impl Section for Disks {
+    const FIELD: &'static str = "disks";
+
+    fn store(self, pack: &mut Pack) {
+        pack.disks = Some(DiskResponse {
+            disks: self
+                .0
+                .into_iter()
+                .map(|x| DiskStats {
+                    name: x.name,
+                    read_bytes_per_second: x.read_bytes_per_second,
+                    write_bytes_per_second: x.write_bytes_per_second,
+                    read_iops: x.read_iops,
+                    write_iops: x.write_iops,
+                    average_latency_ms: x.average_latency_ms,
+                    queue_depth: x.queue_depth,
+                    utilization: x.utilization,
+                    bytes_read: x.counters.sectors_read * SECTOR_SIZE,
+                    bytes_written: x.counters.sectors_written * SECTOR_SIZE,
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.disks = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::test_util::Fixture;
+
+    use super::{read_diskstats, Counters, DisksCollector};
+
+    const BEFORE: &str = "\
+ 179       0 mmcblk0 1000 10 80000 4000 500 20 40000 10000 0 6000 14000 0 0 0 0
+ 179       1 mmcblk0p1 1000 10 80000 4000 500 20 40000 10000 0 6000 14000 0 0 0 0
+   7       0 loop0 10 0 20 1 0 0 0 0 0 1 1 0 0 0 0";
+    const AFTER: &str = "\
+ 179       0 mmcblk0 1100 10 82048 4500 600 20 44096 11500 3 6500 16000 0 0 0 0
+ 179       1 mmcblk0p1 1100 10 82048 4500 600 20 44096 11500 3 6500 16000 0 0 0 0
+   7       0 loop0 10 0 20 1 0 0 0 0 0 1 1 0 0 0 0";
+
+    #[test]
+    fn test_rates_from_deltas() {
+        let fixture = Fixture::new("disks-rates");
+        fixture
+            .write("proc/diskstats", BEFORE)
+            .write("sys/class/block/mmcblk0p1/partition", "1");
+        let mut collector = DisksCollector::new(fixture.path().join("sys"), "");
+        let start = Instant::now();
+
+        let first = collector.update(start, read_diskstats(&fixture.path().join("proc")).unwrap());
+        assert_eq!(first.0.len(), 1);
+        assert_eq!(first.0[0].read_iops, 0.0);
+
+        fixture.write("proc/diskstats", AFTER);
+        let stats = read_diskstats(&fixture.path().join("proc")).unwrap();
+        let disks = collector.update(start + Duration::from_secs(2), stats);
+        let disk = &disks.0[0];
+
+        assert_eq!(disk.name, "mmcblk0");
+        assert_eq!(disk.read_bytes_per_second, 2048.0 * 512.0 / 2.0);
+        assert_eq!(disk.write_bytes_per_second, 4096.0 * 512.0 / 2.0);
+        assert_eq!((disk.read_iops, disk.write_iops), (50.0, 50.0));
+        assert_eq!(disk.average_latency_ms, 2000.0 / 200.0);
+        assert_eq!(disk.queue_depth, 1.0);
+        assert_eq!(disk.utilization, 25.0);
+        assert_eq!(disk.counters.in_flight, 3);
+    }
+
+    #[test]
+    fn test_wrapped_counters_restart_from_zero() {
+        let mut collector = DisksCollector::new("", "");
+        let start = Instant::now();
+        let stats = |writes| {
+            let counters = Counters {
+                writes,
+                ..Default::default()
+            };
+            vec![("sda".to_owned(), counters)]
+        };
+
+        collector.update(start, stats(u32::MAX as u64));
+        let disks = collector.update(start + Duration::from_secs(1), stats(10));
+        assert_eq!(disks.0[0].write_iops, 10.0);
+    }
+
+    #[test]
+    fn test_malformed_diskstats_fails() {
+        let fixture = Fixture::new("disks-malformed");
+        fixture.write("diskstats", "179 0 mmcblk0 1 2");
+
+        assert!(read_diskstats(fixture.path()).is_err());
+    }
+}
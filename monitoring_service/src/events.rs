@@ -0,0 +1,109 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// How many events are kept for clients that poll rarely, unless `[retention]` says otherwise.
+pub const KEPT_EVENTS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Increases by one with every event, so clients can ask for the ones they missed.
+    pub id: u64,
+    pub time: SystemTime,
+    pub severity: Severity,
+    /// Name of the collector that raised the event.
+    pub source: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug)]
+struct Log {
+    last_id: u64,
+    kept: usize,
+    events: VecDeque<Event>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            last_id: 0,
+            kept: KEPT_EVENTS,
+            events: VecDeque::new(),
+        }
+    }
+}
+
+/// Events raised by collectors, shared with the service.
+#[derive(Debug, Clone, Default)]
+pub struct Events(Arc<Mutex<Log>>);
+
+impl Events {
+    pub fn raise(&self, source: &'static str, severity: Severity, message: String) {
+        let mut log = self.0.lock().unwrap();
+        log.last_id += 1;
+        let event = Event {
+            id: log.last_id,
+            time: SystemTime::now(),
+            severity,
+            source,
+            message,
+        };
+
+        if log.events.len() >= log.kept {
+            log.events.pop_front();
+        }
+        log.events.push_back(event);
+    }
+
+    /// Keeps at most `count` events from now on, dropping the oldest ones beyond it.
+    pub fn keep(&self, count: usize) {
+        let mut log = self.0.lock().unwrap();
+        let excess = log.events.len().saturating_sub(count);
+        log.events.drain(..excess);
+        log.kept = count;
+    }
+
+    /// Kept events newer than `id`, oldest first.
+    pub fn after(&self, id: u64) -> Vec<Event> {
+        let log = self.0.lock().unwrap();
+
+        log.events.iter().filter(|x| x.id > id).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Events, Severity, KEPT_EVENTS};
+
+    #[test]
+    fn test_events_after_id() {
+        let events = Events::default();
+        for i in 0..KEPT_EVENTS + 2 {
+            events.raise("test", Severity::Info, i.to_string());
+        }
+
+        let kept = events.after(0);
+        assert_eq!(kept.len(), KEPT_EVENTS);
+        assert_eq!(kept[0].id, 3);
+        assert_eq!(kept[0].message, "2");
+
+        let latest = events.after(KEPT_EVENTS as u64 + 1);
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].severity, Severity::Info);
+
+        events.keep(2);
+        events.raise("test", Severity::Warning, "last".to_owned());
+        let kept = events.after(0);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[1].message, "last");
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use common::monitoring::{self as proto, FilesystemResponse, Pack};
+
+use crate::{
+    collector::{Collector, Section},
+    events::{Events, Severity},
+    source::SystemSource,
+    thermal::read_number,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub read_only: bool,
+    /// `errors_count` of ext4 filesystems.
+    pub errors: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filesystems(pub Vec<Mount>);
+
+/// Undoes the octal escapes of spaces, tabs and backslashes in `mounts`.
+fn unescape(field: &str) -> String {
+    let mut result = String::new();
+    let mut rest = field;
+
+    while let Some(i) = rest.find('\\') {
+        result.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|x| u8::from_str_radix(x, 8).ok());
+        match code {
+            Some(code) => {
+                result.push(code as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Mounts of block devices, pseudo filesystems are skipped.
+fn read_mounts(procfs_root: &Path) -> std::io::Result<Vec<Mount>> {
+    let path = procfs_root.join("mounts");
+
+    std::fs::read_to_string(&path)?
+        .lines()
+        .filter(|x| x.starts_with("/dev/"))
+        .map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let [device, mount_point, fs_type, options, ..] = fields[..] else {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("cannot parse {}: {line}", path.display()),
+                ));
+            };
+
+            Ok(Mount {
+                device: unescape(device),
+                mount_point: unescape(mount_point),
+                fs_type: fs_type.to_owned(),
+                read_only: options.split(',').any(|x| x == "ro"),
+                errors: None,
+            })
+        })
+        .collect()
+}
+
+/// Kernel name of the device mounted at `mount_point`, looked up by its numbers since
+/// `/dev/root` has no node of that name.
+fn device_name(sysfs_root: &Path, procfs_root: &Path, mount_point: &str) -> Option<String> {
+    let mountinfo = std::fs::read_to_string(procfs_root.join("self/mountinfo")).ok()?;
+    let numbers = mountinfo.lines().find_map(|line| {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let [_, _, numbers, _, point, ..] = fields[..] else {
+            return None;
+        };
+        (unescape(point) == mount_point).then(|| numbers.to_owned())
+    })?;
+    let device = std::fs::read_link(sysfs_root.join("dev/block").join(numbers)).ok()?;
+
+    Some(device.file_name()?.to_string_lossy().into_owned())
+}
+
+/// `errors_count` of every ext4 filesystem by device name.
+fn read_ext4_errors(sysfs_root: &Path) -> std::io::Result<BTreeMap<String, u64>> {
+    let entries = match std::fs::read_dir(sysfs_root.join("fs/ext4")) {
+        Ok(entries) => entries,
+        // ext4 is a module that may be not loaded
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut errors = BTreeMap::new();
+
+    for entry in entries {
+        let path = entry?.path().join("errors_count");
+        if let (Some(name), Ok(count)) = (
+            path.parent().and_then(|x| x.file_name()),
+            read_number::<u64>(&path),
+        ) {
+            errors.insert(name.to_string_lossy().into_owned(), count);
+        }
+    }
+    Ok(errors)
+}
+
+/// Watches mounts flipping to read-only and ext4 error counters growing, the usual signs of
+/// a failing SD card.
+#[derive(Debug)]
+pub struct FilesystemsCollector {
+    sysfs_root: PathBuf,
+    procfs_root: PathBuf,
+    events: Events,
+    previous: Option<(HashMap<String, bool>, BTreeMap<String, u64>)>,
+}
+
+impl FilesystemsCollector {
+    pub fn new(
+        sysfs_root: impl Into<PathBuf>,
+        procfs_root: impl Into<PathBuf>,
+        events: Events,
+    ) -> Self {
+        FilesystemsCollector {
+            sysfs_root: sysfs_root.into(),
+            procfs_root: procfs_root.into(),
+            events,
+            previous: None,
+        }
+    }
+
+    /// Raises events for changes since the previous update. Mounts already read-only are
+    /// reported by the first update, which is only a baseline for the error counters, as
+    /// errors counted before are kept in the superblock across boots.
+    fn update(&mut self, mut mounts: Vec<Mount>, errors: BTreeMap<String, u64>) -> Filesystems {
+        for mount in &mut mounts {
+            let name = mount.device.rsplit('/').next().unwrap_or_default();
+            mount.errors = errors.get(name).copied();
+        }
+
+        let read_only = self.previous.as_ref().map(|x| &x.0);
+        for mount in &mounts {
+            let name = format!("{} ({})", mount.mount_point, mount.device);
+            match (
+                read_only.map(|x| x.get(&mount.mount_point)),
+                mount.read_only,
+            ) {
+                (None, true) => self.events.raise(
+                    "filesystems",
+                    Severity::Warning,
+                    format!("{name} is mounted read-only"),
+                ),
+                (Some(Some(false)), true) => self.events.raise(
+                    "filesystems",
+                    Severity::Critical,
+                    format!("{name} was remounted read-only"),
+                ),
+                (Some(Some(true)), false) => self.events.raise(
+                    "filesystems",
+                    Severity::Info,
+                    format!("{name} is writable again"),
+                ),
+                _ => {}
+            }
+        }
+        if let Some((_, previous_errors)) = &self.previous {
+            for (device, &count) in &errors {
+                let before = previous_errors.get(device).copied().unwrap_or_default();
+                if count > before {
+                    self.events.raise(
+                        "filesystems",
+                        Severity::Critical,
+                        format!(
+                            "ext4 on {device} recorded {} new errors, {count} in total",
+                            count - before
+                        ),
+                    );
+                }
+            }
+        }
+
+        let read_only = mounts
+            .iter()
+            .map(|x| (x.mount_point.clone(), x.read_only))
+            .collect();
+        self.previous = Some((read_only, errors));
+        Filesystems(mounts)
+    }
+}
+
+impl Collector for FilesystemsCollector {
+    type Output = Filesystems;
+
+    fn name(&self) -> &'static str {
+        "filesystems"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        let mut mounts = read_mounts(&self.procfs_root)?;
+        for mount in mounts.iter_mut().filter(|x| x.device == "/dev/root") {
+            if let Some(name) = device_name(&self.sysfs_root, &self.procfs_root, &mount.mount_point)
+            {
+                mount.device = format!("/dev/{name}");
+            }
+        }
+        let errors = read_ext4_errors(&self.sysfs_root)?;
+
+        Ok(self.update(mounts, errors))
+    }
+}
+
+impl Section for Filesystems {
+    const FIELD: &'static str = "filesystems";
+
+    fn store(self, pack: &mut Pack) {
+        pack.filesystems = Some(FilesystemResponse {
+            mounts: self
+                .0
+                .into_iter()
+                .map(|x| proto::Mount {
+                    device: x.device,
+                    mount_point: x.mount_point,
+                    fs_type: x.fs_type,
+                    read_only: x.read_only,
+                    errors: x.errors,
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.filesystems = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sysinfo::{System, SystemExt};
+
+    use crate::{
+        collector::Collector,
+        events::{Events, Severity},
+        test_util::Fixture,
+    };
+
+    use super::{unescape, FilesystemsCollector};
+
+    const MOUNTS: &str = "\
+/dev/mmcblk0p2 / ext4 rw,noatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sda1 /mnt/usb\\040disk vfat rw,relatime 0 0";
+
+    #[test]
+    fn test_read_only_remount_and_errors_raise_events() {
+        let fixture = Fixture::new("filesystems-remount");
+        fixture
+            .write("proc/mounts", MOUNTS)
+            .write("sys/fs/ext4/mmcblk0p2/errors_count", "2");
+        let events = Events::default();
+        let mut collector = FilesystemsCollector::new(
+            fixture.path().join("sys"),
+            fixture.path().join("proc"),
+            events.clone(),
+        );
+
+        let filesystems = collector.collect(&mut System::new()).unwrap();
+        assert_eq!(filesystems.0.len(), 2);
+        assert_eq!(filesystems.0[0].errors, Some(2));
+        assert_eq!(filesystems.0[1].mount_point, "/mnt/usb disk");
+        assert!(events.after(0).is_empty());
+
+        fixture
+            .write("proc/mounts", &MOUNTS.replace("ext4 rw", "ext4 ro"))
+            .write("sys/fs/ext4/mmcblk0p2/errors_count", "5");
+        let filesystems = collector.collect(&mut System::new()).unwrap();
+        assert!(filesystems.0[0].read_only);
+
+        let raised = events.after(0);
+        assert_eq!(raised.len(), 2);
+        assert_eq!(raised[0].severity, Severity::Critical);
+        assert_eq!(
+            raised[0].message,
+            "/ (/dev/mmcblk0p2) was remounted read-only"
+        );
+        assert_eq!(
+            raised[1].message,
+            "ext4 on mmcblk0p2 recorded 3 new errors, 5 in total"
+        );
+
+        collector.collect(&mut System::new()).unwrap();
+        assert_eq!(events.after(0).len(), 2);
+    }
+
+    #[test]
+    fn test_read_only_at_start_and_dev_root() {
+        let fixture = Fixture::new("filesystems-root");
+        fixture
+            .write("proc/mounts", "/dev/root / ext4 ro,relatime 0 0")
+            .write(
+                "proc/self/mountinfo",
+                "23 1 179:2 / / ro,relatime shared:1 - ext4 /dev/root ro",
+            )
+            .write("sys/fs/ext4/mmcblk0p2/errors_count", "1");
+        std::fs::create_dir_all(fixture.path().join("sys/dev/block")).unwrap();
+        std::os::unix::fs::symlink(
+            "../../devices/platform/mmc/block/mmcblk0/mmcblk0p2",
+            fixture.path().join("sys/dev/block/179:2"),
+        )
+        .unwrap();
+        let events = Events::default();
+        let mut collector = FilesystemsCollector::new(
+            fixture.path().join("sys"),
+            fixture.path().join("proc"),
+            events.clone(),
+        );
+
+        let filesystems = collector.collect(&mut System::new()).unwrap();
+        assert_eq!(filesystems.0[0].device, "/dev/mmcblk0p2");
+        assert_eq!(filesystems.0[0].errors, Some(1));
+        let raised = events.after(0);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].severity, Severity::Warning);
+        assert_eq!(raised[0].message, "/ (/dev/mmcblk0p2) is mounted read-only");
+
+        collector.collect(&mut System::new()).unwrap();
+        assert_eq!(events.after(0).len(), 1);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("/mnt/a\\040b\\134c"), "/mnt/a b\\c");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use tokio::sync::Mutex;
+use tonic_health::{server::HealthReporter, ServingStatus};
+
+/// Consecutive failures after which a collector is reported as not serving.
+const FAILURE_THRESHOLD: u32 = 3;
+
+#[derive(Debug, Default)]
+struct FailureCounter {
+    failures: HashMap<&'static str, u32>,
+}
+
+impl FailureCounter {
+    /// Returns the new status of a collector if it has changed.
+    fn record(&mut self, collector: &'static str, success: bool) -> Option<ServingStatus> {
+        let previous = self.failures.insert(collector, 0);
+        let failures = self.failures.get_mut(collector).unwrap();
+        if !success {
+            *failures = previous.unwrap_or_default().saturating_add(1);
+        }
+
+        let was_failing = previous.map(|x| x >= FAILURE_THRESHOLD);
+        let failing = *failures >= FAILURE_THRESHOLD;
+
+        match (was_failing, failing) {
+            (Some(was), now) if was == now => None,
+            (_, true) => Some(ServingStatus::NotServing),
+            (_, false) => Some(ServingStatus::Serving),
+        }
+    }
+}
+
+/// Publishes the state of every collector through `grpc.health.v1.Health`
+/// under the name `monitor.Monitor/<collector>`.
+pub struct CollectorHealth {
+    reporter: HealthReporter,
+    counter: Mutex<FailureCounter>,
+}
+
+impl CollectorHealth {
+    pub async fn new(reporter: HealthReporter, collectors: &[&'static str]) -> Self {
+        let health = CollectorHealth {
+            reporter,
+            counter: Default::default(),
+        };
+        for collector in collectors {
+            health.report(collector, true).await;
+        }
+        health
+    }
+
+    pub async fn report(&self, collector: &'static str, success: bool) {
+        let status = self.counter.lock().await.record(collector, success);
+
+        if let Some(status) = status {
+            self.reporter
+                .clone()
+                .set_service_status(format!("monitor.Monitor/{collector}"), status)
+                .await;
+        }
+    }
+
+    /// Removes the status of a disabled collector, so it reads as unknown.
+    pub async fn forget(&self, collector: &'static str) {
+        self.counter.lock().await.failures.remove(collector);
+        self.reporter
+            .clone()
+            .clear_service_status(&format!("monitor.Monitor/{collector}"))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic_health::ServingStatus;
+
+    use super::{FailureCounter, FAILURE_THRESHOLD};
+
+    #[test]
+    fn test_first_report_sets_status() {
+        let mut counter = FailureCounter::default();
+
+        assert_eq!(counter.record("cpu", true), Some(ServingStatus::Serving));
+        assert_eq!(counter.record("cpu", true), None);
+    }
+
+    #[test]
+    fn test_not_serving_after_threshold() {
+        let mut counter = FailureCounter::default();
+        counter.record("sensors", true);
+
+        for _ in 1..FAILURE_THRESHOLD {
+            assert_eq!(counter.record("sensors", false), None);
+        }
+        assert_eq!(
+            counter.record("sensors", false),
+            Some(ServingStatus::NotServing)
+        );
+        assert_eq!(counter.record("sensors", false), None);
+        assert_eq!(
+            counter.record("sensors", true),
+            Some(ServingStatus::Serving)
+        );
+    }
+
+    #[test]
+    fn test_collectors_are_independent() {
+        let mut counter = FailureCounter::default();
+
+        for _ in 0..FAILURE_THRESHOLD {
+            counter.record("sensors", false);
+        }
+        assert_eq!(
+            counter.record("network", true),
+            Some(ServingStatus::Serving)
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    net::SocketAddr,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{lookup_host, TcpListener, TcpStream},
+};
+use tokio_stream::{wrappers::TcpListenerStream, Stream, StreamExt, StreamMap};
+use tonic::transport::server::{Connected, TcpConnectInfo};
+
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use tokio_stream::wrappers::UnixListenerStream;
+#[cfg(unix)]
+use tonic::transport::server::UdsConnectInfo;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp {
+        host: String,
+        port: u16,
+    },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseListenAddrError(String);
+
+impl Display for ParseListenAddrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid listen address: {}", self.0)
+    }
+}
+
+impl Error for ParseListenAddrError {}
+
+impl FromStr for ListenAddr {
+    type Err = ParseListenAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return match path {
+                "" => Err(ParseListenAddrError("empty socket path".to_owned())),
+                path => Ok(ListenAddr::Unix(PathBuf::from(path))),
+            };
+            #[cfg(not(unix))]
+            return Err(ParseListenAddrError(format!(
+                "unix sockets are not supported on this platform: {path}"
+            )));
+        }
+
+        let (host, port) = s
+            .rsplit_once(':')
+            .ok_or_else(|| ParseListenAddrError(format!("missing port in {s}")))?;
+        let port = port
+            .parse()
+            .map_err(|_| ParseListenAddrError(format!("bad port in {s}")))?;
+        let host = match host.strip_prefix('[') {
+            Some(rest) => rest
+                .strip_suffix(']')
+                .ok_or_else(|| ParseListenAddrError(format!("unclosed bracket in {s}")))?,
+            None if host.contains(':') => {
+                return Err(ParseListenAddrError(format!(
+                    "IPv6 addresses must be enclosed in brackets: {s}"
+                )))
+            }
+            None => host,
+        };
+        if host.is_empty() {
+            return Err(ParseListenAddrError(format!("missing host in {s}")));
+        }
+
+        Ok(ListenAddr::Tcp {
+            host: host.to_owned(),
+            port,
+        })
+    }
+}
+
+impl Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddr::Tcp { host, port } if host.contains(':') => write!(f, "[{host}]:{port}"),
+            ListenAddr::Tcp { host, port } => write!(f, "{host}:{port}"),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(SocketAddr, TcpListener),
+    #[cfg(unix)]
+    Unix(PathBuf, UnixListener),
+}
+
+impl Display for Listener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Listener::Tcp(addr, _) => write!(f, "{addr}"),
+            #[cfg(unix)]
+            Listener::Unix(path, _) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Listener {
+    fn into_stream(self) -> Pin<Box<dyn Stream<Item = std::io::Result<Connection>> + Send>> {
+        match self {
+            Listener::Tcp(_, listener) => Box::pin(TcpListenerStream::new(listener).map(|x| {
+                let stream = x?;
+                stream.set_nodelay(true)?;
+                Ok(Connection::Tcp(stream))
+            })),
+            #[cfg(unix)]
+            Listener::Unix(_, listener) => {
+                Box::pin(UnixListenerStream::new(listener).map(|x| x.map(Connection::Unix)))
+            }
+        }
+    }
+}
+
+/// Merges the connections accepted by every listener into a single stream,
+/// so one server instance handles all of them.
+pub fn incoming(
+    listeners: Vec<Listener>,
+) -> impl Stream<Item = std::io::Result<Connection>> + Send {
+    listeners
+        .into_iter()
+        .map(Listener::into_stream)
+        .enumerate()
+        .collect::<StreamMap<_, _>>()
+        .map(|(_, connection)| connection)
+}
+
+pub enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+#[derive(Debug, Clone)]
+pub enum ConnectionInfo {
+    Tcp(TcpConnectInfo),
+    #[cfg(unix)]
+    Unix(UdsConnectInfo),
+}
+
+impl Display for ConnectionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionInfo::Tcp(info) => match info.remote_addr() {
+                Some(addr) => write!(f, "{addr}"),
+                None => write!(f, "unknown tcp peer"),
+            },
+            #[cfg(unix)]
+            ConnectionInfo::Unix(info) => match info.peer_cred {
+                Some(cred) => write!(f, "unix peer uid={} pid={:?}", cred.uid(), cred.pid()),
+                None => write!(f, "unix peer"),
+            },
+        }
+    }
+}
+
+impl Connected for Connection {
+    type ConnectInfo = ConnectionInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        match self {
+            Connection::Tcp(stream) => ConnectionInfo::Tcp(stream.connect_info()),
+            #[cfg(unix)]
+            Connection::Unix(stream) => ConnectionInfo::Unix(stream.connect_info()),
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Connection::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Connection::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Connection::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Connection::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Connection::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Connection::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+impl ListenAddr {
+    /// Resolves the address and binds every socket it refers to.
+    /// A hostname may resolve to several addresses (e.g. both IPv4 and IPv6 loopback).
+    pub async fn bind(&self) -> std::io::Result<Vec<Listener>> {
+        match self {
+            ListenAddr::Tcp { host, port } => {
+                let mut addrs = lookup_host((host.as_str(), *port))
+                    .await?
+                    .collect::<Vec<_>>();
+                addrs.sort();
+                addrs.dedup();
+
+                let mut listeners = Vec::with_capacity(addrs.len());
+                for addr in addrs {
+                    let listener = TcpListener::bind(addr).await?;
+                    listeners.push(Listener::Tcp(listener.local_addr()?, listener));
+                }
+                Ok(listeners)
+            }
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                use std::os::unix::fs::FileTypeExt;
+
+                // A socket left over from a previous run would make bind fail.
+                // The lock file guarantees we are the only instance, so it is safe to remove.
+                if let Ok(meta) = std::fs::symlink_metadata(path) {
+                    if meta.file_type().is_socket() {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+                Ok(vec![Listener::Unix(
+                    path.clone(),
+                    UnixListener::bind(path)?,
+                )])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListenAddr, Listener};
+
+    fn tcp(host: &str, port: u16) -> ListenAddr {
+        ListenAddr::Tcp {
+            host: host.to_owned(),
+            port,
+        }
+    }
+
+    #[test]
+    fn test_parse_ipv4() {
+        assert_eq!("0.0.0.0:50501".parse(), Ok(tcp("0.0.0.0", 50501)));
+    }
+
+    #[test]
+    fn test_parse_ipv6() {
+        assert_eq!("[::1]:50501".parse(), Ok(tcp("::1", 50501)));
+        assert!("::1:50501".parse::<ListenAddr>().is_err());
+        assert!("[::1:50501".parse::<ListenAddr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_hostname() {
+        assert_eq!("orangepi:80".parse(), Ok(tcp("orangepi", 80)));
+        assert!("orangepi".parse::<ListenAddr>().is_err());
+        assert!(":80".parse::<ListenAddr>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_unix() {
+        assert_eq!(
+            "unix:/run/monitor.sock".parse(),
+            Ok(ListenAddr::Unix("/run/monitor.sock".into()))
+        );
+        assert!("unix:".parse::<ListenAddr>().is_err());
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for addr in ["127.0.0.1:1", "[::]:2", "localhost:3"] {
+            assert_eq!(addr.parse::<ListenAddr>().unwrap().to_string(), addr);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_resolves_hostname() {
+        let listeners = tcp("localhost", 0).bind().await.unwrap();
+
+        assert!(!listeners.is_empty());
+        assert!(listeners
+            .iter()
+            .all(|x| matches!(x, Listener::Tcp(addr, _) if addr.ip().is_loopback())));
+    }
+}
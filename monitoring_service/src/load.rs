@@ -0,0 +1,244 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use common::monitoring::{self as proto, LoadResponse, Pack, PressureStall};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+};
+
+/// One line of a PSI file, averages are percentages of wall time.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stall {
+    pub avg10: f32,
+    pub avg60: f32,
+    pub avg300: f32,
+    pub total_us: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pressure {
+    pub some: Stall,
+    /// Missing for the CPU before Linux 5.13.
+    pub full: Option<Stall>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Load {
+    pub averages: [f32; 3],
+    pub runnable_tasks: u32,
+    pub total_tasks: u32,
+    /// Missing when the kernel is built without `CONFIG_PSI`.
+    pub cpu: Option<Pressure>,
+    pub memory: Option<Pressure>,
+    pub io: Option<Pressure>,
+}
+
+fn invalid(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidData,
+        format!("cannot parse {}", path.display()),
+    )
+}
+
+impl Stall {
+    /// Parses `avg10=0.12 avg60=0.05 avg300=0.01 total=12345`.
+    fn parse(fields: &str) -> Option<Self> {
+        let mut stall = Stall::default();
+
+        for field in fields.split_whitespace() {
+            match field.split_once('=')? {
+                ("avg10", x) => stall.avg10 = x.parse().ok()?,
+                ("avg60", x) => stall.avg60 = x.parse().ok()?,
+                ("avg300", x) => stall.avg300 = x.parse().ok()?,
+                ("total", x) => stall.total_us = x.parse().ok()?,
+                _ => {}
+            }
+        }
+        Some(stall)
+    }
+}
+
+impl Pressure {
+    fn read(path: &Path) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut some = None;
+        let mut full = None;
+
+        for line in content.lines() {
+            match line.split_once(' ') {
+                Some(("some", x)) => some = Some(Stall::parse(x).ok_or_else(|| invalid(path))?),
+                Some(("full", x)) => full = Some(Stall::parse(x).ok_or_else(|| invalid(path))?),
+                _ => {}
+            }
+        }
+
+        Ok(Pressure {
+            some: some.ok_or_else(|| invalid(path))?,
+            full,
+        })
+    }
+}
+
+impl Load {
+    fn read(procfs_root: &Path) -> std::io::Result<Self> {
+        let path = procfs_root.join("loadavg");
+        let content = std::fs::read_to_string(&path)?;
+        let fields = content.split_whitespace().collect::<Vec<_>>();
+
+        let average = |i: usize| -> std::io::Result<f32> {
+            fields
+                .get(i)
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(|| invalid(&path))
+        };
+        let (runnable, total) = fields
+            .get(3)
+            .and_then(|x| x.split_once('/'))
+            .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
+            .ok_or_else(|| invalid(&path))?;
+        let pressure = |x: &str| match Pressure::read(&procfs_root.join("pressure").join(x)) {
+            Ok(pressure) => Ok(Some(pressure)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        };
+
+        Ok(Load {
+            averages: [average(0)?, average(1)?, average(2)?],
+            runnable_tasks: runnable,
+            total_tasks: total,
+            cpu: pressure("cpu")?,
+            memory: pressure("memory")?,
+            io: pressure("io")?,
+        })
+    }
+}
+
+/// Reads `loadavg` and Pressure Stall Information from procfs.
+#[derive(Debug)]
+pub struct LoadCollector {
+    procfs_root: PathBuf,
+}
+
+impl LoadCollector {
+    pub fn new(procfs_root: impl Into<PathBuf>) -> Self {
+        LoadCollector {
+            procfs_root: procfs_root.into(),
+        }
+    }
+}
+
+impl Collector for LoadCollector {
+    type Output = Load;
+
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        Load::read(&self.procfs_root)
+    }
+}
+
+impl From<Stall> for PressureStall {
+    fn from(stall: Stall) -> Self {
+        PressureStall {
+            avg10: stall.avg10,
+            avg60: stall.avg60,
+            avg300: stall.avg300,
+            total_us: stall.total_us,
+        }
+    }
+}
+
+impl From<Pressure> for proto::Pressure {
+    fn from(pressure: Pressure) -> Self {
+        proto::Pressure {
+            some: Some(pressure.some.into()),
+            full: pressure.full.map(Into::into),
+        }
+    }
+}
+
+impl Section for Load {
+    const FIELD: &'static str = "load";
+
+    fn store(self, pack: &mut Pack) {
+        let [load1, load5, load15] = self.averages;
+
+        pack.load = Some(LoadResponse {
+            load1,
+            load5,
+            load15,
+            runnable_tasks: self.runnable_tasks,
+            total_tasks: self.total_tasks,
+            cpu: self.cpu.map(Into::into),
+            memory: self.memory.map(Into::into),
+            io: self.io.map(Into::into),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.load = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::Fixture;
+
+    use super::{Load, Pressure, Stall};
+
+    #[test]
+    fn test_read_load_and_pressure() {
+        let fixture = Fixture::new("load-psi");
+        fixture
+            .write("loadavg", "0.52 0.58 1.25 3/1234 5678")
+            .write(
+                "pressure/cpu",
+                "some avg10=12.50 avg60=4.00 avg300=1.00 total=123456",
+            )
+            .write(
+                "pressure/io",
+                "some avg10=0.00 avg60=0.00 avg300=0.00 total=10\n\
+                 full avg10=1.50 avg60=0.00 avg300=0.00 total=5",
+            );
+
+        let load = Load::read(fixture.path()).unwrap();
+        assert_eq!(load.averages, [0.52, 0.58, 1.25]);
+        assert_eq!((load.runnable_tasks, load.total_tasks), (3, 1234));
+        assert_eq!(
+            load.cpu,
+            Some(Pressure {
+                some: Stall {
+                    avg10: 12.5,
+                    avg60: 4.0,
+                    avg300: 1.0,
+                    total_us: 123456,
+                },
+                full: None,
+            })
+        );
+        assert_eq!(load.memory, None);
+        assert_eq!(load.io.unwrap().full.unwrap().avg10, 1.5);
+    }
+
+    #[test]
+    fn test_malformed_loadavg_fails() {
+        let fixture = Fixture::new("load-malformed");
+        fixture.write("loadavg", "0.52 0.58");
+
+        assert!(Load::read(fixture.path()).is_err());
+    }
+}
@@ -1,27 +1,80 @@
 #![forbid(unsafe_code)]
 
+mod actions;
+mod alerts;
+mod audit;
+mod auth;
+mod cgroups;
 mod collect_info;
+mod collector;
+mod config;
 mod cpu_service;
+mod disks;
+mod events;
+mod filesystems;
+mod health;
+mod listen;
+mod load;
+mod power;
+mod processes;
+mod record;
+mod settings;
+mod signals;
+mod snapshot;
+mod source;
+mod systemd;
+#[cfg(test)]
+mod test_util;
+mod thermal;
+mod throttling;
+mod topology;
+mod update;
+mod watch;
+mod wear;
 
 extern crate clap;
 
 use std::{
-    error::Error, io::stdin, net::SocketAddr, process::exit, str::FromStr, sync::Arc,
-    time::Duration,
+    error::Error, io::stdin, path::PathBuf, process::exit, str::FromStr, sync::Arc, time::Duration,
 };
 
-use clap::{arg, Parser};
-use common::monitoring::monitor_server::MonitorServer;
+use clap::Parser;
+use common::monitoring::{monitor_server::MonitorServer, FILE_DESCRIPTOR_SET};
 use fslock::LockFile;
 use sysinfo::{
     CpuRefreshKind, Pid, ProcessExt, ProcessRefreshKind, RefreshKind, System, SystemExt,
 };
 use tokio::sync::Mutex;
 use tonic::{codegen::http::HeaderName, transport::Server};
+use tonic_health::ServingStatus;
 use tonic_web::GrpcWebLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 
-use crate::cpu_service::MonitorService;
+use crate::{
+    alerts::Alerts,
+    cgroups::CgroupsCollector,
+    collect_info::{CpuCollector, NetworkCollector, SensorsCollector},
+    collector::{Registry, Sampler},
+    config::Config,
+    cpu_service::MonitorService,
+    disks::DisksCollector,
+    events::Events,
+    filesystems::FilesystemsCollector,
+    health::CollectorHealth,
+    listen::ListenAddr,
+    load::LoadCollector,
+    processes::ProcessesCollector,
+    record::{Recorder, Replay},
+    snapshot::Snapshots,
+    source::{SharedProcesses, Simulated, SystemSource},
+    systemd::SystemdCollector,
+    thermal::ThermalCollector,
+    throttling::ThrottlingCollector,
+    topology::TopologyCollector,
+    update::{Rollback, Updater},
+    watch::{History, WatchCollector},
+    wear::WearCollector,
+};
 
 const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
 const DEFAULT_EXPOSED_HEADERS: [&str; 4] = [
@@ -30,8 +83,9 @@ const DEFAULT_EXPOSED_HEADERS: [&str; 4] = [
     "grpc-status-details-bin",
     "grpc-encoding",
 ];
-const DEFAULT_ALLOW_HEADERS: [&str; 5] = [
+const DEFAULT_ALLOW_HEADERS: [&str; 6] = [
     "x-grpc-web",
+    "authorization",
     "content-type",
     "x-user-agent",
     "grpc-timeout",
@@ -39,23 +93,192 @@ const DEFAULT_ALLOW_HEADERS: [&str; 5] = [
 ];
 
 #[derive(Parser)]
+#[command(version)]
 struct ServerCli {
     #[arg(short = 'a', long = "address", default_value = "127.0.0.1")]
     address: String,
     #[arg(short = 'p', long = "port", default_value = "50501")]
     port: u16,
-    #[arg(short = 'u', long = "update", default_value = "100")]
-    update_every_ms: u64,
+    /// Sampling period in milliseconds, 100 by default. Overrides `sampling_interval_ms` of
+    /// the config.
+    #[arg(short = 'u', long = "update")]
+    update_every_ms: Option<u64>,
     #[arg(short = 'l', long = "lock", default_value = ".service.lock")]
     lock_file: String,
+    /// Address to listen on: `host:port`, `[ipv6]:port` or `unix:/path.sock`.
+    /// May be repeated. Overrides `--address` and `--port`.
+    #[arg(long = "listen")]
+    listen: Vec<ListenAddr>,
+    /// TOML file with collector settings.
+    #[arg(short = 'c', long = "config")]
+    config: Option<PathBuf>,
+    /// Serve synthetic readings from the `[simulation]` section of the config instead of the
+    /// real system.
+    #[arg(long = "simulate")]
+    simulate: bool,
+    /// Write every collected pack to a file for a later `--replay`.
+    #[arg(long = "record")]
+    record: Option<PathBuf>,
+    /// Serve packs recorded with `--record` instead of the real system.
+    #[arg(long = "replay", conflicts_with_all = ["simulate", "record"])]
+    replay: Option<PathBuf>,
+    /// How many times faster than recorded to replay.
+    #[arg(long = "replay-speed", default_value = "1.0", requires = "replay")]
+    replay_speed: f64,
+    /// Set when an update started this build, see [`update::nonce`].
+    #[arg(skip)]
+    update_nonce: Option<String>,
 }
 
-async fn launch(system: System, cli: ServerCli) -> Result<(), Box<dyn Error>> {
-    let addr: SocketAddr = format!("{}:{}", cli.address, cli.port).parse()?;
+impl ServerCli {
+    fn listen_addresses(&self) -> Vec<ListenAddr> {
+        if self.listen.is_empty() {
+            vec![ListenAddr::Tcp {
+                host: self.address.clone(),
+                port: self.port,
+            }]
+        } else {
+            self.listen.clone()
+        }
+    }
+
+    fn load_config(&self) -> Result<Config, Box<dyn Error>> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        if let Some(interval) = self.update_every_ms {
+            config.sampling_interval_ms = Some(interval);
+        }
+        config.settings().validate()?;
+
+        Ok(config)
+    }
+
+    fn registry(
+        &self,
+        config: &Config,
+        events: &Events,
+        history: &History,
+    ) -> Result<Registry, Box<dyn Error>> {
+        let mut registry = Registry::new(
+            config.collectors.clone(),
+            Duration::from_millis(config.settings().sampling_interval_ms),
+        );
+        registry
+            .register(CpuCollector)
+            .register(SensorsCollector)
+            .register(NetworkCollector)
+            .register(ThermalCollector::new(config.sysfs_root()))
+            .register(ThrottlingCollector::new(config.sysfs_root()))
+            .register(TopologyCollector::new(
+                config.sysfs_root(),
+                config.procfs_root(),
+            ))
+            .register(LoadCollector::new(config.procfs_root()))
+            .register(DisksCollector::new(
+                config.sysfs_root(),
+                config.procfs_root(),
+            ))
+            .register(WearCollector::new(
+                config.wear.clone(),
+                config.sysfs_root(),
+                config.procfs_root(),
+            ))
+            .register(FilesystemsCollector::new(
+                config.sysfs_root(),
+                config.procfs_root(),
+                events.clone(),
+            ))
+            .register(CgroupsCollector::new(config.cgroups.clone()))
+            .register(SystemdCollector::new(config.systemd.clone()))
+            .register(ProcessesCollector)
+            .register(WatchCollector::new(
+                &config.watch,
+                config.procfs_root(),
+                events.clone(),
+                history.clone(),
+            )?);
+        registry.validate()?;
 
-    let service = MonitorService::new(system);
+        Ok(registry)
+    }
 
-    println!("Listening server on {addr}");
+    fn source(
+        &self,
+        system: System,
+        config: &Config,
+    ) -> Result<Box<dyn SystemSource>, Box<dyn Error>> {
+        if let Some(path) = &self.replay {
+            if self.replay_speed.is_nan() || self.replay_speed <= 0.0 {
+                return Err("replay speed must be positive".into());
+            }
+            Ok(Box::new(Replay::open(path, self.replay_speed)?))
+        } else if self.simulate {
+            Ok(Box::new(Simulated::new(config.simulation.clone())))
+        } else {
+            Ok(Box::new(SharedProcesses::new(system)))
+        }
+    }
+}
+
+async fn launch(
+    source: Box<dyn SystemSource>,
+    registry: Registry,
+    events: Events,
+    history: History,
+    config: Config,
+    cli: ServerCli,
+    lock: Arc<Mutex<LockFile>>,
+) -> Result<(), Box<dyn Error>> {
+    let (mut reporter, health_service) = tonic_health::server::health_reporter();
+    reporter
+        .set_serving::<MonitorServer<MonitorService>>()
+        .await;
+    let mut update_reporter = reporter.clone();
+    let health = CollectorHealth::new(reporter, &registry.names()).await;
+    let mut sampler = Sampler::new(source, registry);
+    if let Some(path) = &cli.record {
+        sampler.record(Recorder::create(path)?);
+    }
+    let settings = config.settings();
+    events.keep(settings.retention.events);
+    history.keep(settings.retention.watched_samples);
+    let snapshots = Arc::new(Snapshots::new(
+        sampler,
+        health,
+        Alerts::new(settings.alerts, events.clone()),
+        Duration::from_millis(settings.sampling_interval_ms),
+        Duration::from_millis(settings.cache_max_age_ms),
+    ));
+    tokio::spawn(snapshots.clone().run());
+    let service = MonitorService::new(
+        snapshots,
+        events,
+        history,
+        &config,
+        cli.config.clone(),
+        Updater::new(config.update.clone(), &cli.listen_addresses(), lock)?,
+    )?;
+
+    let reflection_service = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build()?;
+
+    let mut listeners = vec![];
+    for addr in cli.listen_addresses() {
+        for listener in addr.bind().await? {
+            println!("Listening server on {listener}");
+            listeners.push(listener);
+        }
+    }
+    // Tells the watchdog left by the previous build that this one is up
+    if let Some(nonce) = &cli.update_nonce {
+        update_reporter
+            .set_service_status(update::health_name(nonce), ServingStatus::Serving)
+            .await;
+    }
 
     Server::builder()
         .accept_http1(true)
@@ -81,7 +304,9 @@ async fn launch(system: System, cli: ServerCli) -> Result<(), Box<dyn Error>> {
         )
         .layer(GrpcWebLayer::new())
         .add_service(MonitorServer::new(service))
-        .serve(addr)
+        .add_service(health_service)
+        .add_service(reflection_service)
+        .serve_with_incoming(listen::incoming(listeners))
         .await?;
 
     Ok(())
@@ -112,9 +337,23 @@ fn register_on_kill(lock: Arc<Mutex<LockFile>>) {
     });
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let cli = ServerCli::parse();
+fn main() -> Result<(), Box<dyn Error>> {
+    // Read while this is the only thread, the runtime starts more
+    let nonce = update::nonce();
+
+    tokio::runtime::Runtime::new()?.block_on(run(nonce))
+}
+
+async fn run(nonce: Option<String>) -> Result<(), Box<dyn Error>> {
+    if let Some(rollback) = Rollback::from_env() {
+        return Ok(rollback?.watch().await?);
+    }
+    let mut cli = ServerCli::parse();
+    cli.update_nonce = nonce;
+    let config = cli.load_config()?;
+    let events = Events::default();
+    let history = History::default();
+    let registry = cli.registry(&config, &events, &history)?;
     let system = System::new_with_specifics(
         RefreshKind::new()
             .with_cpu(CpuRefreshKind::everything())
@@ -128,10 +367,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
     register_on_kill(lock.clone());
 
     if lock.lock().await.try_lock_with_pid()? {
-        launch(system, cli).await?;
+        let source = cli.source(system, &config)?;
+        launch(source, registry, events, history, config, cli, lock).await?;
     } else if disable_lock(&cli, &system)? {
         lock.lock().await.lock_with_pid()?;
-        launch(system, cli).await?;
+        let source = cli.source(system, &config)?;
+        launch(source, registry, events, history, config, cli, lock).await?;
     }
 
     Ok(())
@@ -0,0 +1,378 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::broadcast;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::{
+    auth::random_token,
+    events::{Events, Severity},
+    systemd::{call_manager, connect, dbus_error},
+};
+
+/// How long a confirm token stays valid.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest delay an operation can be scheduled with.
+const MAX_DELAY: Duration = Duration::from_secs(3600);
+/// Notices kept for subscribers that fall behind.
+const BUFFERED_NOTICES: usize = 16;
+
+/// Operations that take the board or one of its services down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Reboot,
+    Shutdown,
+    RestartService(String),
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Reboot => write!(f, "reboot"),
+            Operation::Shutdown => write!(f, "shutdown"),
+            Operation::RestartService(unit) => write!(f, "restart of {unit}"),
+        }
+    }
+}
+
+/// What actually reboots, shuts down or restarts. Tests use a fake.
+pub trait System: Send + Sync + std::fmt::Debug {
+    fn reboot(&self) -> std::io::Result<()>;
+    fn shutdown(&self) -> std::io::Result<()>;
+    fn restart_service(&self, unit: &str) -> std::io::Result<()>;
+}
+
+/// Asks systemd through its D-Bus API, so services are stopped cleanly.
+#[derive(Debug, Clone, Default)]
+pub struct Systemd {
+    /// D-Bus address to connect to instead of the system bus.
+    pub address: Option<String>,
+}
+
+impl Systemd {
+    fn call(&self, method: &str, unit: &str, mode: &str) -> std::io::Result<()> {
+        let connection = connect(self.address.as_deref()).map_err(dbus_error)?;
+
+        call_manager::<_, OwnedObjectPath>(&connection, method, &(unit, mode))
+            .map(|_| ())
+            .map_err(dbus_error)
+    }
+}
+
+impl System for Systemd {
+    fn reboot(&self) -> std::io::Result<()> {
+        self.call("StartUnit", "reboot.target", "replace-irreversibly")
+    }
+
+    fn shutdown(&self) -> std::io::Result<()> {
+        self.call("StartUnit", "poweroff.target", "replace-irreversibly")
+    }
+
+    fn restart_service(&self, unit: &str) -> std::io::Result<()> {
+        self.call("RestartUnit", unit, "replace")
+    }
+}
+
+/// Announcement of an operation about to happen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    pub message: String,
+    pub delay: Duration,
+}
+
+/// Outcome of a request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// The request has to be repeated with the token to go ahead.
+    Confirm {
+        token: String,
+        expires_in: Duration,
+    },
+    Scheduled,
+}
+
+#[derive(Debug)]
+struct Pending {
+    caller: String,
+    operation: Operation,
+    delay: Duration,
+    expires: Instant,
+}
+
+/// Two-phase requests for power operations, scheduled once confirmed.
+#[derive(Debug)]
+pub struct Power {
+    system: Arc<dyn System>,
+    pending: Mutex<HashMap<String, Pending>>,
+    events: Events,
+    notices: broadcast::Sender<Notice>,
+}
+
+impl Power {
+    pub fn new(system: Arc<dyn System>, events: Events) -> Self {
+        Power {
+            system,
+            pending: Mutex::new(HashMap::new()),
+            events,
+            notices: broadcast::channel(BUFFERED_NOTICES).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Notice> {
+        self.notices.subscribe()
+    }
+
+    /// Without `confirm_token` hands one out. With it, schedules the operation if the token was
+    /// handed out to the same caller for the same operation and delay. Tokens work once.
+    pub fn request(
+        &self,
+        caller: &str,
+        operation: Operation,
+        delay: Duration,
+        confirm_token: &str,
+    ) -> std::io::Result<Step> {
+        let invalid = |message: String| std::io::Error::new(ErrorKind::InvalidInput, message);
+        if delay > MAX_DELAY {
+            return Err(invalid(format!(
+                "the delay is longer than {} seconds",
+                MAX_DELAY.as_secs()
+            )));
+        }
+        if let Operation::RestartService(unit) = &operation {
+            if !unit.ends_with(".service") || unit.contains('/') {
+                return Err(invalid(format!("{unit} is not a service")));
+            }
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        let now = Instant::now();
+        pending.retain(|_, x| x.expires > now);
+
+        if confirm_token.is_empty() {
+            let token = random_token()?;
+            pending.insert(
+                token.clone(),
+                Pending {
+                    caller: caller.to_owned(),
+                    operation,
+                    delay,
+                    expires: now + CONFIRM_TIMEOUT,
+                },
+            );
+            return Ok(Step::Confirm {
+                token,
+                expires_in: CONFIRM_TIMEOUT,
+            });
+        }
+
+        let Some(confirmed) = pending.remove(confirm_token) else {
+            return Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                "unknown or expired confirm token",
+            ));
+        };
+        if confirmed.caller != caller || confirmed.operation != operation {
+            return Err(invalid(format!(
+                "the confirm token was issued for the {} requested by {}",
+                confirmed.operation, confirmed.caller
+            )));
+        }
+        if confirmed.delay != delay {
+            return Err(invalid(
+                "the confirm token was issued for another delay".to_owned(),
+            ));
+        }
+        drop(pending);
+
+        self.schedule(caller, operation, delay);
+        Ok(Step::Scheduled)
+    }
+
+    fn schedule(&self, caller: &str, operation: Operation, delay: Duration) {
+        let severity = match operation {
+            Operation::Reboot | Operation::Shutdown => Severity::Critical,
+            Operation::RestartService(_) => Severity::Warning,
+        };
+        let message = format!(
+            "The {operation} requested by {caller} happens in {} seconds",
+            delay.as_secs()
+        );
+        self.events.raise("power", severity, message.clone());
+        // Nobody listening is fine
+        let _ = self.notices.send(Notice { message, delay });
+
+        let system = self.system.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let done = operation.clone();
+            let result = tokio::task::spawn_blocking(move || match &done {
+                Operation::Reboot => system.reboot(),
+                Operation::Shutdown => system.shutdown(),
+                Operation::RestartService(unit) => system.restart_service(unit),
+            })
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+
+            if let Err(e) = result {
+                let message = format!("The {operation} failed: {e}");
+                events.raise("power", Severity::Critical, message);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::ErrorKind,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::events::{Events, Severity};
+
+    use super::{Operation, Power, Step, System};
+
+    /// Records what would have been done.
+    #[derive(Debug, Default)]
+    pub struct FakeSystem {
+        pub done: Mutex<Vec<Operation>>,
+        pub failing: bool,
+    }
+
+    impl FakeSystem {
+        fn run(&self, operation: Operation) -> std::io::Result<()> {
+            if self.failing {
+                return Err(std::io::Error::other("access denied"));
+            }
+            self.done.lock().unwrap().push(operation);
+            Ok(())
+        }
+    }
+
+    impl System for FakeSystem {
+        fn reboot(&self) -> std::io::Result<()> {
+            self.run(Operation::Reboot)
+        }
+
+        fn shutdown(&self) -> std::io::Result<()> {
+            self.run(Operation::Shutdown)
+        }
+
+        fn restart_service(&self, unit: &str) -> std::io::Result<()> {
+            self.run(Operation::RestartService(unit.to_owned()))
+        }
+    }
+
+    fn confirm_token(step: Step) -> String {
+        match step {
+            Step::Confirm { token, .. } => token,
+            Step::Scheduled => panic!("scheduled without confirmation"),
+        }
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn test_operations_need_confirmation() {
+        let system = Arc::new(FakeSystem::default());
+        let events = Events::default();
+        let power = Power::new(system.clone(), events.clone());
+        let mut notices = power.subscribe();
+        let delay = Duration::ZERO;
+
+        let token = confirm_token(
+            power
+                .request("oncall", Operation::Reboot, delay, "")
+                .unwrap(),
+        );
+        assert_eq!(token.len(), 32);
+        let other = power
+            .request("deploy", Operation::Reboot, delay, &token)
+            .unwrap_err();
+        assert_eq!(other.kind(), ErrorKind::InvalidInput);
+        // The failed attempt used the token up
+        let replayed = power
+            .request("oncall", Operation::Reboot, delay, &token)
+            .unwrap_err();
+        assert_eq!(replayed.kind(), ErrorKind::PermissionDenied);
+        settle().await;
+        assert!(system.done.lock().unwrap().is_empty());
+
+        let token = confirm_token(
+            power
+                .request("oncall", Operation::Reboot, delay, "")
+                .unwrap(),
+        );
+        let step = power
+            .request("oncall", Operation::Reboot, delay, &token)
+            .unwrap();
+        assert_eq!(step, Step::Scheduled);
+        let notice = notices.recv().await.unwrap();
+        assert_eq!(
+            notice.message,
+            "The reboot requested by oncall happens in 0 seconds"
+        );
+        settle().await;
+        assert_eq!(*system.done.lock().unwrap(), [Operation::Reboot]);
+        let raised = events.after(0);
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].severity, Severity::Critical);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_and_failing_operations() {
+        let system = Arc::new(FakeSystem {
+            failing: true,
+            ..Default::default()
+        });
+        let events = Events::default();
+        let power = Power::new(system, events.clone());
+        let restart = |unit: &str| Operation::RestartService(unit.to_owned());
+
+        let target = power
+            .request("oncall", restart("reboot.target"), Duration::ZERO, "")
+            .unwrap_err();
+        assert_eq!(target.kind(), ErrorKind::InvalidInput);
+        let long = power
+            .request(
+                "oncall",
+                Operation::Shutdown,
+                Duration::from_secs(86400),
+                "",
+            )
+            .unwrap_err();
+        assert_eq!(long.kind(), ErrorKind::InvalidInput);
+
+        let delay = Duration::from_secs(1);
+        let step = power.request("oncall", restart("app.service"), delay, "");
+        let token = confirm_token(step.unwrap());
+        let other = power.request("oncall", restart("app.service"), Duration::ZERO, &token);
+        assert_eq!(other.unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        let token = confirm_token(
+            power
+                .request("oncall", restart("app.service"), delay, "")
+                .unwrap(),
+        );
+        power
+            .request("oncall", restart("app.service"), delay, &token)
+            .unwrap();
+        assert_eq!(events.after(0)[0].severity, Severity::Warning);
+        tokio::time::sleep(delay).await;
+        settle().await;
+        let raised = events.after(0);
+        assert_eq!(raised.len(), 2);
+        assert_eq!(
+            raised[1].message,
+            "The restart of app.service failed: access denied"
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::monitoring::{Pack, ProcessResponse, ProcessSummary};
+
+use crate::{
+    collector::{Collector, Section},
+    source::{ProcessInfo, SystemSource},
+};
+
+/// Parents are followed at most this far, in case of a cycle in a broken procfs.
+const MAX_DEPTH: usize = 64;
+
+/// Every running process, ordered by PID. Clients build the tree from the parents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Processes(pub Vec<ProcessInfo>);
+
+#[derive(Debug)]
+pub struct ProcessesCollector;
+
+impl Collector for ProcessesCollector {
+    type Output = Processes;
+
+    fn name(&self) -> &'static str {
+        "processes"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        let mut processes = system.processes();
+        processes.sort_by_key(|x| x.pid);
+
+        Ok(Processes(processes))
+    }
+}
+
+/// Fields of `/proc/<pid>/status` the details are made of.
+#[derive(Debug, Default)]
+pub(crate) struct Status {
+    pub name: String,
+    pub state: String,
+    pub parent: u32,
+    pub uid: Option<u32>,
+    pub threads: u32,
+}
+
+/// Value of a `Key:\tvalue` line of `/proc/<pid>/status`.
+pub(crate) fn status_field<'a>(status: &'a str, key: &str) -> Option<&'a str> {
+    status.lines().find_map(|x| {
+        let (name, value) = x.split_once(':')?;
+        (name == key).then_some(value.trim())
+    })
+}
+
+pub(crate) fn read_status(dir: &Path) -> std::io::Result<Status> {
+    let status = std::fs::read_to_string(dir.join("status"))?;
+    let number = |key| status_field(&status, key).and_then(|x| x.parse().ok());
+
+    Ok(Status {
+        name: status_field(&status, "Name").unwrap_or_default().to_owned(),
+        state: status_field(&status, "State")
+            .unwrap_or_default()
+            .to_owned(),
+        parent: number("PPid").unwrap_or_default(),
+        // Real, effective, saved and filesystem IDs
+        uid: status_field(&status, "Uid").and_then(|x| x.split_whitespace().next()?.parse().ok()),
+        threads: number("Threads").unwrap_or_default(),
+    })
+}
+
+/// A process referenced from another one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relative {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// A line of `/proc/<pid>/limits`, missing values are unlimited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limit {
+    pub name: String,
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+    pub unit: String,
+}
+
+/// Counters of `/proc/<pid>/io`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IoCounters {
+    /// Passed to read and write calls, including the page cache.
+    pub read_chars: u64,
+    pub write_chars: u64,
+    pub read_syscalls: u64,
+    pub write_syscalls: u64,
+    /// Fetched from and sent to storage.
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub cancelled_write_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Details {
+    pub pid: u32,
+    pub name: String,
+    pub state: String,
+    pub uid: Option<u32>,
+    pub cmdline: Vec<String>,
+    /// Nearest first.
+    pub parents: Vec<Relative>,
+    pub children: Vec<Relative>,
+    /// `KEY=value` pairs, missing when not asked for or not readable.
+    pub environment: Option<Vec<String>>,
+    pub cwd: Option<PathBuf>,
+    pub open_fds: Option<u32>,
+    pub limits: Vec<Limit>,
+    pub threads: u32,
+    pub io: Option<IoCounters>,
+    /// Path in the cgroup v2 hierarchy.
+    pub cgroup: Option<String>,
+}
+
+/// Splits a NUL separated file such as `cmdline` or `environ`.
+fn read_nul_separated(path: &Path) -> std::io::Result<Vec<String>> {
+    Ok(String::from_utf8_lossy(&std::fs::read(path)?)
+        .split('\0')
+        .filter(|x| !x.is_empty())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+fn parse_limits(content: &str) -> Vec<Limit> {
+    let value = |x: &str| x.parse().ok();
+
+    // Columns are padded with spaces and names contain single ones
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split("  ").map(str::trim).filter(|x| !x.is_empty());
+
+            Some(Limit {
+                name: columns.next()?.to_owned(),
+                soft: value(columns.next()?),
+                hard: value(columns.next()?),
+                unit: columns.next().unwrap_or_default().to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn parse_io(content: &str) -> IoCounters {
+    let field = |key| status_field(content, key).and_then(|x| x.parse().ok());
+
+    IoCounters {
+        read_chars: field("rchar").unwrap_or_default(),
+        write_chars: field("wchar").unwrap_or_default(),
+        read_syscalls: field("syscr").unwrap_or_default(),
+        write_syscalls: field("syscw").unwrap_or_default(),
+        read_bytes: field("read_bytes").unwrap_or_default(),
+        write_bytes: field("write_bytes").unwrap_or_default(),
+        cancelled_write_bytes: field("cancelled_write_bytes").unwrap_or_default(),
+    }
+}
+
+fn read_children(procfs_root: &Path, pid: u32) -> std::io::Result<Vec<Relative>> {
+    let mut children = vec![];
+
+    for entry in std::fs::read_dir(procfs_root)? {
+        let entry = entry?;
+        let Some(child) = entry.file_name().to_str().and_then(|x| x.parse().ok()) else {
+            continue;
+        };
+        // Processes may exit while the directory is read
+        if let Ok(status) = read_status(&entry.path()) {
+            if status.parent == pid {
+                children.push(Relative {
+                    pid: child,
+                    name: status.name,
+                });
+            }
+        }
+    }
+    children.sort_by_key(|x| x.pid);
+    Ok(children)
+}
+
+/// Everything procfs tells about a process. Fields that need more privileges than the service
+/// has are left empty, the environment is only read when `environment` is set.
+pub fn read_details(procfs_root: &Path, pid: u32, environment: bool) -> std::io::Result<Details> {
+    let dir = procfs_root.join(pid.to_string());
+    let status = read_status(&dir)?;
+
+    let mut parents = vec![];
+    let mut parent = status.parent;
+    while parent != 0 && parents.len() < MAX_DEPTH {
+        let Ok(status) = read_status(&procfs_root.join(parent.to_string())) else {
+            break;
+        };
+        parents.push(Relative {
+            pid: parent,
+            name: status.name,
+        });
+        parent = status.parent;
+    }
+
+    let optional = |name| match std::fs::read_to_string(dir.join(name)) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied) => Ok(None),
+        Err(e) => Err(e),
+    };
+
+    Ok(Details {
+        pid,
+        name: status.name,
+        state: status.state,
+        uid: status.uid,
+        cmdline: read_nul_separated(&dir.join("cmdline")).unwrap_or_default(),
+        parents,
+        children: read_children(procfs_root, pid)?,
+        environment: environment
+            .then(|| read_nul_separated(&dir.join("environ")).ok())
+            .flatten(),
+        cwd: std::fs::read_link(dir.join("cwd")).ok(),
+        open_fds: std::fs::read_dir(dir.join("fd"))
+            .ok()
+            .map(|x| x.count() as u32),
+        limits: optional("limits")?
+            .map(|x| parse_limits(&x))
+            .unwrap_or_default(),
+        threads: status.threads,
+        io: optional("io")?.map(|x| parse_io(&x)),
+        cgroup: optional("cgroup")?.and_then(|x| {
+            x.lines()
+                .find_map(|x| x.strip_prefix("0::").map(ToOwned::to_owned))
+        }),
+    })
+}
+
+impl Section for Processes {
+    const FIELD: &'static str = "processes";
+
+    fn store(self, pack: &mut Pack) {
+        pack.processes = Some(ProcessResponse {
+            processes: self
+                .0
+                .into_iter()
+                .map(|x| ProcessSummary {
+                    pid: x.pid,
+                    parent: x.parent,
+                    name: x.name,
+                    cpu_usage: x.cpu_usage,
+                    memory: x.memory,
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.processes = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use crate::test_util::Fixture;
+
+    use super::{parse_limits, read_details};
+
+    const LIMITS: &str = "\
+Limit                     Soft Limit           Hard Limit           Units
+Max cpu time              unlimited            unlimited            seconds
+Max open files            1024                 524288               files
+Max nice priority         0                    0
+";
+
+    fn status(name: &str, parent: u32) -> String {
+        format!(
+            "Name:\t{name}\nState:\tS (sleeping)\nPPid:\t{parent}\n\
+             Uid:\t1000\t1000\t1000\t1000\nThreads:\t3\n"
+        )
+    }
+
+    #[test]
+    fn test_read_details() {
+        let fixture = Fixture::new("processes-details");
+        fixture
+            .write("1/status", &status("systemd", 0))
+            .write("40/status", &status("supervisord", 1))
+            .write("41/status", &status("worker", 40))
+            .write("42/status", &status("worker", 40))
+            .write("43/status", &status("sshd", 1))
+            .write("40/limits", LIMITS)
+            .write("40/io", "rchar: 2048\nwchar: 512\nread_bytes: 4096\n")
+            .write("40/cgroup", "0::/system.slice/supervisor.service\n")
+            .write("40/fd/0", "")
+            .write("self", "");
+        // Unlike sysfs attributes these do not end with a newline
+        let dir = fixture.path().join("40");
+        std::fs::write(
+            dir.join("cmdline"),
+            "/usr/bin/python3\0/usr/bin/supervisord\0",
+        )
+        .unwrap();
+        std::fs::write(dir.join("environ"), "HOME=/root\0LANG=C\0").unwrap();
+
+        let details = read_details(fixture.path(), 40, false).unwrap();
+        assert_eq!(details.name, "supervisord");
+        assert_eq!(details.uid, Some(1000));
+        assert_eq!(
+            details.cmdline,
+            ["/usr/bin/python3", "/usr/bin/supervisord"]
+        );
+        assert_eq!(details.parents.len(), 1);
+        assert_eq!(details.parents[0].name, "systemd");
+        let children = details.children.iter().map(|x| x.pid).collect::<Vec<_>>();
+        assert_eq!(children, [41, 42]);
+        assert!(details.environment.is_none());
+        assert_eq!(details.open_fds, Some(1));
+        assert_eq!(details.threads, 3);
+        assert_eq!(details.io.unwrap().read_bytes, 4096);
+        assert_eq!(
+            details.cgroup.as_deref(),
+            Some("/system.slice/supervisor.service")
+        );
+
+        let details = read_details(fixture.path(), 40, true).unwrap();
+        assert_eq!(details.environment.unwrap(), ["HOME=/root", "LANG=C"]);
+
+        let details = read_details(fixture.path(), 41, true).unwrap();
+        let parents = details.parents.iter().map(|x| x.pid).collect::<Vec<_>>();
+        assert_eq!(parents, [40, 1]);
+        assert!(details.io.is_none());
+
+        let error = read_details(fixture.path(), 99, false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = parse_limits(LIMITS);
+
+        assert_eq!(limits.len(), 3);
+        assert_eq!(limits[0].name, "Max cpu time");
+        assert_eq!(limits[0].soft, None);
+        assert_eq!(limits[1].soft, Some(1024));
+        assert_eq!(limits[1].hard, Some(524288));
+        assert_eq!(limits[1].unit, "files");
+        assert_eq!(limits[2].unit, "");
+    }
+}
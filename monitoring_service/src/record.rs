@@ -0,0 +1,250 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+use common::monitoring::Pack;
+use prost::Message;
+
+use crate::{
+    cgroups::Cgroups,
+    collect_info::{Cpu, Network},
+    collector::Section,
+    disks::Disks,
+    filesystems::Filesystems,
+    load::Load,
+    processes::Processes,
+    source::SystemSource,
+    systemd::Units,
+    thermal::Thermal,
+    watch::Watched,
+    wear::Wear,
+};
+
+/// Appends every collected pack to a file as a length-delimited protobuf message.
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Recorder {
+            file: File::create(path)?,
+        })
+    }
+
+    /// Writes the pack at once, so the recording survives a crash of the board.
+    pub fn write(&mut self, pack: &Pack) -> std::io::Result<()> {
+        self.file.write_all(&pack.encode_length_delimited_to_vec())
+    }
+}
+
+fn read_packs(reader: impl Read) -> std::io::Result<Vec<Pack>> {
+    let mut content = vec![];
+    BufReader::new(reader).read_to_end(&mut content)?;
+
+    let mut buffer = content.as_slice();
+    let mut packs = vec![];
+    while !buffer.is_empty() {
+        packs.push(Pack::decode_length_delimited(&mut buffer)?);
+    }
+    Ok(packs)
+}
+
+/// Serves readings of a recorded session, following the timestamps of its packs.
+pub struct Replay {
+    /// Packs with their offsets from the first one.
+    packs: Vec<(Duration, Pack)>,
+    start: Instant,
+    speed: f64,
+}
+
+impl Replay {
+    pub fn new(packs: Vec<Pack>, speed: f64) -> std::io::Result<Self> {
+        let time = |x: &Pack| {
+            x.timestamp
+                .clone()
+                .and_then(|x| SystemTime::try_from(x).ok())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        };
+        let first = packs.first().map(time).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "recording is empty")
+        })?;
+
+        Ok(Replay {
+            packs: packs
+                .into_iter()
+                .map(|x| (time(&x).duration_since(first).unwrap_or_default(), x))
+                .collect(),
+            start: Instant::now(),
+            speed,
+        })
+    }
+
+    /// Replays a file written by [`Recorder`], `speed` times faster than it was recorded.
+    pub fn open(path: &Path, speed: f64) -> std::io::Result<Self> {
+        Replay::new(read_packs(File::open(path)?)?, speed)
+    }
+
+    fn position(&self) -> Duration {
+        self.start.elapsed().mul_f64(self.speed)
+    }
+
+    /// Latest section recorded not after `t`. The last pack is served once the recording ends.
+    fn latest<T>(&self, t: Duration, section: impl Fn(&Pack) -> Option<T>) -> Option<T> {
+        let end = self.packs.partition_point(|x| x.0 <= t).max(1);
+
+        self.packs[..end].iter().rev().find_map(|x| section(&x.1))
+    }
+}
+
+impl SystemSource for Replay {
+    fn cpus(&mut self) -> Vec<(f32, u64)> {
+        self.latest(self.position(), |x| {
+            let cpu = x.cpu.as_ref().filter(|x| !x.usage.is_empty())?;
+            Some(
+                cpu.usage
+                    .iter()
+                    .enumerate()
+                    .map(|(i, usage)| (*usage, cpu.frequency.get(i).copied().unwrap_or_default()))
+                    .collect(),
+            )
+        })
+        .unwrap_or_default()
+    }
+
+    fn temperatures(&mut self) -> Vec<f32> {
+        self.latest(self.position(), |x| x.cpu.as_ref()?.temperature)
+            .into_iter()
+            .collect()
+    }
+
+    fn networks(&mut self) -> Vec<(String, u64, u64)> {
+        self.latest(self.position(), |x| {
+            Some(
+                x.network
+                    .as_ref()?
+                    .interfaces
+                    .iter()
+                    .map(|x| (x.name.clone(), x.bytes_in, x.bytes_out))
+                    .collect(),
+            )
+        })
+        .unwrap_or_default()
+    }
+
+    fn is_host(&self) -> bool {
+        false
+    }
+
+    /// Every section as last recorded, so sections read from sysfs, procfs or D-Bus are
+    /// replayed too and nothing is read from the host.
+    fn recorded(&mut self, fields: &dyn Fn(&str) -> bool) -> Option<Pack> {
+        let t = self.position();
+        let section = |field| fields(field).then_some(t);
+
+        Some(Pack {
+            network: section(Network::FIELD).and_then(|t| self.latest(t, |x| x.network.clone())),
+            cpu: section(Cpu::FIELD).and_then(|t| self.latest(t, |x| x.cpu.clone())),
+            timestamp: None,
+            thermal: section(Thermal::FIELD).and_then(|t| self.latest(t, |x| x.thermal.clone())),
+            load: section(Load::FIELD).and_then(|t| self.latest(t, |x| x.load.clone())),
+            disks: section(Disks::FIELD).and_then(|t| self.latest(t, |x| x.disks.clone())),
+            wear: section(Wear::FIELD).and_then(|t| self.latest(t, |x| x.wear.clone())),
+            filesystems: section(Filesystems::FIELD)
+                .and_then(|t| self.latest(t, |x| x.filesystems.clone())),
+            cgroups: section(Cgroups::FIELD).and_then(|t| self.latest(t, |x| x.cgroups.clone())),
+            units: section(Units::FIELD).and_then(|t| self.latest(t, |x| x.units.clone())),
+            watched: section(Watched::FIELD).and_then(|t| self.latest(t, |x| x.watched.clone())),
+            processes: section(Processes::FIELD)
+                .and_then(|t| self.latest(t, |x| x.processes.clone())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use common::monitoring::{CpuResponse, NetworkResponse, Pack};
+
+    use crate::source::SystemSource;
+
+    use super::{read_packs, Recorder, Replay};
+
+    fn pack(offset_ms: u64, temperature: Option<f32>) -> Pack {
+        Pack {
+            cpu: temperature.map(|x| CpuResponse {
+                usage: vec![x],
+                temperature: Some(x),
+                frequency: vec![1000],
+                ..Default::default()
+            }),
+            network: Some(NetworkResponse::default()),
+            timestamp: Some((SystemTime::UNIX_EPOCH + Duration::from_millis(offset_ms)).into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let path = std::env::temp_dir().join(format!("record-{}.bin", std::process::id()));
+        let packs = vec![pack(0, Some(40.0)), pack(100, None), pack(200, Some(80.5))];
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        packs.iter().for_each(|x| recorder.write(x).unwrap());
+        drop(recorder);
+
+        let read = read_packs(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, packs);
+    }
+
+    #[test]
+    fn test_replay_follows_timestamps() {
+        let replay = Replay::new(
+            vec![
+                pack(1000, Some(40.0)),
+                pack(1100, None),
+                pack(1200, Some(80.5)),
+            ],
+            1.0,
+        )
+        .unwrap();
+        let temperature =
+            |t| replay.latest(Duration::from_millis(t), |x| x.cpu.as_ref()?.temperature);
+
+        assert_eq!(temperature(0), Some(40.0));
+        assert_eq!(temperature(150), Some(40.0));
+        assert_eq!(temperature(200), Some(80.5));
+        assert_eq!(temperature(10_000), Some(80.5));
+    }
+
+    #[test]
+    fn test_accelerated_replay() {
+        let mut replay =
+            Replay::new(vec![pack(0, Some(40.0)), pack(60_000, Some(90.0))], 1e6).unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(replay.temperatures(), vec![90.0]);
+        assert_eq!(replay.cpus(), vec![(90.0, 1000)]);
+    }
+
+    #[test]
+    fn test_every_section_is_replayed() {
+        let mut replay = Replay::new(vec![pack(0, Some(40.0)), pack(50, None)], 1e6).unwrap();
+
+        std::thread::sleep(Duration::from_millis(10));
+        let recorded = replay.recorded(&|x| x != "network").unwrap();
+        assert_eq!(recorded.cpu.unwrap().temperature, Some(40.0));
+        assert!(recorded.network.is_none());
+        assert!(replay.recorded(&|_| true).unwrap().network.is_some());
+    }
+
+    #[test]
+    fn test_empty_recording_is_rejected() {
+        assert!(Replay::new(vec![], 1.0).is_err());
+    }
+}
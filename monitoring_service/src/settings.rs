@@ -0,0 +1,153 @@
+use std::{collections::BTreeMap, error::Error, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alerts::AlertsConfig, config::CollectorConfig, events::KEPT_EVENTS, watch::KEPT_SAMPLES,
+};
+
+/// Used when neither `--update` nor the config sets a sampling interval.
+pub const DEFAULT_SAMPLING_INTERVAL_MS: u64 = 100;
+const MAX_SAMPLING_INTERVAL_MS: u64 = 60 * 60 * 1000;
+/// Used when the config does not say how long clients share a collection.
+pub const DEFAULT_CACHE_MAX_AGE_MS: u64 = 100;
+/// Upper bound of every `[retention]` count, so a typo cannot exhaust the memory.
+const MAX_KEPT: usize = 100_000;
+
+/// How much history is kept in memory, `[retention]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Events kept for clients that poll rarely.
+    pub events: usize,
+    /// Samples kept for every watched process.
+    pub watched_samples: usize,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            events: KEPT_EVENTS,
+            watched_samples: KEPT_SAMPLES,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidSettings(String);
+
+impl Display for InvalidSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid settings: {}", self.0)
+    }
+}
+
+impl Error for InvalidSettings {}
+
+/// Part of the config that can be changed while the service runs, kept under the same keys
+/// as in the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub sampling_interval_ms: u64,
+    pub cache_max_age_ms: u64,
+    pub collectors: BTreeMap<String, CollectorConfig>,
+    pub alerts: AlertsConfig,
+    pub retention: RetentionConfig,
+}
+
+impl Settings {
+    /// Checks what the types cannot. Unknown collectors are left to the registry.
+    pub fn validate(&self) -> Result<(), InvalidSettings> {
+        if !(1..=MAX_SAMPLING_INTERVAL_MS).contains(&self.sampling_interval_ms) {
+            return Err(InvalidSettings(format!(
+                "the sampling interval has to be between 1 and {MAX_SAMPLING_INTERVAL_MS} ms"
+            )));
+        }
+        if self.cache_max_age_ms > MAX_SAMPLING_INTERVAL_MS {
+            return Err(InvalidSettings(format!(
+                "the cache age is longer than {MAX_SAMPLING_INTERVAL_MS} ms"
+            )));
+        }
+        self.alerts.validate().map_err(InvalidSettings)?;
+
+        let retention = [
+            ("events", self.retention.events),
+            ("watched_samples", self.retention.watched_samples),
+        ];
+        for (name, count) in retention {
+            if !(1..=MAX_KEPT).contains(&count) {
+                return Err(InvalidSettings(format!(
+                    "retention of {name} must be between 1 and {MAX_KEPT}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the settings over their keys in the config file at `path`. The rest of the file,
+    /// comments included, is kept.
+    pub fn persist(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut document = std::fs::read_to_string(path)?.parse::<toml_edit::Document>()?;
+        let settings = toml::to_string(self)?.parse::<toml_edit::Document>()?;
+        for (key, item) in settings.iter() {
+            document[key] = item.clone();
+        }
+
+        // Replaced at once, so a crash cannot leave half a config behind
+        let mut staged = path.as_os_str().to_owned();
+        staged.push(".new");
+        std::fs::write(&staged, document.to_string())?;
+        std::fs::set_permissions(&staged, std::fs::metadata(path)?.permissions())?;
+        std::fs::rename(&staged, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::Config, test_util::Fixture};
+
+    #[test]
+    fn test_validation_and_persistence() {
+        let fixture = Fixture::new("settings");
+        fixture.write(
+            "monitor.toml",
+            r#"
+            [collectors.sensors]
+            enabled = false
+
+            # Who may change the settings
+            [auth.tokens]
+            oncall = "s3cret"
+            "#,
+        );
+        let path = fixture.path().join("monitor.toml");
+        let config = Config::load(&path).unwrap();
+        let mut settings = config.settings();
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.retention.events, 256);
+
+        settings.retention.watched_samples = 0;
+        assert!(settings.validate().is_err());
+        settings.retention.watched_samples = 60;
+        settings.sampling_interval_ms = 0;
+        assert!(settings.validate().is_err());
+        settings.sampling_interval_ms = 1000;
+        settings.cache_max_age_ms = 250;
+        settings.collectors.remove("sensors");
+        settings
+            .collectors
+            .entry("cpu".to_owned())
+            .or_default()
+            .interval_ms = Some(500);
+        settings.alerts.temperature_celsius = Some(85.0);
+        settings.persist(&path).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("# Who may change the settings\n"));
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.settings(), settings);
+        assert_eq!(config.auth.tokens["oncall"], "s3cret");
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::HashMap, io::ErrorKind, path::Path};
+
+use serde::Deserialize;
+use sysinfo::{Pid, PidExt, ProcessExt, ProcessRefreshKind, System, SystemExt};
+
+use crate::processes::read_status;
+
+/// `[signals]` section of the config. Processes matching neither list cannot be signalled.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SignalsConfig {
+    /// Process names as in `comm`, which the kernel cuts to 15 characters.
+    pub names: Vec<String>,
+    /// Users whose processes may be signalled.
+    pub users: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Term,
+    Kill,
+    Hup,
+    Stop,
+    Cont,
+}
+
+impl From<Signal> for sysinfo::Signal {
+    fn from(signal: Signal) -> Self {
+        match signal {
+            Signal::Term => sysinfo::Signal::Term,
+            Signal::Kill => sysinfo::Signal::Kill,
+            Signal::Hup => sysinfo::Signal::Hangup,
+            Signal::Stop => sysinfo::Signal::Stop,
+            Signal::Cont => sysinfo::Signal::Continue,
+        }
+    }
+}
+
+/// User IDs by name from the contents of `/etc/passwd`.
+pub fn parse_passwd(content: &str) -> HashMap<String, u32> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+
+            Some((name.to_owned(), uid))
+        })
+        .collect()
+}
+
+/// Start of a process in clock ticks since boot, field 22 of `/proc/<pid>/stat`. Tells it
+/// apart from a later process that got the same PID.
+fn start_ticks(procfs_root: &Path, pid: u32) -> std::io::Result<u64> {
+    let path = procfs_root.join(pid.to_string()).join("stat");
+    let stat = std::fs::read_to_string(&path)?;
+
+    // Fields after the name, which may contain spaces and parentheses
+    stat.rsplit_once(')')
+        .and_then(|x| x.1.split_whitespace().nth(19)?.parse().ok())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("cannot parse {}", path.display()),
+            )
+        })
+}
+
+/// A process that passed [`check`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Allowed {
+    pid: u32,
+    start_ticks: u64,
+}
+
+impl Allowed {
+    /// Sends `signal` unless the PID was given to another process since the check.
+    pub fn send(&self, procfs_root: &Path, signal: Signal) -> std::io::Result<()> {
+        send_after(self.pid, signal, || {
+            if start_ticks(procfs_root, self.pid).ok() == Some(self.start_ticks) {
+                Ok(())
+            } else {
+                Err(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("PID {} exited and was reused", self.pid),
+                ))
+            }
+        })
+    }
+}
+
+/// Fails with `PermissionDenied` unless `pid` may be signalled: never the service itself or
+/// init, otherwise only processes allowed by the config.
+pub fn check(
+    config: &SignalsConfig,
+    procfs_root: &Path,
+    uids: &HashMap<String, u32>,
+    pid: u32,
+) -> std::io::Result<Allowed> {
+    let refuse = |reason: String| Err(std::io::Error::new(ErrorKind::PermissionDenied, reason));
+
+    if pid == std::process::id() {
+        return refuse("the service cannot signal itself".to_owned());
+    }
+    if pid <= 1 {
+        return refuse(format!("PID {pid} cannot be signalled"));
+    }
+    // Read before the status, so the allowed process cannot be replaced in between
+    let start_ticks = start_ticks(procfs_root, pid)?;
+    let status = read_status(&procfs_root.join(pid.to_string()))?;
+
+    let by_name = config.names.contains(&status.name);
+    let by_user = config
+        .users
+        .iter()
+        .any(|x| uids.get(x).is_some_and(|x| Some(*x) == status.uid));
+    if by_name || by_user {
+        Ok(Allowed { pid, start_ticks })
+    } else {
+        refuse(format!(
+            "{} (PID {pid}) is not allowed by the signals section of the config",
+            status.name
+        ))
+    }
+}
+
+pub fn send(pid: u32, signal: Signal) -> std::io::Result<()> {
+    send_after(pid, signal, || Ok(()))
+}
+
+/// Runs `recheck` right before the signal is sent, and gives up if it fails.
+fn send_after(
+    pid: u32,
+    signal: Signal,
+    recheck: impl FnOnce() -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_process_specifics(pid, ProcessRefreshKind::new());
+    let process = system
+        .process(pid)
+        .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, format!("no process {pid}")))?;
+    recheck()?;
+
+    match process.kill_with(signal.into()) {
+        Some(true) => Ok(()),
+        Some(false) => Err(std::io::Error::last_os_error()),
+        None => Err(std::io::Error::new(
+            ErrorKind::Unsupported,
+            format!("{signal:?} is not supported on this platform"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::ErrorKind, process::Command};
+
+    use crate::test_util::Fixture;
+
+    use super::{check, parse_passwd, Signal, SignalsConfig};
+
+    fn stat(fixture: &Fixture, pid: u32, name: &str, start_ticks: u64) {
+        fixture.write(
+            &format!("{pid}/stat"),
+            // Fields 3 to 21 do not matter here
+            &format!("{pid} ({name}) S{} {start_ticks} 0", " 0".repeat(18)),
+        );
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let fixture = Fixture::new("signals-allowlist");
+        fixture
+            .write("1/status", "Name:\tsystemd\nUid:\t0\t0\t0\t0")
+            .write("40/status", "Name:\tapp\nUid:\t0\t0\t0\t0")
+            .write("41/status", "Name:\tworker\nUid:\t1001\t1001\t1001\t1001")
+            .write("42/status", "Name:\tsshd\nUid:\t0\t0\t0\t0");
+        for pid in [40, 41, 42] {
+            stat(&fixture, pid, "app", 1000);
+        }
+        let config = SignalsConfig {
+            names: vec!["app".to_owned(), "systemd".to_owned()],
+            users: vec!["deploy".to_owned()],
+        };
+        let uids = parse_passwd(
+            "root:x:0:0:root:/root:/bin/bash\ndeploy:x:1001:1001::/home/deploy:/bin/sh",
+        );
+        let check = |pid| check(&config, fixture.path(), &uids, pid);
+
+        assert!(check(40).is_ok());
+        assert!(check(41).is_ok());
+        assert_eq!(check(42).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(check(1).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(
+            check(std::process::id()).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(check(99).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_reused_pid_is_not_signalled() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id();
+        let fixture = Fixture::new("signals-reused");
+        fixture.write(&format!("{pid}/status"), "Name:\tsleep\nUid:\t0");
+        stat(&fixture, pid, "sleep (1)", 500);
+        let config = SignalsConfig {
+            names: vec!["sleep".to_owned()],
+            ..Default::default()
+        };
+
+        let allowed = check(&config, fixture.path(), &HashMap::new(), pid).unwrap();
+        stat(&fixture, pid, "sleep", 900);
+        let error = allowed.send(fixture.path(), Signal::Term).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert!(child.try_wait().unwrap().is_none());
+
+        stat(&fixture, pid, "sleep", 500);
+        allowed.send(fixture.path(), Signal::Kill).unwrap();
+        assert!(!child.wait().unwrap().success());
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use common::monitoring::{Pack, ServiceStats};
+use tokio::sync::Mutex;
+use tonic::Status;
+
+use crate::{
+    alerts::Alerts,
+    collector::{Fields, Sample, Sampler, UnknownCollectors},
+    config::CollectorConfig,
+    health::CollectorHealth,
+    settings::Settings,
+};
+
+/// Shortest period of the background sampling, for sampling intervals of zero.
+const MIN_BACKGROUND_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Snapshot {
+    taken: Instant,
+    /// Requested fields, sorted, empty when every field was collected.
+    fields: Vec<String>,
+    pack: Pack,
+    /// Set when every collector of the snapshot has failed.
+    failure: Option<String>,
+}
+
+impl Snapshot {
+    fn covers(&self, fields: &[String]) -> bool {
+        self.fields.is_empty()
+            || (!fields.is_empty() && fields.iter().all(|x| self.fields.contains(x)))
+    }
+}
+
+/// Shares collected packs between clients.
+///
+/// The latest snapshot of every requested field set is reused while it is younger than
+/// `max_age`, so clients asking for different fields do not evict each other. Requests that
+/// arrive while a collection is running wait for it instead of starting their own one.
+/// Every new snapshot is checked against the alert thresholds.
+///
+/// [`Snapshots::run`] samples every sampling interval, so history, throttled time, wear and
+/// events build up while no client polls.
+pub struct Snapshots {
+    /// Shared with the blocking thread that runs the collectors.
+    sampler: Arc<Mutex<Sampler>>,
+    health: CollectorHealth,
+    alerts: std::sync::Mutex<Alerts>,
+    fields: Fields,
+    interval: std::sync::Mutex<Duration>,
+    max_age: std::sync::Mutex<Duration>,
+    latest: std::sync::Mutex<HashMap<Vec<String>, Arc<Snapshot>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Snapshots {
+    pub fn new(
+        sampler: Sampler,
+        health: CollectorHealth,
+        alerts: Alerts,
+        interval: Duration,
+        max_age: Duration,
+    ) -> Self {
+        Snapshots {
+            fields: sampler.fields().clone(),
+            sampler: Arc::new(Mutex::new(sampler)),
+            health,
+            alerts: std::sync::Mutex::new(alerts),
+            interval: std::sync::Mutex::new(interval),
+            max_age: std::sync::Mutex::new(max_age),
+            latest: Default::default(),
+            hits: Default::default(),
+            misses: Default::default(),
+        }
+    }
+
+    fn cached(
+        &self,
+        fields: &[String],
+        requested: Instant,
+        max_age: Duration,
+    ) -> Option<Arc<Snapshot>> {
+        let latest = self.latest.lock().unwrap();
+
+        latest
+            .values()
+            .filter(|x| x.taken >= requested || x.taken.elapsed() <= max_age)
+            .filter(|x| x.covers(fields))
+            .max_by_key(|x| x.taken)
+            .cloned()
+    }
+
+    /// Keeps `snapshot` as the latest one of its fields and drops the stale ones.
+    fn store(&self, snapshot: Arc<Snapshot>) {
+        let max_age = *self.max_age.lock().unwrap();
+        let mut latest = self.latest.lock().unwrap();
+
+        latest.retain(|_, x| x.taken.elapsed() <= max_age);
+        latest.insert(snapshot.fields.clone(), snapshot);
+    }
+
+    /// Returns a snapshot younger than `max_age` or taken after the call, and whether it was
+    /// cached.
+    async fn snapshot(&self, fields: &[String], max_age: Duration) -> (Arc<Snapshot>, bool) {
+        let requested = Instant::now();
+        let mut fields = fields.to_vec();
+        fields.sort();
+        fields.dedup();
+        let fields = fields.as_slice();
+
+        if let Some(snapshot) = self.cached(fields, requested, max_age) {
+            return (snapshot, true);
+        }
+
+        let sampler = self.sampler.clone().lock_owned().await;
+        if let Some(snapshot) = self.cached(fields, requested, max_age) {
+            return (snapshot, true);
+        }
+
+        let taken = Instant::now();
+        // Collectors read sysfs and call D-Bus, which would stall the other requests
+        let wanted = fields.to_vec();
+        let (_sampler, Sample { pack, results }) = tokio::task::spawn_blocking(move || {
+            let mut sampler = sampler;
+            let sample = sampler.sample(|x| wanted.is_empty() || wanted.iter().any(|f| f == x));
+            (sampler, sample)
+        })
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+
+        let mut failure = None;
+        for (name, result) in results.iter() {
+            self.health.report(name, result.is_ok()).await;
+            if let Err(e) = result {
+                failure = Some(format!("{name} collector failed: {e}"));
+            }
+        }
+        if results.iter().any(|x| x.1.is_ok()) {
+            failure = None;
+        }
+        self.alerts.lock().unwrap().check(&pack);
+
+        let snapshot = Arc::new(Snapshot {
+            taken,
+            fields: fields.to_vec(),
+            pack,
+            failure,
+        });
+        self.store(snapshot.clone());
+        (snapshot, false)
+    }
+
+    /// Samples every enabled collector each sampling interval, never sooner than
+    /// [`MIN_BACKGROUND_INTERVAL`]. Clients reuse these snapshots like their own, but they are
+    /// not counted in the cache statistics.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let interval = *self.interval.lock().unwrap();
+            tokio::time::sleep(interval.max(MIN_BACKGROUND_INTERVAL)).await;
+            // Failures are reported through the health service
+            self.snapshot(&[], Duration::ZERO).await;
+        }
+    }
+
+    /// Returns a pack with the given fields, or with all of them if `fields` is empty.
+    pub async fn get(&self, fields: &[String]) -> Result<Pack, Status> {
+        if let Some(field) = fields
+            .iter()
+            .find(|x| !self.fields.contains_key(x.as_str()))
+        {
+            return Err(Status::invalid_argument(format!("unknown field {field}")));
+        }
+
+        let max_age = *self.max_age.lock().unwrap();
+        let (snapshot, cached) = self.snapshot(fields, max_age).await;
+        let counter = if cached { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Some(failure) = &snapshot.failure {
+            return Err(Status::unavailable(failure));
+        }
+
+        let mut pack = snapshot.pack.clone();
+        if !fields.is_empty() {
+            self.fields
+                .iter()
+                .filter(|(field, _)| !fields.iter().any(|x| x == *field))
+                .for_each(|(_, clear)| clear(&mut pack));
+        }
+        Ok(pack)
+    }
+
+    /// Applies the collector settings, sampling interval, cache age and alert thresholds.
+    /// Nothing changes if the settings mention unknown collectors. The next request starts
+    /// a new collection.
+    pub async fn reconfigure(&self, settings: &Settings) -> Result<(), UnknownCollectors> {
+        let mut sampler = self.sampler.lock().await;
+        let interval = Duration::from_millis(settings.sampling_interval_ms);
+        let collectors = settings.collectors.clone().into_iter().collect();
+
+        let before = sampler.registry().names();
+        sampler.registry_mut().reconfigure(collectors, interval)?;
+        let after = sampler.registry().names();
+        for name in before.iter().filter(|x| !after.contains(x)) {
+            self.health.forget(name).await;
+        }
+        for name in after.iter().filter(|x| !before.contains(x)) {
+            self.health.report(name, true).await;
+        }
+
+        *self.interval.lock().unwrap() = interval;
+        *self.max_age.lock().unwrap() = Duration::from_millis(settings.cache_max_age_ms);
+        self.alerts
+            .lock()
+            .unwrap()
+            .configure(settings.alerts.clone());
+        self.latest.lock().unwrap().clear();
+        Ok(())
+    }
+
+    /// Every registered collector with its settings.
+    pub async fn collectors(&self) -> Vec<(&'static str, CollectorConfig)> {
+        self.sampler.lock().await.registry().collectors()
+    }
+
+    pub async fn stats(&self) -> ServiceStats {
+        ServiceStats {
+            collectors: self.sampler.lock().await.stats(),
+            cache_hits: self.hits.load(Ordering::Relaxed),
+            cache_misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use sysinfo::{System, SystemExt};
+
+    use crate::{
+        alerts::Alerts,
+        collect_info::CpuCollector,
+        collector::{tests::Counter, Registry, Sampler},
+        config::{CollectorConfig, Config},
+        events::Events,
+        health::CollectorHealth,
+    };
+
+    use super::Snapshots;
+
+    async fn snapshots(max_age: Duration) -> Snapshots {
+        let mut registry = Registry::new(HashMap::new(), Duration::ZERO);
+        registry.register(Counter::default());
+        with_registry(registry, max_age).await
+    }
+
+    async fn with_registry(registry: Registry, max_age: Duration) -> Snapshots {
+        let (reporter, _) = tonic_health::server::health_reporter();
+        let health = CollectorHealth::new(reporter, &registry.names()).await;
+
+        Snapshots::new(
+            Sampler::new(Box::new(System::new()), registry),
+            health,
+            Alerts::new(Default::default(), Events::default()),
+            Duration::ZERO,
+            max_age,
+        )
+    }
+
+    async fn count(snapshots: &Snapshots) -> usize {
+        let pack = snapshots.get(&[]).await.unwrap();
+        pack.network.unwrap().interfaces.len()
+    }
+
+    #[tokio::test]
+    async fn test_fresh_snapshot_is_shared() {
+        let snapshots = snapshots(Duration::from_secs(60)).await;
+
+        assert_eq!(count(&snapshots).await, 1);
+        assert_eq!(count(&snapshots).await, 1);
+
+        let stats = snapshots.stats().await;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_stale_snapshot_is_refreshed() {
+        let snapshots = snapshots(Duration::ZERO).await;
+
+        assert_eq!(count(&snapshots).await, 1);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert_eq!(count(&snapshots).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_coalesced() {
+        let snapshots = Arc::new(snapshots(Duration::ZERO).await);
+        let sampler = snapshots.sampler.lock().await;
+
+        let requests = (0..8)
+            .map(|_| {
+                let snapshots = snapshots.clone();
+                tokio::spawn(async move { count(&snapshots).await })
+            })
+            .collect::<Vec<_>>();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(sampler);
+
+        for request in requests {
+            assert_eq!(request.await.unwrap(), 1);
+        }
+        assert_eq!(snapshots.stats().await.cache_misses, 1);
+    }
+
+    #[tokio::test]
+    async fn test_background_sampling() {
+        let snapshots = Arc::new(snapshots(Duration::from_secs(60)).await);
+        let background = tokio::spawn(snapshots.clone().run());
+
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        background.abort();
+        assert!(count(&snapshots).await >= 2);
+        let stats = snapshots.stats().await;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_masks() {
+        let snapshots = snapshots(Duration::from_secs(60)).await;
+
+        assert!(snapshots.get(&["gpu".to_owned()]).await.is_err());
+        let pack = snapshots.get(&["network".to_owned()]).await.unwrap();
+        assert!(pack.network.is_some());
+        assert!(snapshots.get(&["cpu".to_owned()]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_field_sets_are_cached_apart() {
+        let mut registry = Registry::new(HashMap::new(), Duration::ZERO);
+        registry.register(Counter::default()).register(CpuCollector);
+        let snapshots = with_registry(registry, Duration::from_secs(60)).await;
+        let network = ["network".to_owned()];
+        let cpu = ["cpu".to_owned()];
+
+        assert!(snapshots.get(&network).await.unwrap().cpu.is_none());
+        assert!(snapshots.get(&cpu).await.unwrap().network.is_none());
+        assert!(snapshots.get(&network).await.is_ok());
+        assert!(snapshots.get(&cpu).await.is_ok());
+
+        let stats = snapshots.stats().await;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure() {
+        let snapshots = snapshots(Duration::from_secs(60)).await;
+        assert_eq!(count(&snapshots).await, 1);
+
+        let mut settings = Config::default().settings();
+        settings
+            .collectors
+            .insert("gpu".to_owned(), Default::default());
+        assert!(snapshots.reconfigure(&settings).await.is_err());
+        assert_eq!(count(&snapshots).await, 1);
+
+        settings.collectors.clear();
+        let disabled = CollectorConfig {
+            enabled: Some(false),
+            interval_ms: None,
+        };
+        settings.collectors.insert("counter".to_owned(), disabled);
+        snapshots.reconfigure(&settings).await.unwrap();
+        assert!(snapshots.get(&[]).await.unwrap().network.is_none());
+        assert!(snapshots.stats().await.collectors.is_empty());
+
+        settings.collectors.clear();
+        settings.sampling_interval_ms = 0;
+        settings.cache_max_age_ms = 0;
+        snapshots.reconfigure(&settings).await.unwrap();
+        assert_eq!(count(&snapshots).await, 2);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert_eq!(count(&snapshots).await, 3);
+        let collectors = snapshots.collectors().await;
+        assert_eq!(collectors[0].1.enabled, Some(true));
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    f64::consts::TAU,
+    time::{Duration, Instant},
+};
+
+use common::monitoring::Pack;
+use serde::Deserialize;
+use sysinfo::{
+    ComponentExt, CpuExt, CpuRefreshKind, NetworkExt, PidExt, ProcessExt, ProcessRefreshKind,
+    System, SystemExt,
+};
+
+/// A running process as seen by the source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub parent: Option<u32>,
+    pub name: String,
+    pub cmd: Vec<String>,
+    /// Percent of one CPU since the previous call.
+    pub cpu_usage: f32,
+    /// Resident set size in bytes.
+    pub memory: u64,
+    /// Seconds since the epoch.
+    pub start_time: u64,
+}
+
+/// Where collectors take their readings from.
+pub trait SystemSource: Send {
+    /// Usage in percent and frequency in MHz of every logical CPU.
+    fn cpus(&mut self) -> Vec<(f32, u64)>;
+
+    /// Readings of every temperature sensor in °C.
+    fn temperatures(&mut self) -> Vec<f32>;
+
+    /// Bytes received and transmitted by every interface since the previous call.
+    fn networks(&mut self) -> Vec<(String, u64, u64)>;
+
+    /// Running processes, none for sources that do not model them.
+    fn processes(&mut self) -> Vec<ProcessInfo> {
+        vec![]
+    }
+
+    /// Whether the readings are those of the machine the service runs on.
+    fn is_host(&self) -> bool {
+        true
+    }
+
+    /// A recorded pack with the sections in `fields`, served instead of running the collectors.
+    fn recorded(&mut self, _fields: &dyn Fn(&str) -> bool) -> Option<Pack> {
+        None
+    }
+}
+
+impl SystemSource for System {
+    fn cpus(&mut self) -> Vec<(f32, u64)> {
+        self.refresh_cpu_specifics(CpuRefreshKind::new().with_cpu_usage().with_frequency());
+        SystemExt::cpus(self)
+            .iter()
+            .map(|c| (c.cpu_usage(), c.frequency()))
+            .collect()
+    }
+
+    fn temperatures(&mut self) -> Vec<f32> {
+        self.refresh_components();
+        SystemExt::components(self)
+            .iter()
+            .map(|c| c.temperature())
+            .collect()
+    }
+
+    fn networks(&mut self) -> Vec<(String, u64, u64)> {
+        self.refresh_networks();
+        SystemExt::networks(self)
+            .into_iter()
+            .map(|(name, data)| (name.clone(), data.received(), data.transmitted()))
+            .collect()
+    }
+
+    fn processes(&mut self) -> Vec<ProcessInfo> {
+        self.refresh_processes_specifics(ProcessRefreshKind::new().with_cpu());
+        SystemExt::processes(self)
+            .values()
+            .map(|x| ProcessInfo {
+                pid: x.pid().as_u32(),
+                parent: x.parent().map(|x| x.as_u32()),
+                name: x.name().to_owned(),
+                cmd: x.cmd().to_vec(),
+                cpu_usage: x.cpu_usage(),
+                memory: x.memory(),
+                start_time: x.start_time(),
+            })
+            .collect()
+    }
+}
+
+/// Refreshing processes twice within a sample would leave the second caller with CPU usage
+/// measured over a few microseconds, so the list is shared for this long.
+const PROCESSES_TTL: Duration = Duration::from_millis(200);
+
+/// Passes readings through, sharing one process list between the collectors of a sample.
+pub struct SharedProcesses<S> {
+    source: S,
+    latest: Option<(Instant, Vec<ProcessInfo>)>,
+}
+
+impl<S> SharedProcesses<S> {
+    pub fn new(source: S) -> Self {
+        SharedProcesses {
+            source,
+            latest: None,
+        }
+    }
+}
+
+impl<S: SystemSource> SystemSource for SharedProcesses<S> {
+    fn cpus(&mut self) -> Vec<(f32, u64)> {
+        self.source.cpus()
+    }
+
+    fn temperatures(&mut self) -> Vec<f32> {
+        self.source.temperatures()
+    }
+
+    fn networks(&mut self) -> Vec<(String, u64, u64)> {
+        self.source.networks()
+    }
+
+    fn is_host(&self) -> bool {
+        self.source.is_host()
+    }
+
+    fn processes(&mut self) -> Vec<ProcessInfo> {
+        match &self.latest {
+            Some((time, processes)) if time.elapsed() < PROCESSES_TTL => processes.clone(),
+            _ => {
+                let processes = self.source.processes();
+                self.latest = Some((Instant::now(), processes.clone()));
+                processes
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sine {
+    pub amplitude: f64,
+    pub period_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Added to the signal during every second half of the period.
+    pub height: f64,
+    pub period_ms: u64,
+}
+
+/// Synthetic signal: `base + sine + step + noise`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Signal {
+    pub base: f64,
+    pub sine: Option<Sine>,
+    pub step: Option<Step>,
+    /// Amplitude of the uniform noise.
+    pub noise: f64,
+}
+
+impl Signal {
+    fn constant(base: f64) -> Self {
+        Signal {
+            base,
+            ..Default::default()
+        }
+    }
+
+    /// Value at time `t`, `random` is uniformly distributed in `[-1, 1]`.
+    fn value(&self, t: Duration, random: f64) -> f64 {
+        let t = t.as_millis() as f64;
+        let sine = self.sine.as_ref().map_or(0.0, |x| {
+            x.amplitude * (TAU * t / x.period_ms.max(1) as f64).sin()
+        });
+        let step = self.step.as_ref().map_or(0.0, |x| {
+            let period = x.period_ms.max(1) as f64;
+            if t % period >= period / 2.0 {
+                x.height
+            } else {
+                0.0
+            }
+        });
+
+        self.base + sine + step + self.noise * random
+    }
+}
+
+/// `[simulation]` section used with `--simulate`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub cpus: usize,
+    pub frequency_mhz: u64,
+    /// Usage in percent, clamped to `[0, 100]`. Every core is shifted in phase.
+    pub cpu_usage: Signal,
+    /// Temperature in °C.
+    pub temperature: Signal,
+    pub interfaces: Vec<String>,
+    /// Traffic of every interface in bytes per second, in both directions.
+    pub network: Signal,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            cpus: 4,
+            frequency_mhz: 1800,
+            cpu_usage: Signal {
+                base: 35.0,
+                sine: Some(Sine {
+                    amplitude: 25.0,
+                    period_ms: 20_000,
+                }),
+                step: None,
+                noise: 5.0,
+            },
+            temperature: Signal {
+                base: 50.0,
+                sine: None,
+                step: Some(Step {
+                    height: 15.0,
+                    period_ms: 60_000,
+                }),
+                noise: 1.0,
+            },
+            interfaces: vec!["eth0".to_owned(), "lo".to_owned()],
+            network: Signal::constant(64.0 * 1024.0),
+        }
+    }
+}
+
+/// Generates readings from [`Signal`]s instead of the real system.
+pub struct Simulated {
+    config: SimulationConfig,
+    start: Instant,
+    last_network: Duration,
+    seed: u64,
+}
+
+impl Simulated {
+    pub fn new(config: SimulationConfig) -> Self {
+        Simulated {
+            config,
+            start: Instant::now(),
+            last_network: Duration::ZERO,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    /// Xorshift generator, uniformly distributed in `[-1, 1]`.
+    fn random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    fn cpus_at(&mut self, t: Duration) -> Vec<(f32, u64)> {
+        let cpus = self.config.cpus;
+        let period = self
+            .config
+            .cpu_usage
+            .sine
+            .as_ref()
+            .map_or(0, |x| x.period_ms);
+
+        (0..cpus)
+            .map(|i| {
+                let phase = Duration::from_millis(period * i as u64 / cpus as u64);
+                let random = self.random();
+                let usage = self.config.cpu_usage.value(t + phase, random);
+                (usage.clamp(0.0, 100.0) as f32, self.config.frequency_mhz)
+            })
+            .collect()
+    }
+
+    fn temperatures_at(&mut self, t: Duration) -> Vec<f32> {
+        let random = self.random();
+        vec![self.config.temperature.value(t, random) as f32]
+    }
+
+    fn networks_at(&mut self, t: Duration) -> Vec<(String, u64, u64)> {
+        let elapsed = t.saturating_sub(self.last_network).as_secs_f64();
+        self.last_network = t;
+
+        (0..self.config.interfaces.len())
+            .map(|i| {
+                let (a, b) = (self.random(), self.random());
+                let rate =
+                    |random| (self.config.network.value(t, random).max(0.0) * elapsed) as u64;
+                (self.config.interfaces[i].clone(), rate(a), rate(b))
+            })
+            .collect()
+    }
+}
+
+impl SystemSource for Simulated {
+    fn is_host(&self) -> bool {
+        false
+    }
+
+    fn cpus(&mut self) -> Vec<(f32, u64)> {
+        self.cpus_at(self.start.elapsed())
+    }
+
+    fn temperatures(&mut self) -> Vec<f32> {
+        self.temperatures_at(self.start.elapsed())
+    }
+
+    fn networks(&mut self) -> Vec<(String, u64, u64)> {
+        self.networks_at(self.start.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Signal, Simulated, SimulationConfig, Sine, Step};
+
+    #[test]
+    fn test_sine() {
+        let signal = Signal {
+            base: 10.0,
+            sine: Some(Sine {
+                amplitude: 5.0,
+                period_ms: 1000,
+            }),
+            ..Default::default()
+        };
+
+        assert!((signal.value(Duration::ZERO, 0.0) - 10.0).abs() < 1e-9);
+        assert!((signal.value(Duration::from_millis(250), 0.0) - 15.0).abs() < 1e-9);
+        assert!((signal.value(Duration::from_millis(750), 0.0) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_step_and_noise() {
+        let signal = Signal {
+            base: 40.0,
+            step: Some(Step {
+                height: 20.0,
+                period_ms: 1000,
+            }),
+            noise: 2.0,
+            ..Default::default()
+        };
+
+        assert_eq!(signal.value(Duration::from_millis(100), 0.0), 40.0);
+        assert_eq!(signal.value(Duration::from_millis(600), 0.0), 60.0);
+        assert_eq!(signal.value(Duration::from_millis(600), -1.0), 58.0);
+    }
+
+    #[test]
+    fn test_random_is_bounded() {
+        let mut source = Simulated::new(SimulationConfig::default());
+
+        assert!((0..1000)
+            .map(|_| source.random())
+            .all(|x| (-1.0..=1.0).contains(&x)));
+    }
+
+    #[test]
+    fn test_cpu_usage_is_clamped() {
+        let mut source = Simulated::new(SimulationConfig {
+            cpu_usage: Signal {
+                base: 150.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let cpus = source.cpus_at(Duration::ZERO);
+        assert_eq!(cpus.len(), 4);
+        assert!(cpus.iter().all(|x| x.0 == 100.0 && x.1 == 1800));
+    }
+
+    #[test]
+    fn test_network_is_rate_times_elapsed() {
+        let mut source = Simulated::new(SimulationConfig {
+            interfaces: vec!["sim0".to_owned()],
+            network: Signal::constant(1000.0),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            source.networks_at(Duration::from_secs(2)),
+            vec![("sim0".to_owned(), 2000, 2000)]
+        );
+        assert_eq!(
+            source.networks_at(Duration::from_millis(2500)),
+            vec![("sim0".to_owned(), 500, 500)]
+        );
+    }
+}
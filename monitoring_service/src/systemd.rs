@@ -0,0 +1,503 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use zbus::{
+    blocking::Connection,
+    zvariant::{OwnedObjectPath, OwnedValue},
+};
+
+use common::monitoring::{Pack, SystemdUnit, UnitResponse};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+};
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+const MANAGER_PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER: &str = "org.freedesktop.systemd1.Manager";
+const UNIT: &str = "org.freedesktop.systemd1.Unit";
+const SERVICE: &str = "org.freedesktop.systemd1.Service";
+/// systemd reports unset accounting values as the largest number.
+const NOT_SET: u64 = u64::MAX;
+/// Longest wait before asking a bus that failed again.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// `[systemd]` section of the config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SystemdConfig {
+    /// Units reported in every state, failed units are reported anyway.
+    pub units: Vec<String>,
+    /// D-Bus address to connect to instead of the system bus.
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    pub description: String,
+    pub load_state: String,
+    pub active_state: String,
+    pub sub_state: String,
+    /// Service properties, missing for other kinds of units.
+    pub restarts: Option<u32>,
+    pub main_pid: Option<u32>,
+    /// Missing when accounting is disabled.
+    pub memory: Option<u64>,
+    pub cpu_time: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units(pub Vec<Unit>);
+
+/// One entry of `ListUnits`.
+type ListedUnit = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    OwnedObjectPath,
+    u32,
+    String,
+    OwnedObjectPath,
+);
+
+pub(crate) fn dbus_error(e: zbus::Error) -> std::io::Error {
+    std::io::Error::other(format!("D-Bus: {e}"))
+}
+
+/// Reads unit states from systemd through its D-Bus API.
+pub struct SystemdCollector {
+    config: SystemdConfig,
+    connection: Option<Connection>,
+    /// Doubles with every failure in a row, so a missing bus is not asked on every sample.
+    backoff: Duration,
+    /// When to ask again after a failure, with its message.
+    retry: Option<(Instant, String)>,
+}
+
+impl std::fmt::Debug for SystemdCollector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemdCollector")
+            .field("config", &self.config)
+            .field("connected", &self.connection.is_some())
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+impl SystemdCollector {
+    pub fn new(config: SystemdConfig) -> Self {
+        SystemdCollector {
+            config,
+            connection: None,
+            backoff: Duration::ZERO,
+            retry: None,
+        }
+    }
+
+    /// Uses an established connection, tests serve a stand-in manager through it.
+    #[cfg(test)]
+    fn with_connection(config: SystemdConfig, connection: Connection) -> Self {
+        SystemdCollector {
+            connection: Some(connection),
+            ..SystemdCollector::new(config)
+        }
+    }
+
+    fn connect(&mut self) -> zbus::Result<&Connection> {
+        if self.connection.is_none() {
+            self.connection = Some(connect(self.config.address.as_deref())?);
+        }
+        Ok(self.connection.as_ref().unwrap())
+    }
+
+    fn read(&mut self) -> zbus::Result<Units> {
+        let configured = self.config.units.clone();
+        let connection = self.connect()?;
+
+        let mut paths = configured
+            .iter()
+            .map(|x| call_manager::<_, OwnedObjectPath>(connection, "LoadUnit", &x.as_str()))
+            .collect::<zbus::Result<Vec<_>>>()?;
+        let mut failed = call_manager::<_, Vec<ListedUnit>>(connection, "ListUnits", &())?
+            .into_iter()
+            .filter(|x| x.3 == "failed" && !configured.contains(&x.0))
+            .collect::<Vec<_>>();
+        failed.sort_by(|a, b| a.0.cmp(&b.0));
+        paths.extend(failed.into_iter().map(|x| x.6));
+
+        let units = paths
+            .iter()
+            .map(|path| read_unit(connection, path))
+            .collect::<zbus::Result<_>>()?;
+        Ok(Units(units))
+    }
+}
+
+/// Connects to `address`, or to the system bus without one.
+pub(crate) fn connect(address: Option<&str>) -> zbus::Result<Connection> {
+    match address {
+        Some(address) => zbus::blocking::ConnectionBuilder::address(address)?.build(),
+        None => Connection::system(),
+    }
+}
+
+pub(crate) fn call_manager<B, R>(connection: &Connection, method: &str, body: &B) -> zbus::Result<R>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+    R: for<'de> Deserialize<'de> + zbus::zvariant::Type,
+{
+    connection
+        .call_method(Some(DESTINATION), MANAGER_PATH, Some(MANAGER), method, body)?
+        .body()
+}
+
+fn properties(
+    connection: &Connection,
+    path: &OwnedObjectPath,
+    interface: &str,
+) -> zbus::Result<HashMap<String, OwnedValue>> {
+    connection
+        .call_method(
+            Some(DESTINATION),
+            path.as_str(),
+            Some("org.freedesktop.DBus.Properties"),
+            "GetAll",
+            &interface,
+        )?
+        .body()
+}
+
+fn read_unit(connection: &Connection, path: &OwnedObjectPath) -> zbus::Result<Unit> {
+    let unit = properties(connection, path, UNIT)?;
+    let string = |key: &str| {
+        unit.get(key)
+            .and_then(|x| String::try_from(x.clone()).ok())
+            .unwrap_or_default()
+    };
+    let service = if string("Id").ends_with(".service") {
+        properties(connection, path, SERVICE)?
+    } else {
+        HashMap::new()
+    };
+    let u32 = |key: &str| service.get(key).and_then(|x| u32::try_from(x.clone()).ok());
+    let u64 = |key: &str| {
+        service
+            .get(key)
+            .and_then(|x| u64::try_from(x.clone()).ok())
+            .filter(|x| *x != NOT_SET)
+    };
+
+    Ok(Unit {
+        name: string("Id"),
+        description: string("Description"),
+        load_state: string("LoadState"),
+        active_state: string("ActiveState"),
+        sub_state: string("SubState"),
+        restarts: u32("NRestarts"),
+        main_pid: u32("MainPID").filter(|x| *x != 0),
+        memory: u64("MemoryCurrent"),
+        cpu_time: u64("CPUUsageNSec").map(Duration::from_nanos),
+    })
+}
+
+impl Collector for SystemdCollector {
+    type Output = Units;
+
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(2)
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        if let Some((at, message)) = &self.retry {
+            if Instant::now() < *at {
+                return Err(std::io::Error::other(message.clone()));
+            }
+        }
+
+        self.read()
+            .inspect(|_| {
+                self.backoff = Duration::ZERO;
+                self.retry = None;
+            })
+            .map_err(|e| {
+                // Reconnect next time, systemd may have been restarted
+                self.connection = None;
+                self.backoff = (self.backoff * 2).clamp(self.interval(), MAX_BACKOFF);
+                let e = dbus_error(e);
+                self.retry = Some((Instant::now() + self.backoff, e.to_string()));
+                e
+            })
+    }
+}
+
+impl Section for Units {
+    const FIELD: &'static str = "units";
+
+    fn store(self, pack: &mut Pack) {
+        pack.units = Some(UnitResponse {
+            units: self
+                .0
+                .into_iter()
+                .map(|x| SystemdUnit {
+                    name: x.name,
+                    description: x.description,
+                    load_state: x.load_state,
+                    active_state: x.active_state,
+                    sub_state: x.sub_state,
+                    restarts: x.restarts,
+                    main_pid: x.main_pid,
+                    memory: x.memory,
+                    cpu_time_ms: x.cpu_time.map(|x| x.as_millis() as u64),
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.units = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, thread, time::Duration};
+
+    use sysinfo::{System, SystemExt};
+    use zbus::{
+        blocking::{Connection, ConnectionBuilder},
+        dbus_interface, fdo,
+        zvariant::OwnedObjectPath,
+        Guid,
+    };
+
+    use crate::collector::Collector;
+
+    use super::{ListedUnit, SystemdCollector, SystemdConfig, NOT_SET};
+
+    /// Stand-in for the systemd manager with a running and a crash-looping service.
+    struct Manager;
+
+    fn path(unit: &str) -> OwnedObjectPath {
+        let escaped = unit.replace('.', "_2e").replace('-', "_2d");
+        OwnedObjectPath::try_from(format!("/org/freedesktop/systemd1/unit/{escaped}")).unwrap()
+    }
+
+    fn listed(name: &str, active_state: &str) -> ListedUnit {
+        (
+            name.to_owned(),
+            String::new(),
+            "loaded".to_owned(),
+            active_state.to_owned(),
+            String::new(),
+            String::new(),
+            path(name),
+            0,
+            String::new(),
+            OwnedObjectPath::try_from("/").unwrap(),
+        )
+    }
+
+    #[dbus_interface(name = "org.freedesktop.systemd1.Manager")]
+    impl Manager {
+        fn list_units(&self) -> Vec<ListedUnit> {
+            vec![
+                listed("app.service", "failed"),
+                listed("nginx.service", "active"),
+                listed("backup.mount", "failed"),
+            ]
+        }
+
+        fn load_unit(&self, name: &str) -> fdo::Result<OwnedObjectPath> {
+            Ok(path(name))
+        }
+    }
+
+    struct Unit {
+        id: &'static str,
+        active_state: &'static str,
+        sub_state: &'static str,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.systemd1.Unit")]
+    impl Unit {
+        #[dbus_interface(property)]
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        #[dbus_interface(property)]
+        fn description(&self) -> &str {
+            "Stand-in"
+        }
+
+        #[dbus_interface(property)]
+        fn load_state(&self) -> &str {
+            "loaded"
+        }
+
+        #[dbus_interface(property)]
+        fn active_state(&self) -> &str {
+            self.active_state
+        }
+
+        #[dbus_interface(property)]
+        fn sub_state(&self) -> &str {
+            self.sub_state
+        }
+    }
+
+    struct Service {
+        restarts: u32,
+        main_pid: u32,
+        memory: u64,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.systemd1.Service")]
+    impl Service {
+        #[dbus_interface(property, name = "NRestarts")]
+        fn restarts(&self) -> u32 {
+            self.restarts
+        }
+
+        #[dbus_interface(property, name = "MainPID")]
+        fn main_pid(&self) -> u32 {
+            self.main_pid
+        }
+
+        #[dbus_interface(property, name = "MemoryCurrent")]
+        fn memory(&self) -> u64 {
+            self.memory
+        }
+
+        #[dbus_interface(property, name = "CPUUsageNSec")]
+        fn cpu(&self) -> u64 {
+            1_500_000_000
+        }
+    }
+
+    /// Connects to a stand-in manager over a socket pair, without a bus.
+    fn stand_in() -> (Connection, Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let guid = Guid::generate();
+            let [app, nginx, backup] = ["app.service", "nginx.service", "backup.mount"].map(path);
+            let connection = ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at("/org/freedesktop/systemd1", Manager)?
+                .serve_at(
+                    app.as_str(),
+                    Unit {
+                        id: "app.service",
+                        active_state: "failed",
+                        sub_state: "failed",
+                    },
+                )?
+                .serve_at(
+                    app.as_str(),
+                    Service {
+                        restarts: 5,
+                        main_pid: 0,
+                        memory: NOT_SET,
+                    },
+                )?
+                .serve_at(
+                    nginx.as_str(),
+                    Unit {
+                        id: "nginx.service",
+                        active_state: "active",
+                        sub_state: "running",
+                    },
+                )?
+                .serve_at(
+                    nginx.as_str(),
+                    Service {
+                        restarts: 0,
+                        main_pid: 812,
+                        memory: 12 << 20,
+                    },
+                )?
+                .serve_at(
+                    backup.as_str(),
+                    Unit {
+                        id: "backup.mount",
+                        active_state: "failed",
+                        sub_state: "failed",
+                    },
+                )?
+                .build();
+            connection
+        });
+        let client = ConnectionBuilder::unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+
+        (server.join().unwrap().unwrap(), client)
+    }
+
+    #[test]
+    fn test_configured_and_failed_units() {
+        let (_server, client) = stand_in();
+        let config = SystemdConfig {
+            units: vec!["nginx.service".to_owned()],
+            ..Default::default()
+        };
+        let mut collector = SystemdCollector::with_connection(config, client);
+
+        let units = collector.collect(&mut System::new()).unwrap().0;
+        let names = units.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["nginx.service", "app.service", "backup.mount"]);
+
+        let nginx = &units[0];
+        assert_eq!(
+            (nginx.active_state.as_str(), nginx.sub_state.as_str()),
+            ("active", "running")
+        );
+        assert_eq!(nginx.main_pid, Some(812));
+        assert_eq!(nginx.memory, Some(12 << 20));
+        assert_eq!(nginx.cpu_time, Some(Duration::from_millis(1500)));
+
+        let app = &units[1];
+        assert_eq!(app.active_state, "failed");
+        assert_eq!(app.restarts, Some(5));
+        assert_eq!((app.main_pid, app.memory), (None, None));
+        assert_eq!(units[2].restarts, None);
+    }
+
+    #[test]
+    fn test_unreachable_bus_fails() {
+        let mut collector = SystemdCollector::new(SystemdConfig {
+            address: Some("unix:path=/nonexistent/bus".to_owned()),
+            ..Default::default()
+        });
+
+        let error = collector.collect(&mut System::new()).unwrap_err();
+        assert_eq!(collector.backoff, Duration::from_secs(2));
+        // Not asked again before the backoff is over
+        collector.config.address = None;
+        let again = collector.collect(&mut System::new()).unwrap_err();
+        assert_eq!(again.to_string(), error.to_string());
+        assert_eq!(collector.backoff, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use common::monitoring::{self as proto, Pack, ThermalResponse};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TripPoint {
+    pub kind: String,
+    pub temperature: f32,
+    pub hysteresis: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoolingDevice {
+    pub kind: String,
+    pub current_state: u64,
+    pub max_state: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermalZone {
+    pub name: String,
+    pub kind: String,
+    pub temperature: Option<f32>,
+    pub trip_points: Vec<TripPoint>,
+    pub cooling_devices: Vec<CoolingDevice>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HwmonSensor {
+    pub chip: String,
+    pub label: String,
+    pub temperature: f32,
+    pub max: Option<f32>,
+    pub critical: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thermal {
+    pub zones: Vec<ThermalZone>,
+    pub sensors: Vec<HwmonSensor>,
+}
+
+/// Reads `class/thermal` and `class/hwmon` under a sysfs root.
+///
+/// Unlike the sensors collector it keeps every zone apart and does not depend on which
+/// chips sysinfo knows about.
+#[derive(Debug)]
+pub struct ThermalCollector {
+    sysfs_root: PathBuf,
+}
+
+impl ThermalCollector {
+    pub fn new(sysfs_root: impl Into<PathBuf>) -> Self {
+        ThermalCollector {
+            sysfs_root: sysfs_root.into(),
+        }
+    }
+}
+
+impl Collector for ThermalCollector {
+    type Output = Thermal;
+
+    fn name(&self) -> &'static str {
+        "thermal"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        Thermal::read(&self.sysfs_root)
+    }
+}
+
+pub(crate) fn read_string(path: &Path) -> std::io::Result<String> {
+    Ok(std::fs::read_to_string(path)?.trim().to_owned())
+}
+
+pub(crate) fn read_number<T: std::str::FromStr>(path: &Path) -> std::io::Result<T> {
+    read_string(path)?.parse().map_err(|_| {
+        std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is not a number", path.display()),
+        )
+    })
+}
+
+/// Reads a value in millidegrees Celsius.
+pub(crate) fn read_millidegrees(path: &Path) -> std::io::Result<f32> {
+    Ok(read_number::<i64>(path)? as f32 / 1000.0)
+}
+
+/// Sorted indices of the entries of `dir` named `<prefix><index><suffix>`.
+pub(crate) fn indices(dir: &Path, prefix: &str, suffix: &str) -> std::io::Result<Vec<u32>> {
+    let mut indices = std::fs::read_dir(dir)?
+        .filter_map(|x| x.ok())
+        .filter_map(|x| {
+            x.file_name()
+                .to_str()?
+                .strip_prefix(prefix)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()
+        })
+        .collect::<Vec<_>>();
+
+    indices.sort_unstable();
+    Ok(indices)
+}
+
+/// Treats a missing directory as an empty one.
+fn or_empty<T>(result: std::io::Result<Vec<T>>) -> std::io::Result<Vec<T>> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        result => result,
+    }
+}
+
+impl TripPoint {
+    fn read_all(zone: &Path) -> std::io::Result<Vec<Self>> {
+        indices(zone, "trip_point_", "_temp")?
+            .into_iter()
+            .map(|i| {
+                let attribute = |x: &str| zone.join(format!("trip_point_{i}_{x}"));
+                Ok(TripPoint {
+                    kind: read_string(&attribute("type"))?,
+                    temperature: read_millidegrees(&attribute("temp"))?,
+                    hysteresis: read_millidegrees(&attribute("hyst")).ok(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl CoolingDevice {
+    /// Reads the devices bound to a zone through its `cdev<N>` links.
+    fn read_all(zone: &Path) -> std::io::Result<Vec<Self>> {
+        indices(zone, "cdev", "")?
+            .into_iter()
+            .map(|i| {
+                let device = zone.join(format!("cdev{i}"));
+                Ok(CoolingDevice {
+                    kind: read_string(&device.join("type"))?,
+                    current_state: read_number(&device.join("cur_state"))?,
+                    max_state: read_number(&device.join("max_state"))?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl ThermalZone {
+    pub(crate) fn read_all(sysfs_root: &Path) -> std::io::Result<Vec<Self>> {
+        let class = sysfs_root.join("class/thermal");
+
+        or_empty(indices(&class, "thermal_zone", ""))?
+            .into_iter()
+            .map(|i| {
+                let name = format!("thermal_zone{i}");
+                let zone = class.join(&name);
+                Ok(ThermalZone {
+                    kind: read_string(&zone.join("type"))?,
+                    temperature: read_millidegrees(&zone.join("temp")).ok(),
+                    trip_points: TripPoint::read_all(&zone)?,
+                    cooling_devices: CoolingDevice::read_all(&zone)?,
+                    name,
+                })
+            })
+            .collect()
+    }
+}
+
+impl HwmonSensor {
+    fn read_all(sysfs_root: &Path) -> std::io::Result<Vec<Self>> {
+        let class = sysfs_root.join("class/hwmon");
+        let mut sensors = vec![];
+
+        for i in or_empty(indices(&class, "hwmon", ""))? {
+            let chip = class.join(format!("hwmon{i}"));
+            let name = read_string(&chip.join("name")).unwrap_or_else(|_| format!("hwmon{i}"));
+
+            for k in indices(&chip, "temp", "_input")? {
+                let attribute = |x: &str| chip.join(format!("temp{k}_{x}"));
+                let Ok(temperature) = read_millidegrees(&attribute("input")) else {
+                    continue;
+                };
+
+                let label = read_string(&attribute("label")).unwrap_or_else(|_| format!("temp{k}"));
+                sensors.push(HwmonSensor {
+                    chip: name.clone(),
+                    label,
+                    temperature,
+                    max: read_millidegrees(&attribute("max")).ok(),
+                    critical: read_millidegrees(&attribute("crit")).ok(),
+                });
+            }
+        }
+        Ok(sensors)
+    }
+}
+
+impl Thermal {
+    fn read(sysfs_root: &Path) -> std::io::Result<Self> {
+        let thermal = Thermal {
+            zones: ThermalZone::read_all(sysfs_root)?,
+            sensors: HwmonSensor::read_all(sysfs_root)?,
+        };
+
+        if thermal.zones.is_empty() && thermal.sensors.is_empty() {
+            return Err(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "no thermal zones or hwmon sensors under {}",
+                    sysfs_root.display()
+                ),
+            ));
+        }
+        Ok(thermal)
+    }
+}
+
+impl Section for Thermal {
+    const FIELD: &'static str = "thermal";
+
+    fn store(self, pack: &mut Pack) {
+        pack.thermal = Some(ThermalResponse {
+            zones: self
+                .zones
+                .into_iter()
+                .map(|x| proto::ThermalZone {
+                    name: x.name,
+                    r#type: x.kind,
+                    temperature: x.temperature,
+                    trip_points: x
+                        .trip_points
+                        .into_iter()
+                        .map(|x| proto::TripPoint {
+                            r#type: x.kind,
+                            temperature: x.temperature,
+                            hysteresis: x.hysteresis,
+                        })
+                        .collect(),
+                    cooling_devices: x
+                        .cooling_devices
+                        .into_iter()
+                        .map(|x| proto::CoolingDevice {
+                            r#type: x.kind,
+                            current_state: x.current_state,
+                            max_state: x.max_state,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            sensors: self
+                .sensors
+                .into_iter()
+                .map(|x| proto::HwmonSensor {
+                    chip: x.chip,
+                    label: x.label,
+                    temperature: x.temperature,
+                    max: x.max,
+                    critical: x.critical,
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.thermal = None;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use sysinfo::{System, SystemExt};
+
+    use crate::{
+        collector::{Registry, Sampler},
+        source::{Simulated, SystemSource},
+        test_util::Fixture,
+    };
+
+    use super::{CoolingDevice, HwmonSensor, Thermal, ThermalCollector, TripPoint};
+
+    /// An Allwinner H6 zone with a passive trip point bound to cpufreq.
+    pub fn cpu_zone(fixture: &Fixture, temperature: &str) {
+        fixture
+            .write("class/thermal/thermal_zone0/type", "cpu-thermal")
+            .write("class/thermal/thermal_zone0/temp", temperature)
+            .write("class/thermal/thermal_zone0/trip_point_0_type", "passive")
+            .write("class/thermal/thermal_zone0/trip_point_0_temp", "70000")
+            .write("class/thermal/thermal_zone0/trip_point_0_hyst", "2000")
+            .write("class/thermal/thermal_zone0/trip_point_1_type", "critical")
+            .write("class/thermal/thermal_zone0/trip_point_1_temp", "100000")
+            .write("class/thermal/thermal_zone0/cdev0/type", "cpufreq-cpu0")
+            .write("class/thermal/thermal_zone0/cdev0/cur_state", "2")
+            .write("class/thermal/thermal_zone0/cdev0/max_state", "5")
+            .write("class/thermal/thermal_zone0/cdev0_trip_point", "0");
+    }
+
+    #[test]
+    fn test_read_zones() {
+        let fixture = Fixture::new("thermal-zones");
+        cpu_zone(&fixture, "45250");
+        fixture
+            .write("class/thermal/thermal_zone10/type", "gpu-thermal")
+            .write("class/thermal/thermal_zone2/type", "ddr-thermal")
+            .write("class/thermal/thermal_zone2/temp", "40000");
+
+        let thermal = Thermal::read(fixture.path()).unwrap();
+        let names = thermal
+            .zones
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["thermal_zone0", "thermal_zone2", "thermal_zone10"]);
+
+        let cpu = &thermal.zones[0];
+        assert_eq!(cpu.kind, "cpu-thermal");
+        assert_eq!(cpu.temperature, Some(45.25));
+        assert_eq!(
+            cpu.trip_points,
+            [
+                TripPoint {
+                    kind: "passive".to_owned(),
+                    temperature: 70.0,
+                    hysteresis: Some(2.0),
+                },
+                TripPoint {
+                    kind: "critical".to_owned(),
+                    temperature: 100.0,
+                    hysteresis: None,
+                }
+            ]
+        );
+        assert_eq!(
+            cpu.cooling_devices,
+            [CoolingDevice {
+                kind: "cpufreq-cpu0".to_owned(),
+                current_state: 2,
+                max_state: 5,
+            }]
+        );
+        assert_eq!(thermal.zones[2].temperature, None);
+        assert!(thermal.sensors.is_empty());
+    }
+
+    #[test]
+    fn test_read_hwmon() {
+        let fixture = Fixture::new("thermal-hwmon");
+        fixture
+            .write("class/hwmon/hwmon0/name", "rk3588_thermal")
+            .write("class/hwmon/hwmon0/temp1_input", "51000")
+            .write("class/hwmon/hwmon0/temp1_crit", "115000")
+            .write("class/hwmon/hwmon0/temp2_input", "49500")
+            .write("class/hwmon/hwmon0/temp2_label", "gpu");
+
+        let thermal = Thermal::read(fixture.path()).unwrap();
+        assert_eq!(
+            thermal.sensors,
+            [
+                HwmonSensor {
+                    chip: "rk3588_thermal".to_owned(),
+                    label: "temp1".to_owned(),
+                    temperature: 51.0,
+                    max: None,
+                    critical: Some(115.0),
+                },
+                HwmonSensor {
+                    chip: "rk3588_thermal".to_owned(),
+                    label: "gpu".to_owned(),
+                    temperature: 49.5,
+                    max: None,
+                    critical: None,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulation_hides_host_zones() {
+        let fixture = Fixture::new("thermal-simulated");
+        cpu_zone(&fixture, "45250");
+        let sampler = |source: Box<dyn SystemSource>| {
+            let mut registry = Registry::new(HashMap::new(), Duration::ZERO);
+            registry.register(ThermalCollector::new(fixture.path()));
+            Sampler::new(source, registry)
+        };
+
+        let mut host = sampler(Box::new(System::new()));
+        assert!(host.sample(|_| true).pack.thermal.is_some());
+        let mut simulated = sampler(Box::new(Simulated::new(Default::default())));
+        assert!(simulated.sample(|_| true).pack.thermal.is_none());
+    }
+
+    #[test]
+    fn test_empty_root_fails() {
+        let fixture = Fixture::new("thermal-empty");
+
+        assert!(Thermal::read(fixture.path()).is_err());
+    }
+}
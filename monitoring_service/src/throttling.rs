@@ -0,0 +1,366 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use common::monitoring::{self as proto, CpuPolicy, Pack};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+    thermal::{indices, read_number, read_string, ThermalZone},
+};
+
+/// How many finished or ongoing events are reported.
+const RECENT_EVENTS: usize = 16;
+
+/// A cpufreq policy, frequencies are in MHz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub name: String,
+    pub cpus: Vec<u32>,
+    pub governor: String,
+    pub current_frequency: u64,
+    pub hardware_max_frequency: u64,
+    pub scaling_max_frequency: u64,
+    pub available_frequencies: Vec<u64>,
+    pub throttled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThrottleEvent {
+    pub start: SystemTime,
+    pub end: Option<SystemTime>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Throttling {
+    pub policies: Vec<Policy>,
+    pub throttled: bool,
+    pub throttled_time: Duration,
+    pub events: u64,
+    pub recent: Vec<ThrottleEvent>,
+}
+
+fn read_megahertz(path: &Path) -> std::io::Result<u64> {
+    Ok(read_number::<u64>(path)? / 1000)
+}
+
+impl Policy {
+    fn read_all(sysfs_root: &Path) -> std::io::Result<Vec<Self>> {
+        let class = sysfs_root.join("devices/system/cpu/cpufreq");
+
+        indices(&class, "policy", "")?
+            .into_iter()
+            .map(|i| {
+                let name = format!("policy{i}");
+                let policy = class.join(&name);
+                let numbers = |x: &str| -> Vec<u64> {
+                    read_string(&policy.join(x))
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .filter_map(|x| x.parse().ok())
+                        .collect()
+                };
+
+                Ok(Policy {
+                    cpus: numbers("related_cpus")
+                        .into_iter()
+                        .map(|x| x as u32)
+                        .collect(),
+                    governor: read_string(&policy.join("scaling_governor"))?,
+                    current_frequency: read_megahertz(&policy.join("scaling_cur_freq"))?,
+                    hardware_max_frequency: read_megahertz(&policy.join("cpuinfo_max_freq"))?,
+                    scaling_max_frequency: read_megahertz(&policy.join("scaling_max_freq"))?,
+                    available_frequencies: numbers("scaling_available_frequencies")
+                        .into_iter()
+                        .map(|x| x / 1000)
+                        .collect(),
+                    throttled: false,
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    /// Whether something keeps the policy below the hardware maximum. Idle cores of an
+    /// on-demand governor run slower too, so only the performance governor is expected to
+    /// stay at the limit.
+    fn limited(&self) -> bool {
+        self.scaling_max_frequency < self.hardware_max_frequency
+            || (self.governor == "performance"
+                && self.current_frequency < self.scaling_max_frequency)
+    }
+}
+
+/// Describes why the CPU is being cooled: a zone above a passive trip point or a busy
+/// cpufreq cooling device.
+fn thermal_pressure(zones: &[ThermalZone]) -> Option<String> {
+    zones.iter().find_map(|zone| {
+        let trip = zone.temperature.and_then(|t| {
+            let trip = zone
+                .trip_points
+                .iter()
+                .filter(|x| x.kind == "passive")
+                .find(|x| t >= x.temperature)?;
+            Some(format!(
+                "{} at {t:.1} °C reached the passive trip point at {:.1} °C",
+                zone.kind, trip.temperature
+            ))
+        });
+        let cooling = || {
+            let device = zone
+                .cooling_devices
+                .iter()
+                .find(|x| x.kind.contains("cpufreq") && x.current_state > 0)?;
+            Some(format!(
+                "{} set {} to state {} of {}",
+                zone.kind, device.kind, device.current_state, device.max_state
+            ))
+        };
+
+        trip.or_else(cooling)
+    })
+}
+
+#[derive(Debug, Default)]
+struct State {
+    last: Option<Instant>,
+    throttled_time: Duration,
+    events: u64,
+    recent: VecDeque<ThrottleEvent>,
+}
+
+impl State {
+    fn throttled(&self) -> bool {
+        self.recent.back().is_some_and(|x| x.end.is_none())
+    }
+
+    /// Time since the previous update is attributed to the state seen back then.
+    fn update(&mut self, now: Instant, at: SystemTime, reason: Option<String>) {
+        if let Some(last) = self.last.filter(|_| self.throttled()) {
+            self.throttled_time += now.saturating_duration_since(last);
+        }
+        self.last = Some(now);
+
+        match (self.throttled(), reason) {
+            (false, Some(reason)) => {
+                self.events += 1;
+                if self.recent.len() == RECENT_EVENTS {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back(ThrottleEvent {
+                    start: at,
+                    end: None,
+                    reason,
+                });
+            }
+            (true, None) => {
+                if let Some(event) = self.recent.back_mut() {
+                    event.end = Some(at);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Detects thermal throttling from cpufreq policies and thermal-zone trip points.
+#[derive(Debug)]
+pub struct ThrottlingCollector {
+    sysfs_root: PathBuf,
+    state: State,
+}
+
+impl ThrottlingCollector {
+    pub fn new(sysfs_root: impl Into<PathBuf>) -> Self {
+        ThrottlingCollector {
+            sysfs_root: sysfs_root.into(),
+            state: State::default(),
+        }
+    }
+}
+
+impl Collector for ThrottlingCollector {
+    type Output = Throttling;
+
+    fn name(&self) -> &'static str {
+        "throttling"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        let mut policies = Policy::read_all(&self.sysfs_root)?;
+        let pressure = thermal_pressure(&ThermalZone::read_all(&self.sysfs_root)?);
+
+        if pressure.is_some() {
+            policies.iter_mut().for_each(|x| x.throttled = x.limited());
+        }
+        let limited = policies
+            .iter()
+            .filter(|x| x.throttled)
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        let reason = pressure
+            .filter(|_| !limited.is_empty())
+            .map(|x| format!("{} limited: {x}", limited.join(", ")));
+
+        self.state.update(Instant::now(), SystemTime::now(), reason);
+
+        Ok(Throttling {
+            policies,
+            throttled: self.state.throttled(),
+            throttled_time: self.state.throttled_time,
+            events: self.state.events,
+            recent: self.state.recent.iter().cloned().collect(),
+        })
+    }
+}
+
+impl Section for Throttling {
+    const FIELD: &'static str = "cpu";
+
+    fn store(self, pack: &mut Pack) {
+        let cpu = pack.cpu.get_or_insert_with(Default::default);
+        cpu.policies = self
+            .policies
+            .into_iter()
+            .map(|x| CpuPolicy {
+                name: x.name,
+                cpus: x.cpus,
+                governor: x.governor,
+                current_frequency: x.current_frequency,
+                hardware_max_frequency: x.hardware_max_frequency,
+                scaling_max_frequency: x.scaling_max_frequency,
+                available_frequencies: x.available_frequencies,
+                throttled: x.throttled,
+            })
+            .collect();
+        cpu.throttled = self.throttled;
+        cpu.throttled_ms = self.throttled_time.as_millis() as u64;
+        cpu.throttle_events = self.events;
+        cpu.recent_throttle_events = self
+            .recent
+            .into_iter()
+            .map(|x| proto::ThrottleEvent {
+                start: Some(x.start.into()),
+                end: x.end.map(Into::into),
+                reason: x.reason,
+            })
+            .collect();
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.cpu = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, SystemTime};
+
+    use sysinfo::{System, SystemExt};
+
+    use crate::{collector::Collector, test_util::Fixture, thermal::tests::cpu_zone};
+
+    use super::{State, ThrottlingCollector};
+
+    fn policy(fixture: &Fixture, governor: &str, current: &str, scaling_max: &str) {
+        fixture
+            .write("devices/system/cpu/cpufreq/policy0/related_cpus", "0 1 2 3")
+            .write(
+                "devices/system/cpu/cpufreq/policy0/scaling_governor",
+                governor,
+            )
+            .write(
+                "devices/system/cpu/cpufreq/policy0/scaling_cur_freq",
+                current,
+            )
+            .write(
+                "devices/system/cpu/cpufreq/policy0/cpuinfo_max_freq",
+                "1800000",
+            )
+            .write(
+                "devices/system/cpu/cpufreq/policy0/scaling_max_freq",
+                scaling_max,
+            )
+            .write(
+                "devices/system/cpu/cpufreq/policy0/scaling_available_frequencies",
+                "480000 1200000 1800000",
+            );
+    }
+
+    #[test]
+    fn test_capped_policy_of_hot_zone_is_throttled() {
+        let fixture = Fixture::new("throttling-hot");
+        policy(&fixture, "schedutil", "1200000", "1200000");
+        cpu_zone(&fixture, "72000");
+
+        let throttling = ThrottlingCollector::new(fixture.path())
+            .collect(&mut System::new())
+            .unwrap();
+        let policy = &throttling.policies[0];
+        assert_eq!(policy.cpus, [0, 1, 2, 3]);
+        assert_eq!(policy.available_frequencies, [480, 1200, 1800]);
+        assert_eq!(policy.scaling_max_frequency, 1200);
+        assert!(policy.throttled);
+        assert!(throttling.throttled);
+        assert_eq!(throttling.events, 1);
+        assert!(throttling.recent[0].reason.starts_with("policy0 limited"));
+    }
+
+    #[test]
+    fn test_cool_zone_is_not_throttled() {
+        let fixture = Fixture::new("throttling-cool");
+        policy(&fixture, "schedutil", "1200000", "1200000");
+        cpu_zone(&fixture, "45000");
+        fixture.write("class/thermal/thermal_zone0/cdev0/cur_state", "0");
+
+        let throttling = ThrottlingCollector::new(fixture.path())
+            .collect(&mut System::new())
+            .unwrap();
+        assert!(!throttling.policies[0].throttled);
+        assert!(!throttling.throttled);
+        assert_eq!(throttling.events, 0);
+    }
+
+    #[test]
+    fn test_idle_cores_are_not_throttled() {
+        let fixture = Fixture::new("throttling-idle");
+        policy(&fixture, "ondemand", "480000", "1800000");
+        cpu_zone(&fixture, "72000");
+
+        let throttling = ThrottlingCollector::new(fixture.path())
+            .collect(&mut System::new())
+            .unwrap();
+        assert!(!throttling.throttled);
+    }
+
+    #[test]
+    fn test_events_and_time() {
+        let mut state = State::default();
+        let start = Instant::now();
+        let at = SystemTime::UNIX_EPOCH;
+        let reason = || Some("hot".to_owned());
+
+        state.update(start, at, None);
+        state.update(start + Duration::from_secs(1), at, reason());
+        state.update(start + Duration::from_secs(3), at, reason());
+        state.update(start + Duration::from_secs(4), at, None);
+        state.update(start + Duration::from_secs(5), at, reason());
+
+        assert_eq!(state.events, 2);
+        assert_eq!(state.throttled_time, Duration::from_secs(3));
+        assert!(state.recent[0].end.is_some());
+        assert!(state.throttled());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::monitoring::{CpuCore, Pack};
+
+use crate::{
+    collector::{Collector, Section},
+    source::SystemSource,
+    thermal::{indices, read_number, read_string},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Core {
+    pub id: u32,
+    pub cluster_id: u32,
+    pub package_id: u32,
+    pub core_id: u32,
+    pub online: bool,
+    /// In MHz, zero when cpufreq is not available.
+    pub max_frequency: u64,
+    pub part: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology(pub Vec<Core>);
+
+/// Names of ARM cores by the `CPU part` of `/proc/cpuinfo`.
+const ARM_PARTS: [(&str, &str); 14] = [
+    ("0xd03", "Cortex-A53"),
+    ("0xd04", "Cortex-A35"),
+    ("0xd05", "Cortex-A55"),
+    ("0xd07", "Cortex-A57"),
+    ("0xd08", "Cortex-A72"),
+    ("0xd09", "Cortex-A73"),
+    ("0xd0a", "Cortex-A75"),
+    ("0xd0b", "Cortex-A76"),
+    ("0xd0d", "Cortex-A77"),
+    ("0xd41", "Cortex-A78"),
+    ("0xd44", "Cortex-X1"),
+    ("0xd46", "Cortex-A510"),
+    ("0xd47", "Cortex-A710"),
+    ("0xd48", "Cortex-X2"),
+];
+
+/// Core names by logical CPU, from `CPU part` on ARM and `model name` elsewhere.
+fn parts(cpuinfo: &str) -> HashMap<u32, String> {
+    let mut parts = HashMap::new();
+
+    for block in cpuinfo.split("\n\n") {
+        let fields = block
+            .lines()
+            .filter_map(|x| x.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect::<HashMap<_, _>>();
+        let Some(id) = fields.get("processor").and_then(|x| x.parse().ok()) else {
+            continue;
+        };
+        let part = match (fields.get("CPU part"), fields.get("model name")) {
+            (Some(&part), _) => ARM_PARTS.iter().find(|x| x.0 == part).map_or(part, |x| x.1),
+            (None, Some(&model)) => model,
+            (None, None) => continue,
+        };
+        parts.insert(id, part.to_owned());
+    }
+    parts
+}
+
+impl Topology {
+    fn read(sysfs_root: &Path, procfs_root: &Path) -> std::io::Result<Self> {
+        let class = sysfs_root.join("devices/system/cpu");
+        let parts =
+            parts(&std::fs::read_to_string(procfs_root.join("cpuinfo")).unwrap_or_default());
+
+        let cores = indices(&class, "cpu", "")?
+            .into_iter()
+            .map(|id| {
+                let cpu = class.join(format!("cpu{id}"));
+                let topology = |x: &str| read_number::<u32>(&cpu.join("topology").join(x));
+                // Kernels before 5.16 have no cluster_id, but big.LITTLE clusters still
+                // have separate cpufreq policies
+                let policy = || {
+                    read_string(&cpu.join("cpufreq/related_cpus"))
+                        .ok()?
+                        .split_whitespace()
+                        .next()?
+                        .parse()
+                        .ok()
+                };
+                let package_id = topology("physical_package_id").unwrap_or_default();
+
+                Core {
+                    id,
+                    cluster_id: topology("cluster_id")
+                        .ok()
+                        .or_else(policy)
+                        .unwrap_or(package_id),
+                    package_id,
+                    core_id: topology("core_id").unwrap_or(id),
+                    // The boot CPU usually cannot be taken offline and has no `online`
+                    online: read_number::<u8>(&cpu.join("online")).map_or(true, |x| x == 1),
+                    max_frequency: read_number::<u64>(&cpu.join("cpufreq/cpuinfo_max_freq"))
+                        .map_or(0, |x| x / 1000),
+                    part: parts.get(&id).cloned().unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        Ok(Topology(cores))
+    }
+}
+
+/// Reads per-core metadata from `devices/system/cpu` and `cpuinfo`.
+#[derive(Debug)]
+pub struct TopologyCollector {
+    sysfs_root: PathBuf,
+    procfs_root: PathBuf,
+}
+
+impl TopologyCollector {
+    pub fn new(sysfs_root: impl Into<PathBuf>, procfs_root: impl Into<PathBuf>) -> Self {
+        TopologyCollector {
+            sysfs_root: sysfs_root.into(),
+            procfs_root: procfs_root.into(),
+        }
+    }
+}
+
+impl Collector for TopologyCollector {
+    type Output = Topology;
+
+    fn name(&self) -> &'static str {
+        "topology"
+    }
+
+    /// Only hotplug changes the topology.
+    fn interval(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        Topology::read(&self.sysfs_root, &self.procfs_root)
+    }
+}
+
+impl Section for Topology {
+    const FIELD: &'static str = "cpu";
+
+    fn store(self, pack: &mut Pack) {
+        pack.cpu.get_or_insert_with(Default::default).cores = self
+            .0
+            .into_iter()
+            .map(|x| CpuCore {
+                id: x.id,
+                cluster_id: x.cluster_id,
+                package_id: x.package_id,
+                core_id: x.core_id,
+                online: x.online,
+                max_frequency: x.max_frequency,
+                part: x.part,
+            })
+            .collect();
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.cpu = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::Fixture;
+
+    use super::{parts, Topology};
+
+    /// Two A55 and two A76 cores of an RK3588 kernel without `cluster_id`, the last offline.
+    fn rk3588(fixture: &Fixture) {
+        let mut cpuinfo = String::new();
+        for (id, policy, part, max) in [
+            (0, 0, "0xd05", "1800000"),
+            (1, 0, "0xd05", "1800000"),
+            (4, 4, "0xd0b", "2400000"),
+            (5, 4, "0xd0b", "2400000"),
+        ] {
+            let cpu = format!("sys/devices/system/cpu/cpu{id}");
+            fixture
+                .write(&format!("{cpu}/topology/physical_package_id"), "0")
+                .write(&format!("{cpu}/topology/core_id"), &(id % 4).to_string())
+                .write(
+                    &format!("{cpu}/cpufreq/related_cpus"),
+                    &format!("{policy} {}", policy + 1),
+                )
+                .write(&format!("{cpu}/cpufreq/cpuinfo_max_freq"), max);
+            if id != 0 {
+                fixture.write(&format!("{cpu}/online"), if id == 5 { "0" } else { "1" });
+            }
+            cpuinfo +=
+                &format!("processor\t: {id}\nCPU implementer\t: 0x41\nCPU part\t: {part}\n\n");
+        }
+        fixture.write("proc/cpuinfo", &cpuinfo);
+    }
+
+    #[test]
+    fn test_clusters_from_cpufreq_policies() {
+        let fixture = Fixture::new("topology-rk3588");
+        rk3588(&fixture);
+
+        let Topology(cores) =
+            Topology::read(&fixture.path().join("sys"), &fixture.path().join("proc")).unwrap();
+        let summary = cores
+            .iter()
+            .map(|x| {
+                (
+                    x.id,
+                    x.cluster_id,
+                    x.online,
+                    x.max_frequency,
+                    x.part.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (0, 0, true, 1800, "Cortex-A55"),
+                (1, 0, true, 1800, "Cortex-A55"),
+                (4, 4, true, 2400, "Cortex-A76"),
+                (5, 4, false, 2400, "Cortex-A76"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cluster_id_is_preferred() {
+        let fixture = Fixture::new("topology-cluster-id");
+        rk3588(&fixture);
+        fixture.write("sys/devices/system/cpu/cpu4/topology/cluster_id", "1");
+
+        let Topology(cores) =
+            Topology::read(&fixture.path().join("sys"), &fixture.path().join("proc")).unwrap();
+        assert_eq!(cores[2].cluster_id, 1);
+        assert_eq!(cores[3].cluster_id, 4);
+    }
+
+    #[test]
+    fn test_x86_model_names() {
+        let parts = parts(concat!(
+            "processor\t: 0\nmodel name\t: Intel(R) Core(TM) i5\n\n",
+            "processor\t: 1\nCPU part\t: 0xfff\n",
+        ));
+
+        assert_eq!(parts[&0], "Intel(R) Core(TM) i5");
+        assert_eq!(parts[&1], "0xfff");
+    }
+}
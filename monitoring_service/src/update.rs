@@ -0,0 +1,596 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fslock::LockFile;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tonic::transport::Endpoint;
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+
+use crate::{
+    auth::random_token,
+    listen::ListenAddr,
+    signals::{self, Signal},
+};
+
+/// Set for the watchdog, which the previous build is started as.
+const ROLLBACK_ENV: &str = "MONITORING_SERVICE_ROLLBACK";
+/// Set for the new build, which reports the value as healthy once it serves. Commands the
+/// service starts must not inherit it.
+pub const UPDATE_ENV: &str = "MONITORING_SERVICE_UPDATE";
+/// How long `--version` of a new build may take.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Lets the response to the upload reach the client before the restart.
+const RESPONSE_GRACE: Duration = Duration::from_millis(500);
+const HEALTH_POLL: Duration = Duration::from_secs(1);
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// `[update]` section of the config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// Larger uploads are refused.
+    pub max_size_bytes: u64,
+    /// How long the new build has to report healthy before the previous one is restored.
+    pub health_timeout_secs: u64,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        UpdateConfig {
+            max_size_bytes: 256 << 20,
+            health_timeout_secs: 30,
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Replaces this process, returns only on failure.
+#[cfg(unix)]
+fn exec(command: &mut Command) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+
+    command.exec()
+}
+
+#[cfg(not(unix))]
+fn exec(_command: &mut Command) -> std::io::Error {
+    std::io::Error::new(ErrorKind::Unsupported, "restarting in place needs unix")
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Name the new build reports as serving through `grpc.health.v1.Health`.
+pub fn health_name(nonce: &str) -> String {
+    format!("monitor.Update/{nonce}")
+}
+
+/// Nonce to report as healthy when the service was started by an update.
+pub fn nonce() -> Option<String> {
+    std::env::var(UPDATE_ENV).ok()
+}
+
+/// Where the watchdog checks the health of the new build. Unix sockets are not supported.
+pub fn health_uri(listen: &[ListenAddr]) -> Option<String> {
+    listen.iter().find_map(|x| match x {
+        ListenAddr::Tcp { host, port } => {
+            let host = match host.as_str() {
+                "0.0.0.0" => "127.0.0.1",
+                "::" | "[::]" => "[::1]",
+                x => x,
+            };
+            Some(match host.contains(':') && !host.starts_with('[') {
+                true => format!("http://[{host}]:{port}"),
+                false => format!("http://{host}:{port}"),
+            })
+        }
+        #[cfg(unix)]
+        ListenAddr::Unix(_) => None,
+    })
+}
+
+/// Whether the service runs as a systemd unit, which starts it again when it fails.
+pub fn supervised() -> bool {
+    std::env::var_os("INVOCATION_ID").is_some()
+}
+
+/// A binary being received next to the running one, removed again when the upload is dropped
+/// before it is finished.
+#[derive(Debug)]
+pub struct Upload {
+    file: File,
+    hasher: Sha256,
+    max_size: u64,
+    staged: Staged,
+}
+
+impl Upload {
+    pub fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.staged.size += chunk.len() as u64;
+        if self.staged.size > self.max_size {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("the binary is larger than {} bytes", self.max_size),
+            ));
+        }
+        self.hasher.update(chunk);
+        self.file.write_all(chunk)
+    }
+
+    /// Checks the upload against the SHA-256 the client sent and makes it executable. The staged
+    /// file is removed when anything is wrong with it.
+    pub fn finish(mut self, sha256: &str) -> std::io::Result<Staged> {
+        self.check(sha256)?;
+        Ok(self.staged)
+    }
+
+    fn check(&mut self, sha256: &str) -> std::io::Result<()> {
+        self.file.sync_all()?;
+        let actual = hex::encode(self.hasher.finalize_reset());
+        if !actual.eq_ignore_ascii_case(sha256.trim()) {
+            return Err(invalid(format!(
+                "the SHA-256 of the upload is {actual}, not {sha256}"
+            )));
+        }
+
+        let mut magic = [0; 4];
+        let mut file = File::open(&self.staged.path)?;
+        if file.read_exact(&mut magic).is_err() || magic != ELF_MAGIC {
+            return Err(invalid("the upload is not an ELF executable".to_owned()));
+        }
+        make_executable(&self.staged.path)
+    }
+}
+
+/// A verified upload, waiting to replace the running binary. The file is removed when this is
+/// dropped without being installed.
+#[derive(Debug)]
+pub struct Staged {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Keeps other uploads out until the restart.
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Staged {
+    pub async fn verify(&self) -> std::io::Result<String> {
+        verify(&self.path).await
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        // Gone already once installed
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Runs `--version` of the new build, which fails for builds for another architecture.
+pub async fn verify(staged: &Path) -> std::io::Result<String> {
+    let output = tokio::process::Command::new(staged)
+        .arg("--version")
+        .env_remove(UPDATE_ENV)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(VERIFY_TIMEOUT, output)
+        .await
+        .map_err(|_| invalid("the new build did not print its version in time".to_owned()))??;
+
+    if !output.status.success() {
+        return Err(invalid(format!(
+            "the new build cannot run here, --version {}",
+            output.status
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or_default().trim().to_owned())
+}
+
+/// Puts `staged` in place of `exe`, keeping the running binary as `<exe>.previous`.
+pub fn install(staged: &Path, exe: &Path) -> std::io::Result<PathBuf> {
+    let previous = with_suffix(exe, ".previous");
+    match std::fs::remove_file(&previous) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    std::fs::hard_link(exe, &previous)?;
+    std::fs::rename(staged, exe)?;
+
+    Ok(previous)
+}
+
+/// What the watchdog needs to restore the previous build.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rollback {
+    pub pid: u32,
+    pub exe: PathBuf,
+    pub previous: PathBuf,
+    pub args: Vec<OsString>,
+    pub health_uri: String,
+    pub nonce: String,
+    pub timeout_secs: u64,
+    /// Set once the watchdog is no longer a child of the service.
+    pub detached: bool,
+    /// Set when systemd restarts the service once it fails, see [`supervised`].
+    pub supervised: bool,
+}
+
+impl Rollback {
+    /// Set when the process was started as a watchdog.
+    pub fn from_env() -> Option<Result<Self, serde_json::Error>> {
+        let plan = std::env::var(ROLLBACK_ENV).ok()?;
+        Some(serde_json::from_str(&plan))
+    }
+
+    fn spawn(&self, program: &Path) -> std::io::Result<tokio::process::Child> {
+        let plan = serde_json::to_string(self).map_err(std::io::Error::other)?;
+
+        tokio::process::Command::new(program)
+            .env(ROLLBACK_ENV, plan)
+            .env_remove(UPDATE_ENV)
+            .stdin(Stdio::null())
+            .spawn()
+    }
+
+    /// Starts `program` as the watchdog and waits until it has detached from the service.
+    async fn start(&self, program: &Path) -> std::io::Result<()> {
+        let status = self.spawn(program)?.wait().await?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!(
+                "the watchdog failed with {status}"
+            )));
+        }
+        Ok(())
+    }
+
+    async fn healthy(&self) -> bool {
+        let check = async {
+            let endpoint = Endpoint::from_shared(self.health_uri.clone()).ok()?;
+            let mut client = HealthClient::new(endpoint.connect().await.ok()?);
+            let request = HealthCheckRequest {
+                service: health_name(&self.nonce),
+            };
+            let response = client.check(request).await.ok()?.into_inner();
+            Some(response.status == ServingStatus::Serving as i32)
+        };
+
+        matches!(
+            tokio::time::timeout(HEALTH_POLL, check).await,
+            Ok(Some(true))
+        )
+    }
+
+    /// Waits for the new build to report healthy and restores the previous one otherwise.
+    /// A supervised service is killed and left to the supervisor, which starts the restored
+    /// build, and this process fails. Otherwise this process becomes the service again.
+    pub async fn watch(mut self) -> std::io::Result<()> {
+        if !self.detached {
+            // The service would have to reap this process otherwise
+            self.detached = true;
+            self.spawn(&std::env::current_exe()?)?;
+            return Ok(());
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(self.timeout_secs);
+        while Instant::now() < deadline {
+            if self.healthy().await {
+                eprintln!("The new build of PID {} is healthy", self.pid);
+                return Ok(());
+            }
+            tokio::time::sleep(HEALTH_POLL).await;
+        }
+
+        eprintln!(
+            "The new build of PID {} did not become healthy in {} seconds, rolling back",
+            self.pid, self.timeout_secs
+        );
+        std::fs::rename(&self.previous, &self.exe)?;
+        let _ = signals::send(self.pid, Signal::Kill);
+        if self.supervised {
+            // Starting the service here would race the supervisor restarting it
+            return Err(std::io::Error::other(
+                "rolled back, the supervisor restarts the previous build",
+            ));
+        }
+        while Path::new(&format!("/proc/{}", self.pid)).exists() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Err(exec(
+            Command::new(&self.exe)
+                .args(&self.args)
+                .env_remove(ROLLBACK_ENV)
+                .env_remove(UPDATE_ENV),
+        ))
+    }
+}
+
+/// Replaces the running service with uploaded builds.
+#[derive(Debug)]
+pub struct Updater {
+    config: UpdateConfig,
+    /// The running binary and how it was started.
+    exe: PathBuf,
+    args: Vec<OsString>,
+    health_uri: Option<String>,
+    /// Released right before the new build starts, which takes it again.
+    lock: Arc<Mutex<LockFile>>,
+    running: Arc<Semaphore>,
+}
+
+impl Updater {
+    pub fn new(
+        config: UpdateConfig,
+        listen: &[ListenAddr],
+        lock: Arc<Mutex<LockFile>>,
+    ) -> std::io::Result<Self> {
+        Ok(Updater {
+            config,
+            exe: std::env::current_exe()?,
+            args: std::env::args_os().skip(1).collect(),
+            health_uri: health_uri(listen),
+            lock,
+            running: Arc::new(Semaphore::new(1)),
+        })
+    }
+
+    pub fn health_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.health_timeout_secs)
+    }
+
+    /// Fails with `WouldBlock` while another upload is in progress.
+    pub fn upload(&self) -> std::io::Result<Upload> {
+        if cfg!(not(unix)) {
+            return Err(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "updates need a unix system",
+            ));
+        }
+        if self.health_uri.is_none() {
+            return Err(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "updates need a TCP listener to check the new build",
+            ));
+        }
+        let permit = self.running.clone().try_acquire_owned().map_err(|_| {
+            std::io::Error::new(ErrorKind::WouldBlock, "another upload is in progress")
+        })?;
+        let path = with_suffix(&self.exe, ".staged");
+
+        Ok(Upload {
+            file: File::create(&path)?,
+            hasher: Sha256::new(),
+            max_size: self.config.max_size_bytes,
+            staged: Staged {
+                path,
+                size: 0,
+                _permit: permit,
+            },
+        })
+    }
+
+    /// Installs the staged build and restarts into it shortly after, leaving a watchdog behind
+    /// to roll back if it does not become healthy.
+    pub fn restart(&self, staged: Staged) -> std::io::Result<()> {
+        let previous = install(&staged.path, &self.exe)?;
+        let plan = Rollback {
+            pid: std::process::id(),
+            exe: self.exe.clone(),
+            previous,
+            args: self.args.clone(),
+            health_uri: self.health_uri.clone().unwrap_or_default(),
+            nonce: random_token()?,
+            timeout_secs: self.config.health_timeout_secs,
+            detached: false,
+            supervised: supervised(),
+        };
+        let lock = self.lock.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(RESPONSE_GRACE).await;
+            let _ = lock.lock().await.unlock();
+
+            // Without a watchdog nothing would roll back a broken build
+            let error = match plan.start(&plan.previous).await {
+                Ok(()) => exec(
+                    Command::new(&plan.exe)
+                        .args(&plan.args)
+                        .env(UPDATE_ENV, &plan.nonce),
+                ),
+                Err(e) => e,
+            };
+            eprintln!("Cannot restart into the new build, keeping this one: {error}");
+            if let Err(e) = std::fs::rename(&plan.previous, &plan.exe) {
+                eprintln!("Cannot restore the previous build: {e}");
+            }
+            if let Err(e) = lock.lock().await.lock_with_pid() {
+                eprintln!("Cannot lock the service again: {e}");
+            }
+            drop(staged);
+        });
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{io::ErrorKind, os::unix::fs::PermissionsExt, sync::Arc};
+
+    use sha2::{Digest, Sha256};
+    use tokio::sync::Semaphore;
+
+    use crate::{listen::ListenAddr, test_util::Fixture};
+
+    use super::{health_uri, install, verify, Rollback, Staged, Upload};
+
+    fn upload(fixture: &Fixture, max_size: u64) -> Upload {
+        let path = fixture.path().join("monitoring_service.staged");
+
+        Upload {
+            file: std::fs::File::create(&path).unwrap(),
+            hasher: Sha256::new(),
+            max_size,
+            staged: Staged {
+                path,
+                size: 0,
+                _permit: Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_uploads_are_checked() {
+        let fixture = Fixture::new("update-upload");
+        std::fs::create_dir_all(fixture.path()).unwrap();
+        let binary = b"\x7fELF\x01\x01\x01 rest of the build";
+        let sha256 = hex::encode(Sha256::digest(binary));
+
+        let mut received = upload(&fixture, 1024);
+        for chunk in binary.chunks(8) {
+            received.write(chunk).unwrap();
+        }
+        let staged = received.finish(&sha256.to_uppercase()).unwrap();
+        assert_eq!(std::fs::read(&staged.path).unwrap(), binary);
+        assert_eq!(staged.size, binary.len() as u64);
+        let path = staged.path.clone();
+        drop(staged);
+        assert!(!path.exists());
+
+        let mut aborted = upload(&fixture, 1024);
+        aborted.write(&binary[..8]).unwrap();
+        assert!(path.exists());
+        drop(aborted);
+        assert!(!path.exists());
+
+        let mut corrupted = upload(&fixture, 1024);
+        corrupted.write(&binary[1..]).unwrap();
+        let error = corrupted.finish(&sha256).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(!path.exists());
+
+        let script = b"#!/bin/sh\n";
+        let mut received = upload(&fixture, 1024);
+        received.write(script).unwrap();
+        let error = received
+            .finish(&hex::encode(Sha256::digest(script)))
+            .unwrap_err();
+        assert!(error.to_string().contains("not an ELF"));
+
+        let mut large = upload(&fixture, 16);
+        assert_eq!(
+            large.write(binary).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[tokio::test]
+    async fn test_install_keeps_the_previous_build() {
+        let fixture = Fixture::new("update-install");
+        fixture
+            .write(
+                "monitoring_service",
+                "#!/bin/sh\necho monitoring_service 0.1.0",
+            )
+            .write(
+                "monitoring_service.staged",
+                "#!/bin/sh\necho monitoring_service 0.2.0",
+            )
+            .write("monitoring_service.previous", "outdated");
+        let exe = fixture.path().join("monitoring_service");
+        let staged = fixture.path().join("monitoring_service.staged");
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(verify(&staged).await.unwrap(), "monitoring_service 0.2.0");
+        let previous = install(&staged, &exe).unwrap();
+        assert!(std::fs::read_to_string(&exe).unwrap().contains("0.2.0"));
+        assert!(std::fs::read_to_string(previous).unwrap().contains("0.1.0"));
+        assert!(!staged.exists());
+
+        std::fs::write(&staged, "#!/bin/sh\nexit 1").unwrap();
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(verify(&staged).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failing_watchdog_is_reported() {
+        let fixture = Fixture::new("update-watchdog");
+        fixture
+            .write("failing", "#!/bin/sh\nexit 3")
+            .write("detaching", "#!/bin/sh\nexit 0");
+        for name in ["failing", "detaching"] {
+            let path = fixture.path().join(name);
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let plan = Rollback {
+            pid: std::process::id(),
+            exe: fixture.path().join("monitoring_service"),
+            previous: fixture.path().join("monitoring_service.previous"),
+            args: vec![],
+            health_uri: "http://127.0.0.1:50501".to_owned(),
+            nonce: "nonce".to_owned(),
+            timeout_secs: 1,
+            detached: false,
+            supervised: false,
+        };
+
+        let error = plan
+            .start(&fixture.path().join("failing"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("exit status: 3"));
+        assert!(plan.start(&fixture.path().join("detaching")).await.is_ok());
+    }
+
+    #[test]
+    fn test_health_uri() {
+        let tcp = |host: &str| ListenAddr::Tcp {
+            host: host.to_owned(),
+            port: 50501,
+        };
+        let unix = ListenAddr::Unix("/run/monitor.sock".into());
+
+        assert_eq!(
+            health_uri(&[unix.clone(), tcp("0.0.0.0")]).as_deref(),
+            Some("http://127.0.0.1:50501")
+        );
+        assert_eq!(
+            health_uri(&[tcp("::")]).as_deref(),
+            Some("http://[::1]:50501")
+        );
+        assert_eq!(
+            health_uri(&[tcp("orangepi")]).as_deref(),
+            Some("http://orangepi:50501")
+        );
+        assert_eq!(health_uri(&[unix]), None);
+    }
+}
@@ -0,0 +1,493 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use common::monitoring::{self as proto, Pack, WatchedResponse, WatchedSample};
+
+use crate::{
+    collector::{Collector, Section},
+    events::{Events, Severity},
+    processes::status_field,
+    source::{ProcessInfo, SystemSource},
+    thermal::read_number,
+};
+
+/// Samples kept for every watched process, ten minutes at the default interval, unless
+/// `[retention]` says otherwise.
+pub const KEPT_SAMPLES: usize = 600;
+
+/// A `[[watch]]` entry of the config. Exactly one of the matchers has to be set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    pub name: String,
+    /// Exact process name, as in `comm`.
+    pub process: Option<String>,
+    /// Regular expression searched in the command line joined with spaces.
+    pub cmdline: Option<String>,
+    /// File with the PID, as written by most daemons.
+    pub pidfile: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Name(String),
+    Cmdline(Regex),
+    Pidfile(PathBuf),
+}
+
+impl Matcher {
+    fn new(config: &WatchConfig) -> Result<Self, Box<dyn Error>> {
+        match (&config.process, &config.cmdline, &config.pidfile) {
+            (Some(name), None, None) => Ok(Matcher::Name(name.clone())),
+            (None, Some(cmdline), None) => Ok(Matcher::Cmdline(Regex::new(cmdline)?)),
+            (None, None, Some(path)) => Ok(Matcher::Pidfile(path.clone())),
+            _ => Err(format!(
+                "watch {}: exactly one of process, cmdline and pidfile is required",
+                config.name
+            )
+            .into()),
+        }
+    }
+
+    fn matches<'a>(&self, processes: &'a [ProcessInfo]) -> Vec<&'a ProcessInfo> {
+        let pid = match self {
+            Matcher::Pidfile(path) => match read_number::<u32>(path) {
+                Ok(pid) => Some(pid),
+                Err(_) => return vec![],
+            },
+            _ => None,
+        };
+
+        processes
+            .iter()
+            .filter(|x| match self {
+                Matcher::Name(name) => &x.name == name,
+                Matcher::Cmdline(regex) => regex.is_match(&x.cmd.join(" ")),
+                Matcher::Pidfile(_) => Some(x.pid) == pid,
+            })
+            .collect()
+    }
+}
+
+/// Resources of every process matched at once.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sample {
+    pub time: Option<SystemTime>,
+    pub running: bool,
+    /// Percent of one CPU.
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub threads: u32,
+    /// Missing when some `fd` directory cannot be read, usually for lack of permissions.
+    pub open_fds: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedProcess {
+    pub name: String,
+    pub pids: Vec<u32>,
+    /// Start of the oldest matched process, which restarts are tracked by.
+    pub started: Option<SystemTime>,
+    pub restarts: u64,
+    pub latest: Sample,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watched(pub Vec<WatchedProcess>);
+
+#[derive(Debug)]
+struct Samples {
+    kept: usize,
+    processes: HashMap<String, VecDeque<Sample>>,
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Samples {
+            kept: KEPT_SAMPLES,
+            processes: HashMap::new(),
+        }
+    }
+}
+
+/// Samples of every watched process, shared with the service.
+#[derive(Debug, Clone, Default)]
+pub struct History(Arc<Mutex<Samples>>);
+
+impl History {
+    fn push(&self, name: &str, sample: Sample) {
+        let mut history = self.0.lock().unwrap();
+        let kept = history.kept;
+        let samples = history.processes.entry(name.to_owned()).or_default();
+
+        if samples.len() >= kept {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Samples of a watched process, oldest first.
+    pub fn get(&self, name: &str) -> Option<Vec<Sample>> {
+        let history = self.0.lock().unwrap();
+
+        history
+            .processes
+            .get(name)
+            .map(|x| x.iter().cloned().collect())
+    }
+
+    /// Keeps at most `count` samples per process from now on, dropping the oldest ones.
+    pub fn keep(&self, count: usize) {
+        let mut history = self.0.lock().unwrap();
+        for samples in history.processes.values_mut() {
+            let excess = samples.len().saturating_sub(count);
+            samples.drain(..excess);
+        }
+        history.kept = count;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Liveness {
+    Unknown,
+    /// PID and start time of the oldest matched process.
+    Running(u32, u64),
+    Gone,
+}
+
+#[derive(Debug)]
+struct Watch {
+    name: String,
+    matcher: Matcher,
+    liveness: Liveness,
+    restarts: u64,
+}
+
+impl Watch {
+    /// Counts restarts and raises events when the oldest matched process changes.
+    fn observe(&mut self, matched: &[&ProcessInfo], events: &Events) {
+        let liveness = matched
+            .iter()
+            .min_by_key(|x| (x.start_time, x.pid))
+            .map_or(Liveness::Gone, |x| Liveness::Running(x.pid, x.start_time));
+        let raise = |severity, message| events.raise("watch", severity, message);
+
+        match (self.liveness, liveness) {
+            (Liveness::Unknown | Liveness::Running(..), Liveness::Gone) => {
+                raise(Severity::Critical, format!("{} is not running", self.name))
+            }
+            (Liveness::Running(before, _), Liveness::Running(pid, _)) if before != pid => {
+                self.restarts += 1;
+                raise(
+                    Severity::Warning,
+                    format!("{} restarted, PID {before} replaced by {pid}", self.name),
+                );
+            }
+            (Liveness::Gone, Liveness::Running(pid, _)) => {
+                self.restarts += 1;
+                raise(
+                    Severity::Info,
+                    format!("{} is running again as PID {pid}", self.name),
+                );
+            }
+            _ => {}
+        }
+        self.liveness = liveness;
+    }
+}
+
+/// Thread count and open descriptors of a process from procfs.
+fn read_counts(procfs_root: &Path, pid: u32) -> (u32, Option<u32>) {
+    let dir = procfs_root.join(pid.to_string());
+    let threads = std::fs::read_to_string(dir.join("status"))
+        .ok()
+        .and_then(|x| status_field(&x, "Threads")?.parse().ok())
+        .unwrap_or_default();
+    let fds = std::fs::read_dir(dir.join("fd"))
+        .ok()
+        .map(|x| x.count() as u32);
+
+    (threads, fds)
+}
+
+/// Tracks resources and liveness of the processes listed in `[[watch]]`.
+#[derive(Debug)]
+pub struct WatchCollector {
+    procfs_root: PathBuf,
+    watches: Vec<Watch>,
+    events: Events,
+    history: History,
+}
+
+impl WatchCollector {
+    pub fn new(
+        configs: &[WatchConfig],
+        procfs_root: impl Into<PathBuf>,
+        events: Events,
+        history: History,
+    ) -> Result<Self, Box<dyn Error>> {
+        let watches = configs
+            .iter()
+            .map(|config| {
+                Ok(Watch {
+                    name: config.name.clone(),
+                    matcher: Matcher::new(config)?,
+                    liveness: Liveness::Unknown,
+                    restarts: 0,
+                })
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        Ok(WatchCollector {
+            procfs_root: procfs_root.into(),
+            watches,
+            events,
+            history,
+        })
+    }
+
+    fn update(&mut self, processes: &[ProcessInfo], now: SystemTime) -> Watched {
+        let mut watched = vec![];
+
+        for watch in &mut self.watches {
+            let matched = watch.matcher.matches(processes);
+            watch.observe(&matched, &self.events);
+
+            let mut sample = Sample {
+                time: Some(now),
+                running: !matched.is_empty(),
+                open_fds: Some(0),
+                ..Default::default()
+            };
+            for process in &matched {
+                let (threads, fds) = read_counts(&self.procfs_root, process.pid);
+                sample.cpu_usage += process.cpu_usage;
+                sample.memory += process.memory;
+                sample.threads += threads;
+                sample.open_fds = sample.open_fds.zip(fds).map(|(a, b)| a + b);
+            }
+            self.history.push(&watch.name, sample.clone());
+
+            watched.push(WatchedProcess {
+                name: watch.name.clone(),
+                pids: matched.iter().map(|x| x.pid).collect(),
+                started: match watch.liveness {
+                    Liveness::Running(_, start) => {
+                        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(start))
+                    }
+                    _ => None,
+                },
+                restarts: watch.restarts,
+                latest: sample,
+            });
+        }
+        Watched(watched)
+    }
+}
+
+impl Collector for WatchCollector {
+    type Output = Watched;
+
+    fn name(&self) -> &'static str {
+        "watch"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        // Refreshing every process is not free, skip it when nothing is watched
+        if self.watches.is_empty() {
+            return Ok(Watched(vec![]));
+        }
+        let processes = system.processes();
+
+        Ok(self.update(&processes, SystemTime::now()))
+    }
+}
+
+impl From<Sample> for WatchedSample {
+    fn from(sample: Sample) -> Self {
+        WatchedSample {
+            time: sample.time.map(Into::into),
+            running: sample.running,
+            cpu_usage: sample.cpu_usage,
+            memory: sample.memory,
+            threads: sample.threads,
+            open_fds: sample.open_fds,
+        }
+    }
+}
+
+impl Section for Watched {
+    const FIELD: &'static str = "watched";
+
+    fn store(self, pack: &mut Pack) {
+        pack.watched = Some(WatchedResponse {
+            processes: self
+                .0
+                .into_iter()
+                .map(|x| proto::WatchedProcess {
+                    name: x.name,
+                    pids: x.pids,
+                    started: x.started.map(Into::into),
+                    restarts: x.restarts,
+                    latest: Some(x.latest.into()),
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.watched = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use crate::{
+        events::{Events, Severity},
+        source::ProcessInfo,
+        test_util::Fixture,
+    };
+
+    use super::{History, WatchCollector, WatchConfig};
+
+    fn process(pid: u32, name: &str, cmd: &str, start_time: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_owned(),
+            cmd: cmd.split(' ').map(ToOwned::to_owned).collect(),
+            cpu_usage: 10.0,
+            memory: 1 << 20,
+            start_time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_restarts_and_disappearance_raise_events() {
+        let fixture = Fixture::new("watch-restarts");
+        fixture
+            .write("100/status", "Name:\tapp\nThreads:\t4\n")
+            .write("100/fd/0", "")
+            .write("100/fd/1", "")
+            .write("101/status", "Name:\tapp\nThreads:\t2\n");
+        let config = WatchConfig {
+            name: "app".to_owned(),
+            cmdline: Some(r"python3 .*app\.py".to_owned()),
+            ..Default::default()
+        };
+        let events = Events::default();
+        let history = History::default();
+        let mut collector =
+            WatchCollector::new(&[config], fixture.path(), events.clone(), history.clone())
+                .unwrap();
+        let now = SystemTime::now();
+        let app = |pid, start| process(pid, "python3", "python3 /opt/app.py --serve", start);
+
+        let watched = collector.update(&[app(100, 10), app(101, 20)], now).0;
+        assert_eq!(watched[0].pids, [100, 101]);
+        assert_eq!(watched[0].latest.threads, 6);
+        assert_eq!(watched[0].latest.memory, 2 << 20);
+        // The descriptors of 101 are unreadable
+        assert_eq!(watched[0].latest.open_fds, None);
+        assert!(events.after(0).is_empty());
+
+        collector.update(&[process(1, "init", "/sbin/init", 0)], now);
+        collector.update(&[app(200, 30)], now);
+        let watched = collector.update(&[app(300, 40)], now).0;
+        assert_eq!(watched[0].restarts, 2);
+
+        let raised = events.after(0);
+        let messages = raised
+            .iter()
+            .map(|x| x.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "app is not running",
+                "app is running again as PID 200",
+                "app restarted, PID 200 replaced by 300",
+            ]
+        );
+        assert_eq!(raised[0].severity, Severity::Critical);
+
+        let samples = history.get("app").unwrap();
+        assert_eq!(samples.len(), 4);
+        assert!(!samples[1].running);
+        assert!(history.get("other").is_none());
+
+        history.keep(3);
+        let samples = history.get("app").unwrap();
+        assert_eq!(samples.len(), 3);
+        assert!(!samples[0].running);
+    }
+
+    #[test]
+    fn test_pidfile_and_name_matchers() {
+        let fixture = Fixture::new("watch-pidfile");
+        fixture.write("nginx.pid", "812\n");
+        let configs = [
+            WatchConfig {
+                name: "nginx".to_owned(),
+                pidfile: Some(fixture.path().join("nginx.pid")),
+                ..Default::default()
+            },
+            WatchConfig {
+                name: "sshd".to_owned(),
+                process: Some("sshd".to_owned()),
+                ..Default::default()
+            },
+        ];
+        let mut collector = WatchCollector::new(
+            &configs,
+            fixture.path(),
+            Events::default(),
+            History::default(),
+        )
+        .unwrap();
+
+        let processes = [
+            process(812, "nginx", "nginx: master", 1),
+            process(813, "nginx", "nginx: worker", 2),
+            process(90, "sshd", "/usr/sbin/sshd -D", 1),
+        ];
+        let watched = collector.update(&processes, SystemTime::now()).0;
+        assert_eq!(watched[0].pids, [812]);
+        assert_eq!(watched[1].pids, [90]);
+    }
+
+    #[test]
+    fn test_ambiguous_matchers_are_rejected() {
+        let config = |process: Option<&str>, cmdline: Option<&str>| WatchConfig {
+            name: "x".to_owned(),
+            process: process.map(ToOwned::to_owned),
+            cmdline: cmdline.map(ToOwned::to_owned),
+            ..Default::default()
+        };
+        let new =
+            |config| WatchCollector::new(&[config], "", Events::default(), History::default());
+
+        assert!(new(config(None, None)).is_err());
+        assert!(new(config(Some("a"), Some("b"))).is_err());
+        assert!(new(config(None, Some("("))).is_err());
+        assert!(new(config(Some("a"), None)).is_ok());
+    }
+}
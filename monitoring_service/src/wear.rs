@@ -0,0 +1,399 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use common::monitoring::{DiskWear, Pack, WearResponse};
+
+use crate::{
+    collector::{Collector, Section},
+    disks::{is_disk, read_diskstats, SECTOR_SIZE},
+    source::SystemSource,
+};
+
+const DAY: u64 = 24 * 60 * 60;
+/// Daily totals kept for the write rate.
+const KEPT_DAYS: usize = 30;
+/// Vendors rate endurance in terabytes written.
+const TERABYTE: f64 = 1e12;
+
+/// `[wear]` section of the config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WearConfig {
+    /// Where running totals survive restarts, `/var/lib/monitoring_service/wear.toml` by
+    /// default. The directory is created if missing.
+    pub state_file: PathBuf,
+    /// Rated endurance of every device, in TBW.
+    pub endurance_tbw: Option<f64>,
+    /// Endurance of particular devices, overriding `endurance_tbw`.
+    pub device_endurance_tbw: HashMap<String, f64>,
+    /// How often the state file is rewritten, to not wear the card by accounting its wear.
+    pub persist_interval_s: u64,
+}
+
+impl WearConfig {
+    fn endurance(&self, device: &str) -> Option<f64> {
+        self.device_endurance_tbw
+            .get(device)
+            .copied()
+            .or(self.endurance_tbw)
+    }
+}
+
+impl Default for WearConfig {
+    fn default() -> Self {
+        WearConfig {
+            state_file: PathBuf::from("/var/lib/monitoring_service/wear.toml"),
+            endurance_tbw: None,
+            device_endurance_tbw: HashMap::new(),
+            persist_interval_s: 600,
+        }
+    }
+}
+
+/// Running totals of a device, as stored in the state file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct DeviceState {
+    total_bytes: u64,
+    /// Kernel counter at the last observation, meaningful within `boot_id` only.
+    last_counter: u64,
+    boot_id: String,
+    /// Unix time of the first observation.
+    tracked_since: u64,
+    /// Bytes written per day, as `[day since the epoch, bytes]`.
+    days: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct State {
+    devices: BTreeMap<String, DeviceState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceWear {
+    pub device: String,
+    pub total_bytes_written: u64,
+    pub bytes_written_today: u64,
+    pub average_daily_bytes: u64,
+    pub endurance_tbw: Option<f64>,
+    /// Percent of the rated endurance.
+    pub used_endurance: Option<f32>,
+    pub projected_days_left: Option<f64>,
+    pub tracked_since: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wear(pub Vec<DeviceWear>);
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl DeviceState {
+    /// Adds the writes since the previous observation. A new boot or a counter smaller
+    /// than before means the kernel started counting from zero again.
+    fn observe(&mut self, counter: u64, boot_id: &str, now: SystemTime) {
+        let written = if boot_id == self.boot_id && counter >= self.last_counter {
+            counter - self.last_counter
+        } else {
+            counter
+        };
+        let today = unix_seconds(now) / DAY;
+
+        if self.tracked_since == 0 {
+            self.tracked_since = unix_seconds(now);
+        }
+        self.total_bytes += written;
+        self.last_counter = counter;
+        self.boot_id = boot_id.to_owned();
+
+        match self.days.last_mut() {
+            Some((day, bytes)) if *day == today => *bytes += written,
+            _ => self.days.push((today, written)),
+        }
+        if self.days.len() > KEPT_DAYS {
+            self.days.remove(0);
+        }
+    }
+
+    fn wear(&self, device: String, endurance_tbw: Option<f64>, now: SystemTime) -> DeviceWear {
+        let today = unix_seconds(now) / DAY;
+        let first_day = self.days.first().map_or(today, |x| x.0);
+        let recent = self.days.iter().map(|x| x.1).sum::<u64>();
+        let average_daily_bytes = recent / (today - first_day.min(today) + 1);
+
+        let endurance = endurance_tbw.map(|x| x * TERABYTE);
+        let used_endurance = endurance.map(|x| (self.total_bytes as f64 / x * 100.0) as f32);
+        let projected_days_left = endurance
+            .filter(|_| average_daily_bytes > 0)
+            .map(|x| (x - self.total_bytes as f64).max(0.0) / average_daily_bytes as f64);
+
+        DeviceWear {
+            device,
+            total_bytes_written: self.total_bytes,
+            bytes_written_today: self.days.last().filter(|x| x.0 == today).map_or(0, |x| x.1),
+            average_daily_bytes,
+            endurance_tbw,
+            used_endurance,
+            projected_days_left,
+            tracked_since: SystemTime::UNIX_EPOCH + Duration::from_secs(self.tracked_since),
+        }
+    }
+}
+
+impl State {
+    fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(std::io::Error::other),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces the file at once, so a power cut leaves either the old or the new totals.
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temporary = path.with_extension("tmp");
+        let content = toml::to_string(self).map_err(std::io::Error::other)?;
+
+        std::fs::write(&temporary, content)?;
+        std::fs::rename(temporary, path)
+    }
+}
+
+/// Accounts lifetime writes of block devices and projects when their endurance runs out.
+#[derive(Debug)]
+pub struct WearCollector {
+    config: WearConfig,
+    sysfs_root: PathBuf,
+    procfs_root: PathBuf,
+    state: Option<State>,
+    persisted: Option<Instant>,
+}
+
+impl WearCollector {
+    pub fn new(
+        config: WearConfig,
+        sysfs_root: impl Into<PathBuf>,
+        procfs_root: impl Into<PathBuf>,
+    ) -> Self {
+        WearCollector {
+            config,
+            sysfs_root: sysfs_root.into(),
+            procfs_root: procfs_root.into(),
+            state: None,
+            persisted: None,
+        }
+    }
+
+    fn update(&mut self, counters: Vec<(String, u64)>, boot_id: &str, now: SystemTime) -> Wear {
+        let state = self.state.get_or_insert_with(Default::default);
+        for (device, counter) in counters {
+            state
+                .devices
+                .entry(device)
+                .or_default()
+                .observe(counter, boot_id, now);
+        }
+
+        let devices = state
+            .devices
+            .iter()
+            .map(|(device, x)| x.wear(device.clone(), self.config.endurance(device), now))
+            .collect();
+        Wear(devices)
+    }
+}
+
+impl Collector for WearCollector {
+    type Output = Wear;
+
+    fn name(&self) -> &'static str {
+        "wear"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn reads_host(&self) -> bool {
+        true
+    }
+
+    fn collect(&mut self, _system: &mut dyn SystemSource) -> std::io::Result<Self::Output> {
+        if self.state.is_none() {
+            self.state = Some(State::load(&self.config.state_file)?);
+        }
+        let boot_id = std::fs::read_to_string(self.procfs_root.join("sys/kernel/random/boot_id"))?;
+        let counters = read_diskstats(&self.procfs_root)?
+            .into_iter()
+            .filter(|x| is_disk(&self.sysfs_root, &x.0))
+            .map(|(name, x)| (name, x.sectors_written * SECTOR_SIZE))
+            .collect();
+
+        let wear = self.update(counters, boot_id.trim(), SystemTime::now());
+
+        let interval = Duration::from_secs(self.config.persist_interval_s);
+        if self.persisted.is_none_or(|x| x.elapsed() >= interval) {
+            // The totals are kept in memory and saved again after the next interval
+            if let Some(Err(e)) = self.state.as_ref().map(|x| x.save(&self.config.state_file)) {
+                eprintln!(
+                    "Cannot save the wear state to {}: {e}",
+                    self.config.state_file.display()
+                );
+            }
+            self.persisted = Some(Instant::now());
+        }
+        Ok(wear)
+    }
+}
+
+impl Section for Wear {
+    const FIELD: &'static str = "wear";
+
+    fn store(self, pack: &mut Pack) {
+        pack.wear = Some(WearResponse {
+            devices: self
+                .0
+                .into_iter()
+                .map(|x| DiskWear {
+                    device: x.device,
+                    total_bytes_written: x.total_bytes_written,
+                    bytes_written_today: x.bytes_written_today,
+                    average_daily_bytes: x.average_daily_bytes,
+                    endurance_tbw: x.endurance_tbw,
+                    used_endurance: x.used_endurance,
+                    projected_days_left: x.projected_days_left,
+                    tracked_since: Some(x.tracked_since.into()),
+                })
+                .collect(),
+        });
+    }
+
+    fn clear(pack: &mut Pack) {
+        pack.wear = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use sysinfo::{System, SystemExt};
+
+    use crate::{collector::Collector, test_util::Fixture};
+
+    use super::{DeviceState, State, WearCollector, WearConfig, DAY};
+
+    fn at(days: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(days * DAY + 3600)
+    }
+
+    #[test]
+    fn test_totals_survive_reboots_and_resets() {
+        let mut state = DeviceState::default();
+
+        state.observe(1000, "a", at(100));
+        state.observe(1500, "a", at(100));
+        // Rebooted, the kernel counts from zero
+        state.observe(200, "b", at(101));
+        // Wrapped within the same boot
+        state.observe(50, "b", at(101));
+
+        assert_eq!(state.total_bytes, 1750);
+        assert_eq!(state.days, [(100, 1500), (101, 250)]);
+    }
+
+    #[test]
+    fn test_projection() {
+        let mut state = DeviceState::default();
+        state.observe(0, "a", at(10));
+        state.observe(2_000_000_000, "a", at(11));
+
+        let wear = state.wear("mmcblk0".to_owned(), Some(0.01), at(11));
+        assert_eq!(wear.bytes_written_today, 2_000_000_000);
+        assert_eq!(wear.average_daily_bytes, 1_000_000_000);
+        assert_eq!(wear.used_endurance, Some(20.0));
+        assert_eq!(wear.projected_days_left, Some(8.0));
+
+        let unrated = state.wear("mmcblk0".to_owned(), None, at(12));
+        assert_eq!(unrated.bytes_written_today, 0);
+        assert_eq!(unrated.projected_days_left, None);
+    }
+
+    #[test]
+    fn test_state_is_persisted() {
+        let fixture = Fixture::new("wear-state");
+        fixture.write("proc/sys/kernel/random/boot_id", "a").write(
+            "proc/diskstats",
+            "179 0 mmcblk0 0 0 0 0 0 0 8 0 0 0 0 0 0 0 0",
+        );
+        let config = WearConfig {
+            state_file: fixture.path().join("state/wear.toml"),
+            ..Default::default()
+        };
+
+        let mut collector = WearCollector::new(
+            config.clone(),
+            fixture.path().join("sys"),
+            fixture.path().join("proc"),
+        );
+        collector.collect(&mut System::new()).unwrap();
+
+        // Written again after a reboot
+        fixture.write("proc/sys/kernel/random/boot_id", "b");
+        let mut collector = WearCollector::new(
+            config,
+            fixture.path().join("sys"),
+            fixture.path().join("proc"),
+        );
+        let wear = collector.collect(&mut System::new()).unwrap();
+
+        assert_eq!(wear.0[0].device, "mmcblk0");
+        assert_eq!(wear.0[0].total_bytes_written, 2 * 8 * 512);
+        let state = State::load(&fixture.path().join("state/wear.toml")).unwrap();
+        assert_eq!(state.devices["mmcblk0"].total_bytes, 2 * 8 * 512);
+    }
+
+    #[test]
+    fn test_wear_is_reported_when_the_state_cannot_be_saved() {
+        let fixture = Fixture::new("wear-unsaved");
+        fixture.write("proc/sys/kernel/random/boot_id", "a").write(
+            "proc/diskstats",
+            "179 0 mmcblk0 0 0 0 0 0 0 8 0 0 0 0 0 0 0 0",
+        );
+        // Taking the place of the temporary file the state is written to first
+        std::fs::create_dir_all(fixture.path().join("state/wear.tmp")).unwrap();
+        let config = WearConfig {
+            state_file: fixture.path().join("state/wear.toml"),
+            persist_interval_s: 0,
+            ..Default::default()
+        };
+
+        let mut collector = WearCollector::new(
+            config,
+            fixture.path().join("sys"),
+            fixture.path().join("proc"),
+        );
+        let wear = collector.collect(&mut System::new()).unwrap();
+        assert_eq!(wear.0[0].total_bytes_written, 8 * 512);
+
+        // Saved once it can be
+        std::fs::remove_dir(fixture.path().join("state/wear.tmp")).unwrap();
+        collector.collect(&mut System::new()).unwrap();
+        let state = State::load(&fixture.path().join("state/wear.toml")).unwrap();
+        assert_eq!(state.devices["mmcblk0"].total_bytes, 8 * 512);
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::Range;
 use yew::{function_component, html, Html, Properties};
 use yew_plotly::plotly::color::Rgb;
 use yew_plotly::plotly::common::{DashType, Fill, Line, Mode, Title};
-use yew_plotly::plotly::layout::Axis;
+use yew_plotly::plotly::layout::{Axis, Shape, ShapeLayer, ShapeLine, ShapeType};
 use yew_plotly::plotly::{Layout, Plot, Scatter};
 use yew_plotly::Plotly;
 
@@ -16,6 +16,9 @@ pub struct AveragePlotProps {
     pub main_series_color: (u8, u8, u8),
     pub avg_series_name: String,
     pub avg_series_color: (u8, u8, u8),
+    /// Samples to shade, parallel to `y_data`.
+    #[prop_or_default]
+    pub highlighted: Vec<bool>,
 }
 
 impl AveragePlotProps {
@@ -54,17 +57,14 @@ impl AveragePlotProps {
     }
 
     fn main_series(&self) -> Box<Scatter<usize, f32>> {
-        Scatter::new(
-            (0..self.y_data.len()).into_iter().collect(),
-            self.y_data.clone(),
-        )
-        .mode(Mode::Lines)
-        .name(&self.main_series_name)
-        .line(Line::new().dash(DashType::Dot).color(Rgb::new(
-            self.main_series_color.0,
-            self.main_series_color.1,
-            self.main_series_color.2,
-        )))
+        Scatter::new((0..self.y_data.len()).collect(), self.y_data.clone())
+            .mode(Mode::Lines)
+            .name(&self.main_series_name)
+            .line(Line::new().dash(DashType::Dot).color(Rgb::new(
+                self.main_series_color.0,
+                self.main_series_color.1,
+                self.main_series_color.2,
+            )))
     }
 
     fn avg_series(&self) -> Box<Scatter<usize, f32>> {
@@ -75,32 +75,59 @@ impl AveragePlotProps {
             self.avg_series_color.2 as f64 + (255 - self.avg_series_color.2) as f64 * tint_factor,
         );
 
-        Scatter::new(
-            (0..self.y_data.len()).into_iter().collect(),
-            self.cumulative_average(),
-        )
-        .mode(Mode::Lines)
-        .name(&self.avg_series_name)
-        .line(Line::new().color(Rgb::new(
-            self.avg_series_color.0,
-            self.avg_series_color.1,
-            self.avg_series_color.2,
-        )))
-        .fill(Fill::ToZeroY)
-        .fill_color(Rgb::new(tint.0 as u8, tint.1 as u8, tint.2 as u8))
+        Scatter::new((0..self.y_data.len()).collect(), self.cumulative_average())
+            .mode(Mode::Lines)
+            .name(&self.avg_series_name)
+            .line(Line::new().color(Rgb::new(
+                self.avg_series_color.0,
+                self.avg_series_color.1,
+                self.avg_series_color.2,
+            )))
+            .fill(Fill::ToZeroY)
+            .fill_color(Rgb::new(tint.0 as u8, tint.1 as u8, tint.2 as u8))
+    }
+
+    fn highlighted_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = vec![];
+
+        for (i, _) in self.highlighted.iter().enumerate().filter(|x| *x.1) {
+            match ranges.last_mut() {
+                Some(last) if last.end == i => last.end = i + 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        ranges
     }
 
     fn layout(&self) -> Layout {
         let range = self.y_range();
 
-        Layout::new()
+        let mut layout = Layout::new()
             .x_axis(Axis::new().title(Title::new(&self.x_name)))
             .y_axis(
                 Axis::new()
                     .title(Title::new(&self.y_name))
                     .range(vec![range.start, range.end]),
             )
-            .show_legend(false)
+            .show_legend(false);
+
+        for highlight in self.highlighted_ranges() {
+            layout.add_shape(
+                Shape::new()
+                    .shape_type(ShapeType::Rect)
+                    .layer(ShapeLayer::Below)
+                    .x_ref("x")
+                    .y_ref("paper")
+                    .x0(highlight.start as f64 - 0.5)
+                    .x1(highlight.end as f64 - 0.5)
+                    .y0(0)
+                    .y1(1)
+                    .fill_color(Rgb::new(0xf1, 0x46, 0x68))
+                    .opacity(0.2)
+                    .line(ShapeLine::new().width(0.)),
+            );
+        }
+        layout
     }
 }
 
@@ -146,6 +173,7 @@ mod tests {
             main_series_color: (0, 0, 0),
             avg_series_name: "".to_string(),
             avg_series_color: (0, 0, 0),
+            highlighted: vec![],
         }
     }
 
@@ -153,7 +181,17 @@ mod tests {
     fn test_avg_plot_range() {
         let props = default_plot();
 
-        assert!(RangeF32(props.y_range()).approx_eq(RangeF32(0.1..10.9), (f32::EPSILON, 0)))
+        assert!(RangeF32(props.y_range()).approx_eq(RangeF32(-8.0..19.0), (f32::EPSILON, 0)))
+    }
+
+    #[test]
+    fn test_avg_plot_highlighted_ranges() {
+        let props = AveragePlotProps {
+            highlighted: vec![true, false, false, true, true, false, true],
+            ..default_plot()
+        };
+
+        assert_eq!(props.highlighted_ranges(), vec![0..1, 3..5, 6..7]);
     }
 
     #[test]
@@ -4,6 +4,8 @@ use yew::{classes, function_component, html, Html, Properties};
 pub struct BarProps {
     pub fill: f32,
     pub class: String,
+    #[prop_or_default]
+    pub name: Option<String>,
 }
 
 #[function_component(Bar)]
@@ -11,7 +13,10 @@ pub fn bar(props: &BarProps) -> Html {
     html! {
         <div class={classes!("bar", props.class.clone())}>
             <div class="bar-inner" style={format!("width: {}%;", props.fill)}></div>
-            <label class="uk-position-z-index">{format!("{:.2}% usage", props.fill)}</label>
+            <label class="uk-position-z-index">
+                {props.name.iter().map(|x| format!("{x}: ")).collect::<String>()}
+                {format!("{:.2}% usage", props.fill)}
+            </label>
         </div>
     }
 }
@@ -1,17 +1,48 @@
 use common::monitoring::monitor_client::MonitorClient;
-use common::monitoring::Pack;
+use common::monitoring::{
+    Action, ActionOutput, ActionRequest, Event, EventsRequest, Pack, PowerRequest, PowerResponse,
+    RestartServiceRequest, RuntimeConfig, Signal, SignalRequest, UpdateConfigRequest,
+};
+use prost_types::FieldMask;
 use futures::future::{AbortHandle};
 use futures::Stream;
+use std::fmt::{Display, Formatter};
 use std::ops::{Deref, DerefMut};
-use tonic::{Request, Response, Status};
+use tonic::{Code, Request, Status};
 
 #[cfg(target_arch = "wasm32")]
 type MonitorClientWithTransport = MonitorClient<tonic_web_wasm_client::Client>;
 #[cfg(not(target_arch = "wasm32"))]
 type MonitorClientWithTransport = MonitorClient<tonic::transport::Channel>;
 
+/// A pack together with the events raised since the previous one.
+pub struct Update {
+    pub pack: Pack,
+    pub events: Vec<Event>,
+}
+
+/// Operations the service only performs once confirmed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PowerOperation {
+    Reboot,
+    Shutdown,
+    RestartService(String),
+}
+
+impl Display for PowerOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PowerOperation::Reboot => write!(f, "Reboot the board"),
+            PowerOperation::Shutdown => write!(f, "Shut the board down"),
+            PowerOperation::RestartService(unit) => write!(f, "Restart {unit}"),
+        }
+    }
+}
+
 pub struct RpcClient {
     channel: MonitorClientWithTransport,
+    /// Sent with admin requests.
+    token: Option<String>,
 }
 
 impl RpcClient {
@@ -20,6 +51,7 @@ impl RpcClient {
         let client = tonic_web_wasm_client::Client::new(dst);
         Self {
             channel: MonitorClient::new(client),
+            token: None,
         }
     }
 
@@ -32,18 +64,138 @@ impl RpcClient {
         let channel = Channel::builder(Uri::from_str(&dst).unwrap()).connect_lazy();
         Self {
             channel: MonitorClient::new(channel),
+            token: None,
+        }
+    }
+
+    pub fn with_token(self, token: String) -> Self {
+        Self {
+            token: Some(token),
+            ..self
         }
     }
 
-    pub fn connect(
-        self,
-    ) -> (
-        impl Stream<Item = Result<Response<Pack>, Status>>,
-        AbortHandle,
-    ) {
-        let stream = futures::stream::try_unfold(self, |mut client| async move {
-            let pack = client.monitor_all(Request::new(())).await;
-            pack.map(|x| Some((x, client)))
+    /// Tokens that are not valid header values are left out, the service then reports the
+    /// missing token.
+    fn admin_request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(Ok(header)) = self.token.as_ref().map(|x| format!("Bearer {x}").parse()) {
+            request.metadata_mut().insert("authorization", header);
+        }
+        request
+    }
+
+    pub async fn signal(&mut self, pid: u32, signal: Signal) -> Result<(), Status> {
+        let request = self.admin_request(SignalRequest {
+            pid,
+            signal: signal.into(),
+        });
+
+        self.channel.signal_process(request).await.map(|_| ())
+    }
+
+    pub async fn list_actions(&mut self) -> Result<Vec<Action>, Status> {
+        let response = self.channel.list_actions(Request::new(())).await?;
+
+        Ok(response.into_inner().actions)
+    }
+
+    /// Output of the action as it runs, the exit comes last.
+    pub async fn exec(
+        &mut self,
+        name: String,
+    ) -> Result<impl Stream<Item = Result<ActionOutput, Status>>, Status> {
+        let request = self.admin_request(ActionRequest { name });
+
+        Ok(self.channel.exec_action(request).await?.into_inner())
+    }
+
+    /// Without `confirm_token` the response carries one, sending the same request with it
+    /// schedules the operation.
+    pub async fn power(
+        &mut self,
+        operation: PowerOperation,
+        delay_secs: u32,
+        confirm_token: String,
+    ) -> Result<PowerResponse, Status> {
+        let response = match operation {
+            PowerOperation::Reboot => {
+                let request = self.admin_request(PowerRequest {
+                    delay_secs,
+                    confirm_token,
+                });
+                self.channel.reboot(request).await?
+            }
+            PowerOperation::Shutdown => {
+                let request = self.admin_request(PowerRequest {
+                    delay_secs,
+                    confirm_token,
+                });
+                self.channel.shutdown(request).await?
+            }
+            PowerOperation::RestartService(unit) => {
+                let request = self.admin_request(RestartServiceRequest {
+                    unit,
+                    delay_secs,
+                    confirm_token,
+                });
+                self.channel.restart_service(request).await?
+            }
+        };
+
+        Ok(response.into_inner())
+    }
+
+    pub async fn get_config(&mut self) -> Result<RuntimeConfig, Status> {
+        let response = self.channel.get_config(Request::new(())).await?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Applies the settings and returns them as the service understood them. With `persist`
+    /// they are also written to the config file of the service.
+    pub async fn update_config(
+        &mut self,
+        config: RuntimeConfig,
+        persist: bool,
+    ) -> Result<RuntimeConfig, Status> {
+        let request = self.admin_request(UpdateConfigRequest {
+            config: Some(config),
+            persist,
+        });
+
+        Ok(self.channel.update_config(request).await?.into_inner())
+    }
+
+    pub fn connect(self) -> (impl Stream<Item = Result<Update, Status>>, AbortHandle) {
+        let stream = futures::stream::try_unfold((self, 0), |(mut client, after)| async move {
+            let sections = FieldMask {
+                paths: [
+                    "cpu",
+                    "network",
+                    "load",
+                    "disks",
+                    "wear",
+                    "cgroups",
+                    "units",
+                    "processes",
+                ]
+                .map(ToOwned::to_owned)
+                .to_vec(),
+            };
+            let pack = client
+                .monitor_all(Request::new(sections))
+                .await?
+                .into_inner();
+            let events = match client.events(Request::new(EventsRequest { after })).await {
+                Ok(x) => x.into_inner().events,
+                // Services older than the events RPC
+                Err(e) if e.code() == Code::Unimplemented => vec![],
+                Err(e) => return Err(e),
+            };
+            let after = events.last().map_or(after, |x| x.id);
+
+            Ok(Some((Update { pack, events }, (client, after))))
         });
 
         futures::stream::abortable(stream)
@@ -3,6 +3,7 @@
 pub mod average_plot;
 pub mod bar;
 pub mod client;
+pub mod line_plot;
 pub mod model;
 pub mod view;
 
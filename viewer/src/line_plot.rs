@@ -0,0 +1,98 @@
+use ordered_float::OrderedFloat;
+use std::ops::Range;
+use yew::{function_component, html, Html, Properties};
+use yew_plotly::plotly::color::Rgb;
+use yew_plotly::plotly::common::{Line, Mode, Title};
+use yew_plotly::plotly::layout::Axis;
+use yew_plotly::plotly::{Layout, Plot, Scatter};
+use yew_plotly::Plotly;
+
+#[derive(PartialEq, Clone)]
+pub struct Series {
+    pub name: String,
+    pub color: (u8, u8, u8),
+    pub data: Vec<f32>,
+}
+
+/// Several series sharing the time axis, with the value axis starting at zero.
+#[derive(Properties, PartialEq)]
+pub struct LinePlotProps {
+    pub series: Vec<Series>,
+    pub x_name: String,
+    pub y_name: String,
+    /// Lowest upper bound of the value axis, so that idle values stay near the bottom.
+    #[prop_or(1.0)]
+    pub min_y_max: f32,
+}
+
+impl LinePlotProps {
+    fn y_range(&self) -> Range<f32> {
+        let max = self
+            .series
+            .iter()
+            .flat_map(|x| x.data.iter())
+            .max_by_key(|&&x| OrderedFloat(x))
+            .copied()
+            .unwrap_or_default();
+
+        0.0..(max * 1.1).max(self.min_y_max)
+    }
+
+    fn layout(&self) -> Layout {
+        let range = self.y_range();
+
+        Layout::new()
+            .x_axis(Axis::new().title(Title::new(&self.x_name)))
+            .y_axis(
+                Axis::new()
+                    .title(Title::new(&self.y_name))
+                    .range(vec![range.start, range.end]),
+            )
+    }
+}
+
+#[function_component]
+pub fn LinePlot(props: &LinePlotProps) -> Html {
+    let mut plot = Plot::new();
+    plot.set_layout(props.layout());
+    for series in &props.series {
+        plot.add_trace(
+            Scatter::new((0..series.data.len()).collect(), series.data.clone())
+                .mode(Mode::Lines)
+                .name(&series.name)
+                .line(Line::new().color(Rgb::new(series.color.0, series.color.1, series.color.2))),
+        );
+    }
+
+    html! {
+        <Plotly {plot} />
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line_plot::{LinePlotProps, Series};
+
+    fn plot(data: Vec<Vec<f32>>) -> LinePlotProps {
+        LinePlotProps {
+            series: data
+                .into_iter()
+                .map(|data| Series {
+                    name: "".to_string(),
+                    color: (0, 0, 0),
+                    data,
+                })
+                .collect(),
+            x_name: "".to_string(),
+            y_name: "".to_string(),
+            min_y_max: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_line_plot_range() {
+        assert_eq!(plot(vec![]).y_range(), 0.0..1.0);
+        assert_eq!(plot(vec![vec![0.1, 0.2], vec![0.5]]).y_range(), 0.0..1.0);
+        assert_eq!(plot(vec![vec![1.0, 10.0], vec![5.0]]).y_range(), 0.0..11.0);
+    }
+}
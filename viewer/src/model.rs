@@ -1,22 +1,36 @@
-use crate::average_plot::AveragePlot;
-use crate::bar::Bar;
-use crate::client::RpcClient;
+use crate::client::{PowerOperation, RpcClient, Update};
 use crate::model::Message::*;
 use crate::model::Model::*;
 use crate::view::{failed_view, populated_view, unloaded_view};
-use common::monitoring::{CpuResponse, NetworkResponse, Pack};
+use common::monitoring::{
+    action_output, Action, ActionExit, ActionOutput, AlertThresholds, Cgroup, CpuCore, CpuResponse, DiskStats, DiskWear, Event, LoadResponse, NetworkResponse, Pack,
+    ProcessSummary, Retention, RuntimeConfig, Severity, Signal, SystemdUnit,
+};
 use futures::stream::AbortHandle;
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
 use smallvec::SmallVec;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tonic::{Response, Status};
-use ybc::*;
-use yew::{function_component, html, Component, Context, Html, Properties};
+use tonic::Status;
+use yew::{Component, Context, Html, Properties};
 
 #[derive(Clone)]
 pub struct Common {
     connection_address: Arc<String>,
+    /// Admin token, admin controls are only shown when it is set.
+    token: Arc<String>,
+}
+
+impl Common {
+    fn client(&self) -> RpcClient {
+        let client = RpcClient::new(self.connection_address.as_ref().clone());
+        if self.token.is_empty() {
+            client
+        } else {
+            client.with_token(self.token.as_ref().clone())
+        }
+    }
 }
 
 pub enum Model {
@@ -25,30 +39,318 @@ pub enum Model {
     Failed(Common, Status),
     Populated {
         active: AbortHandle,
-        cpu_temp_window: SmallVec<[f32; 60]>,
-        usage: Vec<f32>,
-        network_response: NetworkResponse,
+        dashboard: Box<Dashboard>,
         common: Common,
     },
 }
 
+/// Samples kept for plots.
+const WINDOW: usize = 60;
+/// Warnings kept for the banner.
+const KEPT_EVENTS: usize = 10;
+/// Gives other viewers time to notice the announcement.
+const POWER_DELAY_SECS: u32 = 10;
+
+fn push_window<T>(window: &mut Vec<T>, value: T) {
+    if window.len() == WINDOW {
+        window.remove(0);
+    }
+    window.push(value);
+}
+
+#[derive(Default)]
+pub struct DiskHistory {
+    /// Percent of time busy.
+    pub utilization: Vec<f32>,
+    /// Read and written MiB per second.
+    pub throughput: Vec<f32>,
+}
+
+/// An action started from the viewer.
+#[derive(Default)]
+pub struct ActionRun {
+    pub name: String,
+    /// Standard output and error as they arrived.
+    pub output: String,
+    /// Missing while the action runs.
+    pub exit: Option<ActionExit>,
+    pub error: Option<String>,
+}
+
+impl ActionRun {
+    pub fn running(&self) -> bool {
+        self.exit.is_none() && self.error.is_none()
+    }
+
+    fn receive(&mut self, output: Result<ActionOutput, Status>) {
+        match output.map(|x| x.output) {
+            Ok(Some(action_output::Output::Stdout(x) | action_output::Output::Stderr(x))) => {
+                self.output.push_str(&String::from_utf8_lossy(&x))
+            }
+            Ok(Some(action_output::Output::Exit(exit))) => self.exit = Some(exit),
+            Ok(None) => {}
+            Err(e) => self.error = Some(e.message().to_owned()),
+        }
+    }
+}
+
+/// Everything shown once packs start to arrive.
+#[derive(Default)]
+pub struct Dashboard {
+    pub cpu_temp_window: SmallVec<[f32; 60]>,
+    /// Whether the CPU was throttled when each temperature was taken.
+    pub cpu_throttled_window: Vec<bool>,
+    pub throttling: Throttling,
+    pub usage: Vec<f32>,
+    pub cores: Vec<CpuCore>,
+    pub network_response: NetworkResponse,
+    /// Missing while the load collector is disabled.
+    pub load: Option<LoadResponse>,
+    /// 1, 5 and 15 minute load averages.
+    pub load_windows: [Vec<f32>; 3],
+    /// `some avg10` of the CPU, memory and IO pressure.
+    pub pressure_windows: [Vec<f32>; 3],
+    pub disks: Vec<DiskStats>,
+    pub disk_windows: BTreeMap<String, DiskHistory>,
+    pub wear: Vec<DiskWear>,
+    pub cgroups: Vec<Cgroup>,
+    pub units: Vec<SystemdUnit>,
+    pub processes: Vec<ProcessSummary>,
+    /// Warnings and critical events not dismissed yet, oldest first.
+    pub events: Vec<Event>,
+    /// Outcome of the latest admin operation.
+    pub notice: Option<String>,
+    /// Declared actions, only listed for admin sessions.
+    pub actions: Vec<Action>,
+    pub action_run: Option<ActionRun>,
+    /// Operation waiting for the user to confirm, with the token the service handed out.
+    pub confirm: Option<(PowerOperation, String)>,
+    /// Runtime settings as edited, only loaded for admin sessions.
+    pub settings: Option<RuntimeConfig>,
+}
+
+impl Dashboard {
+    fn update(&mut self, pack: Pack) {
+        // Sections of disabled collectors are missing from the pack
+        let Pack {
+            cpu,
+            network,
+            load,
+            disks,
+            wear,
+            cgroups,
+            units,
+            processes,
+            ..
+        } = pack;
+        let cpu = cpu.unwrap_or_default();
+
+        if self.cpu_temp_window.len() == self.cpu_temp_window.inline_size() {
+            self.cpu_temp_window.remove(0);
+            self.cpu_throttled_window.remove(0);
+        }
+        if let Some(t) = cpu.temperature {
+            self.cpu_temp_window.push(t);
+            self.cpu_throttled_window.push(cpu.throttled);
+        }
+        self.throttling = (&cpu).into();
+        self.usage = cpu.usage;
+        self.cores = cpu.cores;
+        self.network_response = network.unwrap_or_default();
+
+        if let Some(load) = &load {
+            let averages = [load.load1, load.load5, load.load15];
+            let pressures = [&load.cpu, &load.memory, &load.io].map(|x| {
+                x.as_ref()
+                    .and_then(|x| x.some.as_ref())
+                    .map_or(0.0, |x| x.avg10)
+            });
+
+            for (window, value) in self.load_windows.iter_mut().zip(averages) {
+                push_window(window, value);
+            }
+            for (window, value) in self.pressure_windows.iter_mut().zip(pressures) {
+                push_window(window, value);
+            }
+        }
+        self.load = load;
+
+        self.disks = disks.unwrap_or_default().disks;
+        self.disk_windows
+            .retain(|name, _| self.disks.iter().any(|x| &x.name == name));
+        for disk in &self.disks {
+            let history = self.disk_windows.entry(disk.name.clone()).or_default();
+            push_window(&mut history.utilization, disk.utilization);
+            push_window(
+                &mut history.throughput,
+                (disk.read_bytes_per_second + disk.write_bytes_per_second) / (1024.0 * 1024.0),
+            );
+        }
+        self.wear = wear.unwrap_or_default().devices;
+        self.cgroups = cgroups.unwrap_or_default().cgroups;
+        self.units = units.unwrap_or_default().units;
+        self.processes = processes.unwrap_or_default().processes;
+    }
+
+    fn add_events(&mut self, events: Vec<Event>) {
+        self.events.extend(
+            events
+                .into_iter()
+                .filter(|x| x.severity() >= Severity::Warning),
+        );
+        if self.events.len() > KEPT_EVENTS {
+            self.events.drain(..self.events.len() - KEPT_EVENTS);
+        }
+    }
+
+    fn receive(&mut self, update: Update) {
+        self.update(update.pack);
+        self.add_events(update.events);
+    }
+}
+
+/// Throttling counters of the latest response.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Throttling {
+    pub throttled: bool,
+    pub throttled_ms: u64,
+    pub events: u64,
+}
+
+impl From<&CpuResponse> for Throttling {
+    fn from(cpu: &CpuResponse) -> Self {
+        Throttling {
+            throttled: cpu.throttled,
+            throttled_ms: cpu.throttled_ms,
+            events: cpu.throttle_events,
+        }
+    }
+}
+
+/// Number of the settings form.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    SamplingInterval,
+    CacheMaxAge,
+    CpuUsageAlert,
+    TemperatureAlert,
+    LoadAverageAlert,
+    KeptEvents,
+    KeptSamples,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 7] = [
+        Setting::SamplingInterval,
+        Setting::CacheMaxAge,
+        Setting::CpuUsageAlert,
+        Setting::TemperatureAlert,
+        Setting::LoadAverageAlert,
+        Setting::KeptEvents,
+        Setting::KeptSamples,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::SamplingInterval => "Sampling interval, ms",
+            Setting::CacheMaxAge => "Shared collection age, ms",
+            Setting::CpuUsageAlert => "CPU usage alert, %",
+            Setting::TemperatureAlert => "Temperature alert, °C",
+            Setting::LoadAverageAlert => "Load average alert",
+            Setting::KeptEvents => "Events kept",
+            Setting::KeptSamples => "Samples kept per watched process",
+        }
+    }
+
+    fn threshold(self, alerts: &mut AlertThresholds) -> Option<&mut Option<f64>> {
+        match self {
+            Setting::CpuUsageAlert => Some(&mut alerts.cpu_usage_percent),
+            Setting::TemperatureAlert => Some(&mut alerts.temperature_celsius),
+            Setting::LoadAverageAlert => Some(&mut alerts.load_average),
+            _ => None,
+        }
+    }
+
+    fn count(self, retention: &mut Retention) -> Option<&mut u32> {
+        match self {
+            Setting::KeptEvents => Some(&mut retention.events),
+            Setting::KeptSamples => Some(&mut retention.watched_samples),
+            _ => None,
+        }
+    }
+
+    /// Text of the field, empty for unset thresholds.
+    pub fn get(self, config: &RuntimeConfig) -> String {
+        let mut config = config.clone();
+        let alerts = config.alerts.get_or_insert_with(Default::default);
+        let retention = config.retention.get_or_insert_with(Default::default);
+
+        if let Some(threshold) = self.threshold(alerts) {
+            threshold.map(|x| x.to_string()).unwrap_or_default()
+        } else if let Some(count) = self.count(retention) {
+            count.to_string()
+        } else if self == Setting::CacheMaxAge {
+            config.cache_max_age_ms.unwrap_or_default().to_string()
+        } else {
+            config.sampling_interval_ms.unwrap_or_default().to_string()
+        }
+    }
+
+    /// Leaves `config` as it is when `value` is not a number. Clearing a threshold unsets it.
+    pub fn set(self, config: &mut RuntimeConfig, value: &str) {
+        let value = value.trim();
+        let alerts = config.alerts.get_or_insert_with(Default::default);
+        let retention = config.retention.get_or_insert_with(Default::default);
+
+        if let Some(threshold) = self.threshold(alerts) {
+            if value.is_empty() {
+                *threshold = None;
+            } else if let Ok(x) = value.parse() {
+                *threshold = Some(x);
+            }
+        } else if let Some(count) = self.count(retention) {
+            if let Ok(x) = value.parse() {
+                *count = x;
+            }
+        } else if self == Setting::CacheMaxAge {
+            if let Ok(x) = value.parse() {
+                config.cache_max_age_ms = Some(x);
+            }
+        } else if let Ok(x) = value.parse() {
+            config.sampling_interval_ms = Some(x);
+        }
+    }
+}
+
 pub enum Message {
     Fail(Status),
-    Populate(CpuResponse, NetworkResponse),
+    Populate(Box<Update>),
+    DismissEvents,
     Connect,
     ChangeDestination(String),
+    ChangeToken(String),
+    SendSignal(u32, Signal),
+    Notify(String),
+    DismissNotice,
+    ListActions(Vec<Action>),
+    RunAction(String),
+    ReceiveActionOutput(Result<ActionOutput, Status>),
+    RequestPower(PowerOperation),
+    AskConfirmation(PowerOperation, String),
+    ConfirmPower,
+    CancelPower,
+    /// Settings loaded or applied, with a notice for the latter.
+    ReceiveSettings(RuntimeConfig, Option<String>),
+    ChangeSetting(Setting, String),
+    ToggleCollector(String, bool),
+    /// Sends the edited settings, saving them to the config file when set.
+    ApplySettings(bool),
 }
 
-impl From<Result<Response<Pack>, Status>> for Message {
-    fn from(value: Result<Response<Pack>, Status>) -> Self {
+impl From<Result<Update, Status>> for Message {
+    fn from(value: Result<Update, Status>) -> Self {
         match value {
-            Ok(x) => {
-                let Pack {
-                    cpu: Some(cpu),
-                    network: Some(network),
-                } = x.into_inner() else { unreachable!() };
-                Populate(cpu, network)
-            }
+            Ok(x) => Populate(Box::new(x)),
             Err(e) => Fail(e),
         }
     }
@@ -63,11 +365,10 @@ pub struct Props {
 
 impl Default for Model {
     fn default() -> Self {
-        Unloaded {
-            0: Common {
-                connection_address: Arc::new("http://orangepi:50525".to_string()),
-            },
-        }
+        Unloaded(Common {
+            connection_address: Arc::new("http://orangepi:50525".to_string()),
+            token: Arc::default(),
+        })
     }
 }
 
@@ -96,45 +397,59 @@ impl Model {
         &mut self,
         msg: Message,
         redraw: bool,
-        _ctx: &Context<Self>,
+        ctx: &Context<Self>,
     ) -> (Option<Message>, bool) {
         match (msg, self as &mut Self) {
-            (Populate(cpu, network), Connected(common, active)) => {
-                let mut array: SmallVec<_> = Default::default();
-                if let Some(t) = cpu.temperature {
-                    array.push(t)
+            (Populate(update), Connected(common, active)) => {
+                let mut dashboard = Box::<Dashboard>::default();
+                dashboard.receive(*update);
+                if !common.token.is_empty() {
+                    let mut client = common.client();
+                    // Services without actions answer with an error, nothing is shown then
+                    ctx.link().send_future(async move {
+                        ListActions(client.list_actions().await.unwrap_or_default())
+                    });
+                    let mut client = common.client();
+                    // Older services have no settings, the form is left out then
+                    ctx.link().send_future_batch(async move {
+                        client
+                            .get_config()
+                            .await
+                            .map(|config| ReceiveSettings(config, None))
+                            .into_iter()
+                            .collect::<Vec<_>>()
+                    });
                 }
 
                 *self = Populated {
                     active: active.clone(),
-                    cpu_temp_window: array,
-                    usage: cpu.usage,
-                    network_response: network,
+                    dashboard,
                     common: common.clone(),
                 };
                 (None, true)
             }
-            (
-                Populate(cpu, network),
-                Populated {
-                    cpu_temp_window,
-                    network_response,
-                    usage,
-                    ..
-                },
-            ) => {
-                if cpu_temp_window.len() == cpu_temp_window.inline_size() {
-                    cpu_temp_window.remove(0);
-                }
-                if let Some(t) = cpu.temperature {
-                    cpu_temp_window.push(t);
-                }
-                *usage = cpu.usage;
-                *network_response = network;
+            (Populate(update), Populated { dashboard, .. }) => {
+                dashboard.receive(*update);
+                (None, true)
+            }
+            (Populate(_), _) => (None, false),
+            (other, _) => (Some(other), redraw),
+        }
+    }
 
+    #[inline]
+    fn handle_dismiss_message(
+        &mut self,
+        msg: Message,
+        redraw: bool,
+        _ctx: &Context<Self>,
+    ) -> (Option<Message>, bool) {
+        match (msg, self as &mut Self) {
+            (DismissEvents, Populated { dashboard, .. }) => {
+                dashboard.events.clear();
                 (None, true)
             }
-            (Populate(_, _), _) => (None, false),
+            (DismissEvents, _) => (None, false),
             (other, _) => (Some(other), redraw),
         }
     }
@@ -148,8 +463,7 @@ impl Model {
     ) -> (Option<Message>, bool) {
         match (msg, self as &mut Self) {
             (Connect, Unloaded(common)) => {
-                let client = RpcClient::new(common.connection_address.as_ref().clone());
-                let (stream, handle) = client.connect();
+                let (stream, handle) = common.client().connect();
                 let upd_interval = ctx.props().update_interval;
                 ctx.link().send_stream(stream.and_then(move |x| async move {
                     prokio::time::sleep(upd_interval).await;
@@ -181,12 +495,152 @@ impl Model {
     ) -> (Option<Message>, bool) {
         match (msg, self as &mut Self) {
             (ChangeDestination(destination), Unloaded(common)) => {
-                *common = Common {
-                    connection_address: Arc::new(destination),
-                };
+                common.connection_address = Arc::new(destination);
                 (None, false)
             }
-            (ChangeDestination(_), _) => (None, false),
+            (ChangeToken(token), Unloaded(common)) => {
+                common.token = Arc::new(token);
+                (None, false)
+            }
+            (ChangeDestination(_) | ChangeToken(_), _) => (None, false),
+            (other, _) => (Some(other), redraw),
+        }
+    }
+
+    #[inline]
+    fn handle_admin_message(
+        &mut self,
+        msg: Message,
+        redraw: bool,
+        ctx: &Context<Self>,
+    ) -> (Option<Message>, bool) {
+        match (msg, self as &mut Self) {
+            (SendSignal(pid, signal), Populated { common, .. }) => {
+                let mut client = common.client();
+                ctx.link().send_future(async move {
+                    match client.signal(pid, signal).await {
+                        Ok(()) => Notify(format!("Sent {} to PID {pid}", signal.as_str_name())),
+                        Err(e) => Notify(format!("Cannot signal PID {pid}: {}", e.message())),
+                    }
+                });
+                (None, false)
+            }
+            (Notify(notice), Populated { dashboard, .. }) => {
+                dashboard.notice = Some(notice);
+                (None, true)
+            }
+            (DismissNotice, Populated { dashboard, .. }) => {
+                dashboard.notice = None;
+                (None, true)
+            }
+            (ListActions(actions), Populated { dashboard, .. }) => {
+                dashboard.actions = actions;
+                (None, true)
+            }
+            (RunAction(name), Populated { common, dashboard, .. }) => {
+                dashboard.action_run = Some(ActionRun {
+                    name: name.clone(),
+                    ..Default::default()
+                });
+                let mut client = common.client();
+                let outputs = futures::stream::once(async move { client.exec(name).await })
+                    .map(|x| match x {
+                        Ok(outputs) => outputs.left_stream(),
+                        Err(e) => futures::stream::iter([Err(e)]).right_stream(),
+                    })
+                    .flatten()
+                    .map(ReceiveActionOutput);
+                ctx.link().send_stream(outputs);
+                (None, true)
+            }
+            (ReceiveActionOutput(output), Populated { dashboard, .. }) => {
+                if let Some(run) = &mut dashboard.action_run {
+                    run.receive(output);
+                }
+                (None, true)
+            }
+            (RequestPower(operation), Populated { common, .. }) => {
+                let mut client = common.client();
+                ctx.link().send_future(async move {
+                    match client.power(operation.clone(), POWER_DELAY_SECS, String::new()).await {
+                        Ok(x) => AskConfirmation(operation, x.confirm_token),
+                        Err(e) => Notify(format!("{operation} failed: {}", e.message())),
+                    }
+                });
+                (None, false)
+            }
+            (AskConfirmation(operation, token), Populated { dashboard, .. }) => {
+                dashboard.confirm = Some((operation, token));
+                (None, true)
+            }
+            (ConfirmPower, Populated { common, dashboard, .. }) => {
+                if let Some((operation, token)) = dashboard.confirm.take() {
+                    let mut client = common.client();
+                    ctx.link().send_future(async move {
+                        match client.power(operation.clone(), POWER_DELAY_SECS, token).await {
+                            Ok(_) => Notify(format!(
+                                "{operation}: scheduled in {POWER_DELAY_SECS} seconds"
+                            )),
+                            Err(e) => Notify(format!("{operation} failed: {}", e.message())),
+                        }
+                    });
+                }
+                (None, true)
+            }
+            (CancelPower, Populated { dashboard, .. }) => {
+                dashboard.confirm = None;
+                (None, true)
+            }
+            (ReceiveSettings(config, notice), Populated { dashboard, .. }) => {
+                dashboard.settings = Some(config);
+                if notice.is_some() {
+                    dashboard.notice = notice;
+                }
+                (None, true)
+            }
+            (ChangeSetting(setting, value), Populated { dashboard, .. }) => {
+                if let Some(config) = &mut dashboard.settings {
+                    setting.set(config, &value);
+                }
+                (None, true)
+            }
+            (ToggleCollector(name, enabled), Populated { dashboard, .. }) => {
+                let collectors = dashboard.settings.iter_mut().flat_map(|x| &mut x.collectors);
+                for collector in collectors.filter(|x| x.name == name) {
+                    collector.enabled = enabled;
+                }
+                (None, true)
+            }
+            (ApplySettings(persist), Populated { common, dashboard, .. }) => {
+                if let Some(config) = dashboard.settings.clone() {
+                    let mut client = common.client();
+                    ctx.link().send_future(async move {
+                        match client.update_config(config, persist).await {
+                            Ok(config) if persist => ReceiveSettings(config, Some("Settings applied and saved".to_owned())),
+                            Ok(config) => ReceiveSettings(config, Some("Settings applied".to_owned())),
+                            Err(e) => Notify(format!("Cannot apply the settings: {}", e.message())),
+                        }
+                    });
+                }
+                (None, false)
+            }
+            (
+                SendSignal(..)
+                | Notify(_)
+                | DismissNotice
+                | ListActions(_)
+                | RunAction(_)
+                | ReceiveActionOutput(_)
+                | RequestPower(_)
+                | AskConfirmation(..)
+                | ConfirmPower
+                | CancelPower
+                | ReceiveSettings(..)
+                | ChangeSetting(..)
+                | ToggleCollector(..)
+                | ApplySettings(_),
+                _,
+            ) => (None, false),
             (other, _) => (Some(other), redraw),
         }
     }
@@ -209,8 +663,10 @@ impl Component for Model {
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         let handlers = [
             Model::handle_populate_message,
+            Model::handle_dismiss_message,
             Model::handle_connect_message,
             Model::handle_change_message,
+            Model::handle_admin_message,
             Model::handle_fail_message,
         ];
         let mut msg = Some(msg);
@@ -231,87 +687,54 @@ impl Component for Model {
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         match self {
-            Connected(common, _) => unloaded_view(ctx, common.connection_address.clone(), true),
-            Unloaded(form) => unloaded_view(ctx, form.connection_address.clone(), false),
+            Connected(common, _) => unloaded_view(
+                ctx,
+                common.connection_address.clone(),
+                common.token.clone(),
+                true,
+            ),
+            Unloaded(form) => unloaded_view(
+                ctx,
+                form.connection_address.clone(),
+                form.token.clone(),
+                false,
+            ),
             Failed(_, e) => failed_view(e, ctx),
             Populated {
-                cpu_temp_window,
-                usage,
-                network_response,
-                common,
-                ..
+                dashboard, common, ..
             } => populated_view(
                 ctx,
-                cpu_temp_window,
-                usage,
-                network_response,
+                dashboard,
                 common.connection_address.clone(),
+                !common.token.is_empty(),
             ),
-            Populated {
-                active: _,
-                cpu_temp_window,
-                usage,
-                network_response,
-                common,
-            } => html! {
-                <div>
-                    <div class="uk-card-secondary
-                                uk-card-body 
-                                uk-flex 
-                                uk-flex-between 
-                                uk-card-hover 
-                                uk-flex-middle 
-                                uk-padding-small">
-                        <p class="uk-margin-remove-bottom">
-                            {"Connected to "}
-                            <a href={ common.connection_address.as_ref().clone() }>{"device"}</a>
-                            {" at "}{ common.connection_address.as_ref().clone() }
-                        </p>
-                        <button onclick={ctx.link().callback(|_| Connect)}
-                                class="uk-button-danger uk-padding-small">
-                            {"Disconnect"}
-                        </button>
-                    </div>
-                    <div class="uk-card-default uk-card-body uk-padding uk-margin uk-grid-item-match">
-                        <h2 class="uk-card-title">{"CPU properties:"}</h2>
-                        <div class="uk-grid uk-child-width-expand@s uk-grid-divider">
-                            <div class="uk-card uk-card-body">
-                                {"Current temperature: "}
-                            </div>
-                            <div class="uk-card uk-card-body">
-                                <p>{"Usage: "}</p>
-                                {usage.iter().map(|&x|
-                                    html! { <Bar fill={x} class="uk-light uk-margin-small"/> }
-                                ).collect::<Html>()}
-                            </div>
-                        </div>
-                    </div>
-                    <div class="uk-card-default uk-card-body uk-padding uk-margin">
-                        <h2 class="uk-card-title">{"Available networks:"}</h2>
-                        <table class="uk-table uk-table-divider">
-                            <thead>
-                                <tr>
-                                {network_response.interfaces.iter().map(|x| html!{
-                                    <th>{x.name.clone()}</th>
-                                }).collect::<Html>()}
-                                </tr>
-                            </thead>
-                            <tbody>
-                                <tr>
-                                    {network_response.interfaces.iter().map(|x| html!{
-                                        <td>{"Bytes in: "}{x.bytes_in}</td>
-                                    }).collect::<Html>()}
-                                </tr>
-                                <tr>
-                                    {network_response.interfaces.iter().map(|x| html!{
-                                        <td>{"Bytes out: "}{x.bytes_out}</td>
-                                    }).collect::<Html>()}
-                                </tr>
-                            </tbody>
-                        </table>
-                    </div>
-                </div>
-            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Setting;
+    use common::monitoring::RuntimeConfig;
+
+    #[test]
+    fn test_settings_form() {
+        let mut config = RuntimeConfig {
+            sampling_interval_ms: Some(100),
+            ..Default::default()
+        };
+
+        Setting::SamplingInterval.set(&mut config, "250");
+        Setting::TemperatureAlert.set(&mut config, " 85.5");
+        Setting::KeptEvents.set(&mut config, "many");
+        Setting::CacheMaxAge.set(&mut config, "1000");
+        assert_eq!(Setting::SamplingInterval.get(&config), "250");
+        assert_eq!(Setting::TemperatureAlert.get(&config), "85.5");
+        assert_eq!(Setting::CpuUsageAlert.get(&config), "");
+        assert_eq!(Setting::KeptEvents.get(&config), "0");
+        assert_eq!(config.cache_max_age_ms, Some(1000));
+
+        Setting::TemperatureAlert.set(&mut config, "");
+        assert_eq!(config.alerts.unwrap().temperature_celsius, None);
+    }
+}
@@ -1,13 +1,21 @@
 use crate::average_plot::AveragePlot;
 use crate::bar::Bar;
-use crate::model::Message::{ChangeDestination, Connect};
-use crate::model::Model;
-use common::monitoring::NetworkResponse;
-use smallvec::SmallVec;
+use crate::line_plot::{LinePlot, Series};
+use crate::client::PowerOperation;
+use crate::model::Message::{
+    ApplySettings, CancelPower, ChangeDestination, ChangeSetting, ChangeToken, ConfirmPower,
+    Connect, DismissEvents, DismissNotice, RequestPower, RunAction, SendSignal, ToggleCollector,
+};
+use crate::model::{ActionRun, Dashboard, DiskHistory, Model, Setting, Throttling};
+use common::monitoring::{
+    cgroup, ActionExit, Cgroup, CpuCore, DiskWear, Event, NetworkResponse, ProcessSummary,
+    RuntimeConfig, Severity, Signal, SystemdUnit,
+};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tonic::Status;
 use ybc::*;
-use yew::{function_component, html, Context, Html, Properties};
+use yew::{function_component, html, Context, Html, MouseEvent};
 
 #[function_component]
 pub(crate) fn ConnectedView() -> Html {
@@ -16,9 +24,15 @@ pub(crate) fn ConnectedView() -> Html {
     }
 }
 
-pub(crate) fn unloaded_view(ctx: &Context<Model>, destination: Arc<String>, loading: bool) -> Html {
+pub(crate) fn unloaded_view(
+    ctx: &Context<Model>,
+    destination: Arc<String>,
+    token: Arc<String>,
+    loading: bool,
+) -> Html {
     let onclick = ctx.link().callback(|_| Connect);
     let oninput = ctx.link().callback(ChangeDestination);
+    let ontoken = ctx.link().callback(ChangeToken);
 
     html! {
         <Tile ctx={TileCtx::Ancestor}>
@@ -33,6 +47,11 @@ pub(crate) fn unloaded_view(ctx: &Context<Model>, destination: Arc<String>, load
                         <Button classes="is-primary"
                                 {loading} disabled={loading} {onclick}>{"Connect"}</Button>
                     </div>
+                    <Input update={ontoken} name="Token" r#type={InputType::Password}
+                            placeholder="Admin token, optional"
+                            {loading} disabled={loading}
+                            value={token.as_ref().clone()}
+                            classes="mt-3"/>
                 </Tile>
             </Tile>
         </Tile>
@@ -74,25 +93,183 @@ fn connected_to_view(ctx: &Context<Model>, connected_to: Arc<String>) -> Html {
     }
 }
 
-fn cpu_view(ctx: &Context<Model>, temperature: &SmallVec<[f32; 60]>, usage: &Vec<f32>) -> Html {
+/// Time of day in UTC, the service and the viewer may be in different time zones.
+fn format_time(event: &Event) -> String {
+    let seconds = event
+        .time
+        .as_ref()
+        .map_or(0, |x| x.seconds.rem_euclid(24 * 60 * 60));
+
+    format!(
+        "{:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn events_view(ctx: &Context<Model>, events: &[Event]) -> Html {
+    if events.is_empty() {
+        return html! {};
+    }
+    let class = if events.iter().any(|x| x.severity() == Severity::Critical) {
+        "notification is-danger"
+    } else {
+        "notification is-warning"
+    };
+
+    html! {
+        <Tile ctx={TileCtx::Parent}>
+            <Tile ctx={TileCtx::Child} classes={class}>
+                <button class="delete" onclick={ctx.link().callback(|_| DismissEvents)}></button>
+                {events.iter().rev().map(|x| html! {
+                    <p><strong>{format_time(x)}</strong>{" "}{&x.message}</p>
+                }).collect::<Html>()}
+            </Tile>
+        </Tile>
+    }
+}
+
+fn notice_view(ctx: &Context<Model>, notice: &Option<String>) -> Html {
+    let Some(notice) = notice else {
+        return html! {};
+    };
+
+    html! {
+        <Tile ctx={TileCtx::Parent}>
+            <Tile ctx={TileCtx::Child} classes="notification is-info is-light">
+                <button class="delete" onclick={ctx.link().callback(|_| DismissNotice)}></button>
+                {notice}
+            </Tile>
+        </Tile>
+    }
+}
+
+fn confirm_view(ctx: &Context<Model>, confirm: &Option<(PowerOperation, String)>) -> Html {
+    let Some((operation, _)) = confirm else {
+        return html! {};
+    };
+
+    html! {
+        <Tile ctx={TileCtx::Parent}>
+            <Tile ctx={TileCtx::Child} classes="notification is-warning is-light">
+                <p class="mb-2">{format!("{operation}? Everyone watching the board is told first.")}</p>
+                <div class="buttons">
+                    <button class="button is-danger" onclick={ctx.link().callback(|_| ConfirmPower)}>
+                        {"Confirm"}
+                    </button>
+                    <button class="button" onclick={ctx.link().callback(|_| CancelPower)}>
+                        {"Cancel"}
+                    </button>
+                </div>
+            </Tile>
+        </Tile>
+    }
+}
+
+fn throttling_view(throttling: Throttling) -> Html {
+    let class = if throttling.throttled {
+        "has-text-danger"
+    } else {
+        "has-text-grey"
+    };
+
+    html! {
+        <p {class}>
+            {if throttling.throttled { "Throttling now. " } else { "" }}
+            {format!(
+                "Throttled for {:.1} s in {} events",
+                throttling.throttled_ms as f64 / 1000.0,
+                throttling.events
+            )}
+        </p>
+    }
+}
+
+/// Usage bars of the cores of one cluster.
+#[derive(Debug, PartialEq)]
+struct Cluster {
+    title: Option<String>,
+    usage: Vec<(u32, f32)>,
+}
+
+/// Groups usage by cluster. Without topology every core goes to one untitled group.
+fn clusters(usage: &[f32], cores: &[CpuCore]) -> Vec<Cluster> {
+    if cores.is_empty() {
+        return vec![Cluster {
+            title: None,
+            usage: usage
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| (i as u32, x))
+                .collect(),
+        }];
+    }
+
+    let mut clusters: Vec<(u32, Cluster)> = vec![];
+    for (core, &usage) in cores.iter().filter(|x| x.online).zip(usage) {
+        let index = match clusters.iter().position(|x| x.0 == core.cluster_id) {
+            Some(index) => index,
+            None => {
+                let mut title = format!("Cluster {}", core.cluster_id);
+                if !core.part.is_empty() {
+                    title += &format!(", {}", core.part);
+                }
+                if core.max_frequency > 0 {
+                    title += &format!(", up to {} MHz", core.max_frequency);
+                }
+                clusters.push((
+                    core.cluster_id,
+                    Cluster {
+                        title: Some(title),
+                        usage: vec![],
+                    },
+                ));
+                clusters.len() - 1
+            }
+        };
+        clusters[index].1.usage.push((core.id, usage));
+    }
+    clusters.into_iter().map(|x| x.1).collect()
+}
+
+fn usage_view(usage: &[f32], cores: &[CpuCore]) -> Html {
+    clusters(usage, cores)
+        .into_iter()
+        .map(|cluster| {
+            html! {
+                <>
+                    {cluster.title.map(|x| html!{ <Subtitle classes="mt-3 mb-1">{x}</Subtitle> })}
+                    {cluster.usage.into_iter().map(|(id, x)| html!{
+                        <Bar fill={x} name={Some(format!("cpu{id}"))} class="my-2"/>
+                    }).collect::<Html>()}
+                </>
+            }
+        })
+        .collect()
+}
+
+fn cpu_view(dashboard: &Dashboard) -> Html {
     html! {
         <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
             <Title>{"Cpu properties"}</Title>
             <Tile ctx={TileCtx::Parent}>
                 <Tile ctx={TileCtx::Child} classes="box">
                     <Title>{"Current temperature"}</Title>
-                    <AveragePlot y_data={temperature.to_vec()}
+                    <AveragePlot y_data={dashboard.cpu_temp_window.to_vec()}
                                  x_name={"Time, sec"}
                                  y_name={"Temperature, °C"}
                                  main_series_name={"Temperature"}
                                  main_series_color={(0x37, 0x6c, 0x5f)}
                                  avg_series_name={"Average temperature"}
                                  avg_series_color={(0x47, 0x89, 0x78)}
+                                 highlighted={dashboard.cpu_throttled_window.clone()}
                     />
+                    { throttling_view(dashboard.throttling) }
                 </Tile>
                 <Tile ctx={TileCtx::Child} classes="box">
                     <Title>{"Usage"}</Title>
-                    {usage.iter().map(|&x| html!{ <Bar fill={x} class="my-2"/> }).collect::<Html>()}
+                    { usage_view(&dashboard.usage, &dashboard.cores) }
                 </Tile>
             </Tile>
         </Tile>
@@ -132,20 +309,724 @@ fn network_view(network: &NetworkResponse) -> Html {
     }
 }
 
+fn series<'a>(windows: impl IntoIterator<Item = (&'a str, &'a Vec<f32>)>) -> Vec<Series> {
+    let colors = [
+        (0x37, 0x6c, 0x5f),
+        (0x48, 0x5f, 0xc7),
+        (0xf1, 0x46, 0x68),
+        (0xff, 0xb7, 0x0f),
+        (0x3e, 0x8e, 0xd0),
+    ];
+
+    windows
+        .into_iter()
+        .zip(colors.into_iter().cycle())
+        .map(|((name, data), color)| Series {
+            name: name.to_owned(),
+            color,
+            data: data.clone(),
+        })
+        .collect()
+}
+
+/// Formats a byte count with a binary prefix.
+fn format_bytes(bytes: f64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", units[unit])
+    } else {
+        format!("{value:.1} {}", units[unit])
+    }
+}
+
+fn disks_view(dashboard: &Dashboard) -> Html {
+    if dashboard.disks.is_empty() {
+        return html! {};
+    }
+    let history = |f: fn(&DiskHistory) -> &Vec<f32>| {
+        series(
+            dashboard
+                .disk_windows
+                .iter()
+                .map(|(name, x)| (name.as_str(), f(x))),
+        )
+    };
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Disks"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <Table fullwidth=true>
+                        <thead>
+                            <tr>
+                                <th>{"Device"}</th>
+                                <th>{"Read"}</th>
+                                <th>{"Write"}</th>
+                                <th>{"IOPS, r/w"}</th>
+                                <th>{"Latency"}</th>
+                                <th>{"Queue"}</th>
+                                <th>{"Busy"}</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {dashboard.disks.iter().map(|x| html!{
+                                <tr>
+                                    <td>{&x.name}</td>
+                                    <td>{format_bytes(x.read_bytes_per_second as f64)}{"/s"}</td>
+                                    <td>{format_bytes(x.write_bytes_per_second as f64)}{"/s"}</td>
+                                    <td>{format!("{:.0} / {:.0}", x.read_iops, x.write_iops)}</td>
+                                    <td>{format!("{:.1} ms", x.average_latency_ms)}</td>
+                                    <td>{format!("{:.2}", x.queue_depth)}</td>
+                                    <td>{format!("{:.0}%", x.utilization)}</td>
+                                </tr>
+                            }).collect::<Html>()}
+                        </tbody>
+                    </Table>
+                </Tile>
+            </Tile>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <Title>{"Utilization"}</Title>
+                    <LinePlot series={history(|x| &x.utilization)}
+                              x_name={"Time, sec"}
+                              y_name={"Busy, %"}
+                              min_y_max={100.0}
+                    />
+                </Tile>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <Title>{"Throughput"}</Title>
+                    <LinePlot series={history(|x| &x.throughput)}
+                              x_name={"Time, sec"}
+                              y_name={"Read and written, MiB/s"}
+                    />
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+/// Worn devices are flagged before they run out of rated endurance.
+fn worn(wear: &DiskWear) -> bool {
+    wear.used_endurance.is_some_and(|x| x >= 80.0)
+        || wear.projected_days_left.is_some_and(|x| x < 90.0)
+}
+
+fn wear_view(wear: &[DiskWear]) -> Html {
+    if wear.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Disk health"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                {wear.iter().map(|x| {
+                    let classes = if worn(x) { "box has-background-warning-light" } else { "box" };
+                    html! {
+                        <Tile ctx={TileCtx::Child} {classes}>
+                            <Title size={HeaderSize::Is5}>{&x.device}</Title>
+                            <p>{"Written: "}{format_bytes(x.total_bytes_written as f64)}</p>
+                            <p>{"Today: "}{format_bytes(x.bytes_written_today as f64)}</p>
+                            <p>{"Average: "}{format_bytes(x.average_daily_bytes as f64)}{"/day"}</p>
+                            if let (Some(used), Some(endurance)) = (x.used_endurance, x.endurance_tbw) {
+                                <progress class={if worn(x) { "progress is-warning" } else { "progress is-info" }}
+                                          value={used.min(100.0).to_string()} max="100">
+                                    {format!("{used:.0}%")}
+                                </progress>
+                                <p class="has-text-grey">
+                                    {format!("{used:.1}% of {endurance} TBW used")}
+                                    if let Some(days) = x.projected_days_left {
+                                        {format!(", about {days:.0} days left")}
+                                    }
+                                </p>
+                            } else {
+                                <p class="has-text-grey">{"No endurance rating configured"}</p>
+                            }
+                        </Tile>
+                    }
+                }).collect::<Html>()}
+            </Tile>
+        </Tile>
+    }
+}
+
+/// Containers and systemd units, the largest memory users first.
+fn top_cgroups(cgroups: &[Cgroup]) -> Vec<&Cgroup> {
+    let mut top = cgroups
+        .iter()
+        .filter(|x| x.kind() != cgroup::Kind::Other)
+        .collect::<Vec<_>>();
+    top.sort_by_key(|x| std::cmp::Reverse(x.memory_current));
+    top
+}
+
+fn cgroups_view(cgroups: &[Cgroup]) -> Html {
+    let top = top_cgroups(cgroups);
+    if top.is_empty() {
+        return html! {};
+    }
+    let kind = |x: &Cgroup| match x.kind() {
+        cgroup::Kind::Docker => "docker",
+        cgroup::Kind::Podman => "podman",
+        cgroup::Kind::Systemd => "systemd",
+        cgroup::Kind::Other => "",
+    };
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Containers and services"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <Table fullwidth=true>
+                        <thead>
+                            <tr>
+                                <th>{"Name"}</th>
+                                <th>{"Kind"}</th>
+                                <th>{"CPU"}</th>
+                                <th>{"Memory"}</th>
+                                <th>{"Read"}</th>
+                                <th>{"Write"}</th>
+                                <th>{"PIDs"}</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {top.into_iter().map(|x| html!{
+                                <tr title={x.path.clone()}>
+                                    <td>{&x.name}</td>
+                                    <td>{kind(x)}</td>
+                                    <td>{format!("{:.1}%", x.cpu_usage)}</td>
+                                    <td>
+                                        {format_bytes(x.memory_current as f64)}
+                                        if let Some(max) = x.memory_max {
+                                            {" of "}{format_bytes(max as f64)}
+                                        }
+                                    </td>
+                                    <td>{format_bytes(x.io_read_bytes_per_second as f64)}{"/s"}</td>
+                                    <td>{format_bytes(x.io_write_bytes_per_second as f64)}{"/s"}</td>
+                                    <td>{x.pids_current}</td>
+                                </tr>
+                            }).collect::<Html>()}
+                        </tbody>
+                    </Table>
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+fn units_view(ctx: &Context<Model>, units: &[SystemdUnit], admin: bool) -> Html {
+    if units.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Services"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <Table fullwidth=true>
+                        <thead>
+                            <tr>
+                                <th>{"Unit"}</th>
+                                <th>{"State"}</th>
+                                <th>{"Restarts"}</th>
+                                <th>{"PID"}</th>
+                                <th>{"Memory"}</th>
+                                <th>{"CPU time"}</th>
+                                if admin {
+                                    <th></th>
+                                }
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {units.iter().map(|x| {
+                                let class = (x.active_state == "failed").then_some("has-background-danger-light");
+                                let optional = |x: Option<String>| x.unwrap_or_else(|| "-".to_owned());
+                                let name = x.name.clone();
+                                html!{
+                                    <tr {class} title={x.description.clone()}>
+                                        <td>{&x.name}</td>
+                                        <td>{format!("{} ({})", x.active_state, x.sub_state)}</td>
+                                        <td>{optional(x.restarts.map(|x| x.to_string()))}</td>
+                                        <td>{optional(x.main_pid.map(|x| x.to_string()))}</td>
+                                        <td>{optional(x.memory.map(|x| format_bytes(x as f64)))}</td>
+                                        <td>{optional(x.cpu_time_ms.map(|x| format!("{:.1} s", x as f64 / 1000.0)))}</td>
+                                        if admin && x.name.ends_with(".service") {
+                                            <td>
+                                                <button class="button is-small is-light py-0"
+                                                        onclick={ctx.link().callback(move |_| RequestPower(PowerOperation::RestartService(name.clone())))}>
+                                                    {"Restart"}
+                                                </button>
+                                            </td>
+                                        }
+                                    </tr>
+                                }
+                            }).collect::<Html>()}
+                        </tbody>
+                    </Table>
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+/// A process with its descendants.
+struct ProcessNode<'a> {
+    process: &'a ProcessSummary,
+    children: Vec<ProcessNode<'a>>,
+    /// Processes in the subtree, this one included.
+    count: usize,
+    /// Summed over the subtree.
+    cpu_usage: f32,
+}
+
+fn process_node<'a>(
+    process: &'a ProcessSummary,
+    children: &HashMap<u32, Vec<&'a ProcessSummary>>,
+) -> ProcessNode<'a> {
+    let children: Vec<_> = children
+        .get(&process.pid)
+        .map(|x| x.iter().map(|x| process_node(x, children)).collect())
+        .unwrap_or_default();
+
+    ProcessNode {
+        process,
+        count: 1 + children.iter().map(|x| x.count).sum::<usize>(),
+        cpu_usage: process.cpu_usage + children.iter().map(|x| x.cpu_usage).sum::<f32>(),
+        children,
+    }
+}
+
+/// Builds the tree from parent PIDs. Processes whose parent is unknown become roots.
+fn process_tree(processes: &[ProcessSummary]) -> Vec<ProcessNode<'_>> {
+    let pids = processes.iter().map(|x| x.pid).collect::<HashSet<_>>();
+    let mut children = HashMap::<u32, Vec<&ProcessSummary>>::new();
+    let mut roots = vec![];
+
+    for process in processes {
+        match process.parent {
+            Some(parent) if parent != process.pid && pids.contains(&parent) => {
+                children.entry(parent).or_default().push(process)
+            }
+            _ => roots.push(process),
+        }
+    }
+    roots.into_iter().map(|x| process_node(x, &children)).collect()
+}
+
+fn signal_buttons(ctx: &Context<Model>, pid: u32) -> Html {
+    let button = |signal: Signal, title: &'static str| {
+        // Buttons sit inside `summary`, which would toggle the subtree otherwise
+        let onclick = ctx.link().callback(move |e: MouseEvent| {
+            e.prevent_default();
+            SendSignal(pid, signal)
+        });
+        html! {
+            <button class="button is-small is-light ml-1 py-0" {title} {onclick}>
+                {signal.as_str_name()}
+            </button>
+        }
+    };
+
+    html! {
+        <>
+            {button(Signal::Hup, "Reload or restart")}
+            {button(Signal::Term, "Terminate")}
+            {button(Signal::Kill, "Kill")}
+        </>
+    }
+}
+
+fn process_node_view(ctx: &Context<Model>, node: &ProcessNode, depth: usize, admin: bool) -> Html {
+    let process = node.process;
+    let row = html! {
+        <span>
+            <b>{&process.name}</b>
+            <span class="has-text-grey">{format!(" {} ", process.pid)}</span>
+            {format!("{:.1}%, {}", process.cpu_usage, format_bytes(process.memory as f64))}
+            if !node.children.is_empty() {
+                <span class="has-text-grey">
+                    {format!(" / {} processes, {:.1}% in total", node.count, node.cpu_usage)}
+                </span>
+            }
+            if admin {
+                {signal_buttons(ctx, process.pid)}
+            }
+        </span>
+    };
+
+    if node.children.is_empty() {
+        return html! { <div key={process.pid} class="ml-4">{row}</div> };
+    }
+    // Only the initial state is set, yew leaves the attribute alone while it does not change
+    html! {
+        <details key={process.pid} open={depth == 0}>
+            <summary>{row}</summary>
+            <div class="ml-4">
+                {for node.children.iter().map(|x| process_node_view(ctx, x, depth + 1, admin))}
+            </div>
+        </details>
+    }
+}
+
+fn processes_view(ctx: &Context<Model>, processes: &[ProcessSummary], admin: bool) -> Html {
+    if processes.is_empty() {
+        return html! {};
+    }
+    let tree = process_tree(processes);
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Processes"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <div style="max-height: 40rem; overflow-y: auto">
+                        {for tree.iter().map(|x| process_node_view(ctx, x, 0, admin))}
+                    </div>
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+fn action_run_view(run: &ActionRun) -> Html {
+    let status = match (&run.error, &run.exit) {
+        (Some(error), _) => format!("{} failed: {error}", run.name),
+        (None, Some(ActionExit { error: Some(error), .. })) => {
+            format!("{} could not be waited for: {error}", run.name)
+        }
+        (None, Some(exit)) if exit.timed_out => format!("{} timed out", run.name),
+        (None, Some(exit)) => match exit.code {
+            Some(code) => format!("{} exited with {code}", run.name),
+            None => format!("{} was killed by a signal", run.name),
+        },
+        (None, None) => format!("{} is running", run.name),
+    };
+    let class = match (&run.error, &run.exit) {
+        (None, Some(exit)) if exit.code == Some(0) => "has-text-success",
+        (None, None) => "has-text-grey",
+        _ => "has-text-danger",
+    };
+
+    html! {
+        <>
+            <p {class}>{status}</p>
+            if !run.output.is_empty() {
+                <pre style="max-height: 20rem; overflow-y: auto">{&run.output}</pre>
+            }
+        </>
+    }
+}
+
+/// Only admin sessions get the list of actions.
+fn actions_view(ctx: &Context<Model>, dashboard: &Dashboard) -> Html {
+    if dashboard.actions.is_empty() {
+        return html! {};
+    }
+    let running = dashboard.action_run.as_ref().is_some_and(ActionRun::running);
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Actions"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <div class="buttons">
+                        {for dashboard.actions.iter().map(|action| {
+                            let name = action.name.clone();
+                            html! {
+                                <button class="button is-light"
+                                        title={action.description.clone()}
+                                        disabled={running}
+                                        onclick={ctx.link().callback(move |_| RunAction(name.clone()))}>
+                                    {&action.name}
+                                </button>
+                            }
+                        })}
+                    </div>
+                    if let Some(run) = &dashboard.action_run {
+                        {action_run_view(run)}
+                    }
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+fn power_view(ctx: &Context<Model>) -> Html {
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Board"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <div class="buttons">
+                        <button class="button is-light" onclick={ctx.link().callback(|_| RequestPower(PowerOperation::Reboot))}>
+                            {"Reboot"}
+                        </button>
+                        <button class="button is-light" onclick={ctx.link().callback(|_| RequestPower(PowerOperation::Shutdown))}>
+                            {"Shut down"}
+                        </button>
+                    </div>
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+fn settings_view(ctx: &Context<Model>, settings: &Option<RuntimeConfig>) -> Html {
+    let Some(settings) = settings else {
+        return html! {};
+    };
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Settings"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <div class="columns is-multiline">
+                        {for Setting::ALL.into_iter().map(|setting| html! {
+                            <div class="column is-one-third">
+                                <label class="label">{setting.label()}</label>
+                                <Input name={setting.label()}
+                                       value={setting.get(settings)}
+                                       placeholder="Not checked"
+                                       update={ctx.link().callback(move |x| ChangeSetting(setting, x))}/>
+                            </div>
+                        })}
+                    </div>
+                    <label class="label">{"Collectors"}</label>
+                    <div class="mb-3">
+                        {for settings.collectors.iter().map(|collector| {
+                            let name = collector.name.clone();
+                            html! {
+                                <Checkbox name={collector.name.clone()}
+                                          checked={collector.enabled}
+                                          classes="mr-4"
+                                          update={ctx.link().callback(move |x| ToggleCollector(name.clone(), x))}>
+                                    {format!(" {}", collector.name)}
+                                </Checkbox>
+                            }
+                        })}
+                    </div>
+                    <div class="buttons">
+                        <button class="button is-light" onclick={ctx.link().callback(|_| ApplySettings(false))}>
+                            {"Apply"}
+                        </button>
+                        <button class="button is-light" onclick={ctx.link().callback(|_| ApplySettings(true))}>
+                            {"Apply and save"}
+                        </button>
+                    </div>
+                </Tile>
+            </Tile>
+        </Tile>
+    }
+}
+
+fn load_view(dashboard: &Dashboard) -> Html {
+    let Some(load) = &dashboard.load else {
+        return html! {};
+    };
+    let has_pressure = load.cpu.is_some() || load.memory.is_some() || load.io.is_some();
+
+    html! {
+        <Tile ctx={TileCtx::Parent} classes="is-flex is-flex-direction-column">
+            <Title>{"Load"}</Title>
+            <Tile ctx={TileCtx::Parent}>
+                <Tile ctx={TileCtx::Child} classes="box">
+                    <Title>{"Load average"}</Title>
+                    <LinePlot series={series(["1 min", "5 min", "15 min"].into_iter().zip(&dashboard.load_windows))}
+                              x_name={"Time, sec"}
+                              y_name={"Runnable tasks"}
+                    />
+                    <p class="has-text-grey">
+                        {format!("{} of {} tasks runnable", load.runnable_tasks, load.total_tasks)}
+                    </p>
+                </Tile>
+                if has_pressure {
+                    <Tile ctx={TileCtx::Child} classes="box">
+                        <Title>{"Pressure stall"}</Title>
+                        <LinePlot series={series(["CPU", "Memory", "IO"].into_iter().zip(&dashboard.pressure_windows))}
+                                  x_name={"Time, sec"}
+                                  y_name={"Stalled time over 10 sec, %"}
+                        />
+                    </Tile>
+                }
+            </Tile>
+        </Tile>
+    }
+}
+
 pub(crate) fn populated_view(
     ctx: &Context<Model>,
-    temperature_window: &SmallVec<[f32; 60]>,
-    usage: &Vec<f32>,
-    network: &NetworkResponse,
+    dashboard: &Dashboard,
     connected_to: Arc<String>,
+    admin: bool,
 ) -> Html {
     html! {
         <Tile ctx={TileCtx::Ancestor} classes="is-justify-content-center mt-1">
             <Tile vertical=true size={TileSize::Eight}>
                 { connected_to_view(ctx, connected_to) }
-                { cpu_view(ctx, temperature_window, usage) }
-                { network_view(network) }
+                { events_view(ctx, &dashboard.events) }
+                { notice_view(ctx, &dashboard.notice) }
+                { confirm_view(ctx, &dashboard.confirm) }
+                { actions_view(ctx, dashboard) }
+                if admin {
+                    { power_view(ctx) }
+                    { settings_view(ctx, &dashboard.settings) }
+                }
+                { cpu_view(dashboard) }
+                { load_view(dashboard) }
+                { disks_view(dashboard) }
+                { wear_view(&dashboard.wear) }
+                { units_view(ctx, &dashboard.units, admin) }
+                { processes_view(ctx, &dashboard.processes, admin) }
+                { cgroups_view(&dashboard.cgroups) }
+                { network_view(&dashboard.network_response) }
             </Tile>
         </Tile>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::view::{
+        clusters, format_bytes, format_time, process_tree, top_cgroups, worn, Cluster,
+    };
+    use common::monitoring::{cgroup, Cgroup, CpuCore, DiskWear, Event, ProcessSummary};
+
+    fn core(id: u32, cluster_id: u32, online: bool, part: &str) -> CpuCore {
+        CpuCore {
+            id,
+            cluster_id,
+            online,
+            part: part.to_owned(),
+            max_frequency: if cluster_id == 0 { 1800 } else { 2400 },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_usage_is_grouped_by_cluster() {
+        let cores = [
+            core(0, 0, true, "Cortex-A55"),
+            core(1, 0, true, "Cortex-A55"),
+            core(4, 4, false, "Cortex-A76"),
+            core(5, 4, true, "Cortex-A76"),
+        ];
+
+        assert_eq!(
+            clusters(&[10.0, 20.0, 100.0], &cores),
+            vec![
+                Cluster {
+                    title: Some("Cluster 0, Cortex-A55, up to 1800 MHz".to_owned()),
+                    usage: vec![(0, 10.0), (1, 20.0)],
+                },
+                Cluster {
+                    title: Some("Cluster 4, Cortex-A76, up to 2400 MHz".to_owned()),
+                    usage: vec![(5, 100.0)],
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(3.0 * 1024.0 * 1024.0 * 1024.0), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_usage_without_topology() {
+        assert_eq!(
+            clusters(&[1.0, 2.0], &[]),
+            vec![Cluster {
+                title: None,
+                usage: vec![(0, 1.0), (1, 2.0)],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_worn_devices() {
+        let wear = |used_endurance, projected_days_left| DiskWear {
+            used_endurance,
+            projected_days_left,
+            ..Default::default()
+        };
+
+        assert!(!worn(&wear(None, None)));
+        assert!(!worn(&wear(Some(40.0), Some(400.0))));
+        assert!(worn(&wear(Some(85.0), Some(400.0))));
+        assert!(worn(&wear(Some(40.0), Some(30.0))));
+    }
+
+    #[test]
+    fn test_format_time() {
+        let event = Event {
+            time: Some(prost_types::Timestamp {
+                seconds: 3 * 24 * 60 * 60 + 13 * 3600 + 5 * 60 + 9,
+                nanos: 0,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(format_time(&event), "13:05:09 UTC");
+    }
+
+    #[test]
+    fn test_top_cgroups() {
+        let cgroup = |name: &str, kind: cgroup::Kind, memory_current| Cgroup {
+            name: name.to_owned(),
+            kind: kind.into(),
+            memory_current,
+            ..Default::default()
+        };
+        let cgroups = [
+            cgroup("/init.scope", cgroup::Kind::Other, 1 << 30),
+            cgroup("ssh.service", cgroup::Kind::Systemd, 1 << 20),
+            cgroup("grafana", cgroup::Kind::Docker, 1 << 28),
+        ];
+
+        let names = top_cgroups(&cgroups)
+            .into_iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["grafana", "ssh.service"]);
+    }
+
+    #[test]
+    fn test_process_tree() {
+        let process = |pid, parent| ProcessSummary {
+            pid,
+            parent,
+            cpu_usage: 1.0,
+            ..Default::default()
+        };
+        let processes = [
+            process(1, None),
+            process(2, Some(0)),
+            process(40, Some(1)),
+            process(41, Some(40)),
+            process(42, Some(40)),
+            // Parent exited, the orphan is shown at the top
+            process(90, Some(77)),
+        ];
+
+        let tree = process_tree(&processes);
+        let roots = tree.iter().map(|x| x.process.pid).collect::<Vec<_>>();
+        assert_eq!(roots, [1, 2, 90]);
+        assert_eq!(tree[0].count, 4);
+        assert_eq!(tree[0].cpu_usage, 4.0);
+        let supervisor = &tree[0].children[0];
+        assert_eq!(supervisor.process.pid, 40);
+        assert_eq!(supervisor.children.len(), 2);
+    }
+}